@@ -4,6 +4,8 @@ const VERSION_TEMPLATE: &str = r#"
 pub const VERSION: &str = "{version}";
 pub const HW_VER: &str = "{hw}";
 pub const FIRMWARE: &str = "{firmware}";
+pub const BUILD_TIME: u64 = {build_time};
+pub const OTA_PUBKEY: Option<[u8; 32]> = {ota_pubkey};
 "#;
 
 fn main() {
@@ -11,27 +13,62 @@ fn main() {
         println!("cargo:rerun-if-changed=.env");
     }
 
+    let mut ota_pubkey = std::env::var("OTA_SIGN_PUBKEY").ok();
     if let Ok(mut iter) = dotenvy::dotenv_iter() {
         while let Some(Ok((key, value))) = iter.next() {
             println!("cargo:rustc-env={key}={value}");
+            if key == "OTA_SIGN_PUBKEY" && ota_pubkey.is_none() {
+                ota_pubkey = Some(value);
+            }
         }
     }
 
     println!("cargo:rustc-link-arg-bins=-Tlinkall.x");
     println!("cargo:rustc-cfg=feature=\"gen_version\"");
     println!("cargo:rerun-if-env-changed=RELEASE_BUILD");
+    println!("cargo:rerun-if-env-changed=OTA_SIGN_PUBKEY");
+    println!("cargo:rerun-if-env-changed=BUILD_TIME");
     println!("cargo:rerun-if-changed=src/");
 
+    // same value as passed to append_metadata.sh (release.sh)
+    let build_time = std::env::var("BUILD_TIME")
+        .ok()
+        .and_then(|time| time.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .expect("Cannot fail? (Getting epoch)")
+                .as_secs()
+        });
+
     let version_str = if let Ok(rel) = std::env::var("RELEASE_BUILD") {
         println!("cargo:rustc-cfg=feature=\"release_build\"");
         rel
     } else {
-        let epoch = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Cannot fail? (Getting epoch)")
-            .as_secs();
+        format!("D{build_time}")
+    };
 
-        format!("D{epoch}")
+    let ota_pubkey = match ota_pubkey.as_deref().map(str::trim) {
+        Some(hex) if !hex.is_empty() => {
+            let bytes = parse_pubkey(hex).unwrap_or_else(|| {
+                panic!("OTA_SIGN_PUBKEY must be 32 byte ed25519 public key (64 hex chars)!")
+            });
+
+            format!("Some({bytes:?})")
+        }
+        _ => {
+            if std::env::var("RELEASE_BUILD").is_ok() {
+                panic!("OTA_SIGN_PUBKEY is required for release builds!");
+            }
+
+            if std::env::var("CARGO_FEATURE_INSECURE_OTA").is_err() {
+                println!(
+                    "cargo:warning=OTA_SIGN_PUBKEY not set, OTA updates will be rejected (enable insecure_ota feature for development)"
+                );
+            }
+
+            "None".to_string()
+        }
     };
 
     let hw = if cfg!(feature = "v4") {
@@ -45,7 +82,9 @@ fn main() {
     let generated = VERSION_TEMPLATE
         .replace("{version}", &version_str)
         .replace("{hw}", hw)
-        .replace("{firmware}", "STATION");
+        .replace("{firmware}", "STATION")
+        .replace("{build_time}", &build_time.to_string())
+        .replace("{ota_pubkey}", &ota_pubkey);
 
     let Ok(out_dir) = std::env::var("OUT_DIR").map(PathBuf::from) else {
         panic!("Compiler should set OUT_DIR!");
@@ -55,3 +94,16 @@ fn main() {
         panic!("build.rs version.rs inside outdir ({out_dir:?}) failed to write!")
     });
 }
+
+fn parse_pubkey(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    Some(bytes)
+}
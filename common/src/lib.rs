@@ -0,0 +1,13 @@
+//! Code shared by firmware and simulator (`sim/`): server packets, runtime settings,
+//! translation lookup and text LCD buffer. Everything here is hardware independent,
+//! so simulator shows and sends exactly what the station would.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod lcd;
+pub mod packets;
+pub mod settings;
+pub mod time;
+pub mod translations;
@@ -0,0 +1,382 @@
+//! Packets exchanged with server (JSON over websocket)
+
+use alloc::{string::String, vec::Vec};
+use serde::{Deserialize, Serialize};
+
+/// Version of timer <-> server packet protocol (bump on every packet change):
+/// 1 - hello / hello_response
+/// 2 - server_urls, status_request / status_response
+/// 3 - set_settings / effective_settings, remote_command, set_log_config
+/// 4 - ota_resume, signed and zlib compressed OTA with metadata trailer
+/// 5 - time_sync_request / time_sync_response, signed solve `timestamp_ms`, 'M' crash log records
+/// 6 - attendance, attempt info with cutoff and cumulative limit, DNS penalty, add without secret
+/// 7 - structured log frames (sequence, uptime, level, target)
+pub const PROTOCOL_VERSION: u32 = 7;
+
+/// First protocol versions with packets that timer sends on its own
+pub const PROTOCOL_SETTINGS: u32 = 3;
+pub const PROTOCOL_OTA_RESUME: u32 = 4;
+pub const PROTOCOL_TIME_SYNC: u32 = 5;
+pub const PROTOCOL_STRUCTURED_LOGS: u32 = 7;
+
+/// Tag of delegate `Solve` request (server answers with `DelegateResponse` once
+/// delegate resolves the case)
+pub const DELEGATE_TAG: u64 = 69420;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimerPacket {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<u64>,
+    pub data: TimerPacketInner,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerPacketInner {
+    StartUpdate(StartUpdatePacket),
+    /// Sent after reconnect when update was interrupted (server continues from `offset`)
+    OtaResume {
+        version: String,
+        firmware: String,
+        size: u32,
+        crc: u32,
+        offset: u32,
+    },
+    Solve {
+        solve_time: u64,
+        penalty: i64,
+        competitor_id: u64,
+        judge_id: u64,
+        timestamp: u64,
+        #[serde(default)]
+        timestamp_ms: u64,
+        session_id: String, // UUID
+        delegate: bool,
+        inspection_time: i64,
+        group_id: String,
+        sign_key: u32,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hmac: Option<String>,
+    },
+    SolveConfirm(SolveConfirmPacket),
+    DelegateResponse(DelegateResponsePacket),
+    ApiError(ApiError),
+    CustomMessage {
+        line1: String,
+        line2: String,
+    },
+
+    CardInfoRequest {
+        card_id: u64,
+        is_competitor: bool,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        attendance_device: Option<bool>,
+
+        sign_key: u32,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hmac: Option<String>,
+    },
+    CardInfoResponse(CardInfoResponsePacket),
+    AttendanceMarked,
+    DeviceSettings {
+        added: bool,
+        locales: Vec<TranslationLocale>,
+        default_locale: String,
+        fkm_token: i32,
+        secure_rfid: bool,
+        auto_setup: bool,
+        sound_enabled: bool,
+    },
+    Battery {
+        level: Option<f64>,
+        voltage: Option<f64>,
+    },
+    Add {
+        firmware: String,
+        sign_key: u32,
+
+        /// Hex encoded 128-bit device secret (used for HMAC trust and packet signing).
+        /// Sent only once, when device is added over pinned connection.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        secret_key: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hmac: Option<String>,
+    },
+    EpochTime {
+        current_epoch: u64,
+    },
+    TimeSyncRequest {
+        uptime_ms: u64,
+    },
+    TimeSyncResponse {
+        uptime_ms: u64,
+        epoch_ms: u64,
+    },
+    SetDeviceSettings {
+        volume: Option<u8>,
+    },
+    DumpCrashLog,
+    Hello(HelloPacket),
+    HelloResponse {
+        protocol_version: u32,
+    },
+    ServerUrls {
+        urls: Vec<String>,
+    },
+    StatusRequest,
+    SetSettings {
+        version: u32,
+        settings: crate::settings::SettingsUpdate,
+    },
+    EffectiveSettings(crate::settings::Settings),
+    SetLogConfig {
+        config: LogConfig,
+
+        #[serde(default)]
+        persist: bool,
+    },
+    RemoteCommand {
+        command: RemoteCommandKind,
+        nonce: u64,
+        hmac: String,
+    },
+    RemoteCommandAck {
+        command: RemoteCommandKind,
+        accepted: bool,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    StatusResponse(StatusResponsePacket),
+
+    // packet for end to end testing
+    #[cfg(feature = "e2e")]
+    TestPacket(TestPacketData),
+
+    #[cfg(feature = "e2e")]
+    TestAck(SnapshotData),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StartUpdatePacket {
+    pub version: String,
+    pub build_time: u64,
+    pub size: u32,
+    pub crc: u32,
+    pub firmware: String,
+    /// Hex encoded ed25519 signature of sha256 digest of whole image
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Only sent if device advertised `ota_zlib` feature
+    #[serde(default)]
+    pub compression: Option<OtaCompression>,
+    /// Allows older image (requires `downgrade_signature`)
+    #[serde(default)]
+    pub allow_downgrade: bool,
+    /// Hex encoded ed25519 signature of `allow_downgrade|{firmware}|{hardware}|{version}`
+    #[serde(default)]
+    pub downgrade_signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OtaCompression {
+    Zlib,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloPacket {
+    pub protocol_version: u32,
+    pub features: Vec<String>,
+    pub packets: Vec<String>,
+    pub translations_count: usize,
+    pub ws_buf_size: usize,
+    pub tls_buf_size: usize,
+}
+
+/// Log levels are `log::LevelFilter` names ("off", "error", ..., "trace")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogConfig {
+    pub level: String,
+
+    #[serde(default)]
+    pub targets: Vec<LogTargetConfig>,
+}
+
+/// Target is module path prefix (for example `fkm_firmware::rfid`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogTargetConfig {
+    pub target: String,
+    pub level: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteCommandKind {
+    Reboot,
+    ClearSavedSolve,
+    ClearErrorLog,
+    ForgetBleBond,
+    FactoryReset,
+}
+
+impl RemoteCommandKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemoteCommandKind::Reboot => "reboot",
+            RemoteCommandKind::ClearSavedSolve => "clear_saved_solve",
+            RemoteCommandKind::ClearErrorLog => "clear_error_log",
+            RemoteCommandKind::ForgetBleBond => "forget_ble_bond",
+            RemoteCommandKind::FactoryReset => "factory_reset",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusResponsePacket {
+    pub uptime_ms: u64,
+    pub heap_used: usize,
+    pub heap_free: usize,
+    pub scene: String,
+    pub menu_scene: Option<String>,
+    pub battery_level: Option<u8>,
+    pub battery_voltage_mv: Option<f64>,
+    pub battery_current_ma: Option<i16>,
+    pub stackmat_connected: Option<bool>,
+    pub rfid_init: bool,
+    pub wifi_rssi: Option<i32>,
+    pub ws_rtt_ms: Option<u64>,
+    pub error_log_count: usize,
+    pub firmware: String,
+    pub hardware: String,
+    pub version: String,
+}
+
+#[cfg(feature = "e2e")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum TestPacketData {
+    HardStateReset,
+    ResetState,
+    ScanCard(u64),
+    ButtonPress { pin: u8, press_time: u64 },
+    StackmatTime(u64),
+    StackmatReset,
+}
+
+#[cfg(feature = "e2e")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SnapshotData {
+    pub scene: usize,
+    pub inspection_time: Option<u64>,
+    pub solve_time: Option<u64>,
+    pub penalty: Option<i8>,
+    pub time_confirmed: bool,
+    pub possible_groups: usize,
+    pub group_selected_idx: usize,
+    pub current_competitor: Option<u64>,
+    pub current_judge: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranslationLocale {
+    pub locale: String,
+    pub translations: Vec<TranslationRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranslationRecord {
+    pub key: String,
+    pub translation: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CardInfoResponsePacket {
+    pub card_id: u64,
+    pub display: String,
+    pub country_iso2: String,
+    pub can_compete: bool,
+    pub possible_groups: Vec<PossibleGroup>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PossibleGroup {
+    pub group_id: String,
+    pub name: String,
+    pub secondary_text: Option<String>,
+    pub use_inspection: bool,
+    pub limit: Option<u64>,
+
+    /// Result (ms) attempt has to beat, sent only while cutoff still has to be made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cutoff: Option<u64>,
+
+    /// Remaining cumulative time limit (ms) across attempts of the round
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cumulative_remaining: Option<u64>,
+
+    /// 1-based attempt number within the round
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt_number: Option<u8>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts_total: Option<u8>,
+
+    /// Competitor's results from earlier attempts of the round
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_attempts: Vec<PreviousAttempt>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviousAttempt {
+    pub solve_time: u64,
+    pub penalty: i64,
+}
+
+impl fkm_flow::Group for PossibleGroup {
+    fn use_inspection(&self) -> bool {
+        self.use_inspection
+    }
+
+    fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    fn cutoff(&self) -> Option<u64> {
+        self.cutoff
+    }
+
+    fn cumulative_remaining(&self) -> Option<u64> {
+        self.cumulative_remaining
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SolveConfirmPacket {
+    pub competitor_id: u64,
+    pub session_id: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DelegateResponsePacket {
+    pub should_scan_cards: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solve_time: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub penalty: Option<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApiError {
+    pub error: String,
+    pub should_reset_time: bool,
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "sleep")]
+pub const SLEEP_AFTER_MS: u64 = 60000 * 5;
+#[cfg(feature = "sleep")]
+pub const DEEPER_SLEEP_AFTER_MS: u64 = 60000 * 15;
+
+#[cfg(not(feature = "sleep"))]
+pub const SLEEP_AFTER_MS: u64 = 60000 * 9999;
+#[cfg(not(feature = "sleep"))]
+pub const DEEPER_SLEEP_AFTER_MS: u64 = 60000 * 99999;
+
+pub const LOG_SEND_INTERVAL_MS: u64 = 1000;
+
+pub const INSPECTION_TIME_DNF: u64 = 17000;
+pub const INSPECTION_TIME_PLUS2: u64 = 15000;
+
+pub const RFID_DUPLICATE_WINDOW_MS: u64 = 500;
+
+/// Version of runtime settings set
+pub const SETTINGS_VERSION: u32 = 3;
+
+/// Runtime tunable settings.
+/// `version` is bumped when fields are added, unknown fields are ignored
+/// and missing ones keep their current value.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub sleep_after_ms: u64,
+    pub deeper_sleep_after_ms: u64,
+    pub inspection_plus2_ms: u64,
+    pub inspection_dnf_ms: u64,
+    pub rfid_duplicate_window_ms: u64,
+    pub log_send_interval_ms: u64,
+
+    /// Station only reports attendance of scanned cards (no timing)
+    pub attendance_mode: bool,
+
+    /// Beep (v4) and flash at 8s/12s of inspection and at +2/DNF thresholds
+    pub inspection_warnings: bool,
+}
+
+impl Settings {
+    pub const DEFAULT: Self = Self {
+        version: SETTINGS_VERSION,
+        sleep_after_ms: SLEEP_AFTER_MS,
+        deeper_sleep_after_ms: DEEPER_SLEEP_AFTER_MS,
+        inspection_plus2_ms: INSPECTION_TIME_PLUS2,
+        inspection_dnf_ms: INSPECTION_TIME_DNF,
+        rfid_duplicate_window_ms: RFID_DUPLICATE_WINDOW_MS,
+        log_send_interval_ms: LOG_SEND_INTERVAL_MS,
+        attendance_mode: false,
+        inspection_warnings: false,
+    };
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Partial settings update pushed by server (None = keep current value)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SettingsUpdate {
+    pub sleep_after_ms: Option<u64>,
+    pub deeper_sleep_after_ms: Option<u64>,
+    pub inspection_plus2_ms: Option<u64>,
+    pub inspection_dnf_ms: Option<u64>,
+    pub rfid_duplicate_window_ms: Option<u64>,
+    pub log_send_interval_ms: Option<u64>,
+    pub attendance_mode: Option<bool>,
+    pub inspection_warnings: Option<bool>,
+}
@@ -0,0 +1,33 @@
+use crate::packets::PreviousAttempt;
+use alloc::{string::String, vec::Vec};
+
+pub fn ms_to_time_str(ms: u64) -> heapless::String<12> {
+    let minutes: u8 = (ms / 60000) as u8;
+    let seconds: u8 = ((ms % 60000) / 1000) as u8;
+    let ms: u16 = (ms % 1000) as u16;
+
+    let mut time_str = heapless::String::<12>::new();
+    let ms2 = ms / 10;
+    if minutes > 0 {
+        _ = time_str.push_str(&alloc::format!("{minutes}:{seconds:02}.{ms2:02}"));
+    } else {
+        _ = time_str.push_str(&alloc::format!("{seconds:01}.{ms2:02}"));
+    }
+
+    time_str
+}
+
+/// Previous results of the round (e.g. `12.34 DNF 11.02+2`)
+pub fn previous_attempts_str(attempts: &[PreviousAttempt]) -> String {
+    let results: Vec<String> = attempts
+        .iter()
+        .map(|attempt| match attempt.penalty {
+            -2 => "DNS".into(),
+            -1 => "DNF".into(),
+            1.. => alloc::format!("{}+{}", ms_to_time_str(attempt.solve_time), attempt.penalty),
+            _ => ms_to_time_str(attempt.solve_time).as_str().into(),
+        })
+        .collect();
+
+    results.join(" ")
+}
@@ -0,0 +1,117 @@
+//! Translation lookup. Fallback strings (and `TranslationKey` indices) are generated
+//! from `default_translation.json`, locales are pushed by server in `DeviceSettings`.
+
+use crate::packets::TranslationRecord;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+macros::load_default_translations!("src/default_translation.json");
+
+#[derive(Debug)]
+struct Locale {
+    locale: String,
+    translations: Vec<Option<String>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Translations {
+    locales: Vec<Locale>,
+    selected: Option<usize>,
+    default: Option<usize>,
+}
+
+impl Translations {
+    pub const fn new() -> Self {
+        Self {
+            locales: Vec::new(),
+            selected: None,
+            default: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.locales.clear();
+        self.selected = None;
+        self.default = None;
+    }
+
+    pub fn process_locale(&mut self, locale: String, records: Vec<TranslationRecord>) {
+        let locale = locale.to_lowercase();
+        let idx = match self.locales.iter().position(|l| l.locale == locale) {
+            Some(idx) => idx,
+            None => {
+                self.locales.push(Locale {
+                    locale,
+                    translations: alloc::vec![None; TRANSLATIONS_COUNT],
+                });
+
+                self.locales.len() - 1
+            }
+        };
+
+        for record in records {
+            if let Some(key) = TranslationKey::from_key_str(&record.key) {
+                self.locales[idx].translations[key] = Some(record.translation);
+            }
+        }
+    }
+
+    pub fn locale_index(&self, locale: &str) -> Option<usize> {
+        let locale = locale.to_lowercase();
+        self.locales.iter().position(|l| l.locale == locale)
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// `None` (unknown locale) selects default one, returns false if nothing changed
+    pub fn select_idx(&mut self, idx: Option<usize>) -> bool {
+        let idx = idx.or(self.default);
+        if idx == self.selected {
+            return false;
+        }
+
+        self.selected = idx;
+        true
+    }
+
+    pub fn select_locale(&mut self, locale: &str) -> bool {
+        self.select_idx(self.locale_index(locale))
+    }
+
+    pub fn set_default_locale(&mut self) {
+        self.default = self.selected;
+    }
+
+    pub fn restore_default_locale(&mut self) {
+        self.selected = self.default;
+    }
+
+    pub fn get(&self, key: usize) -> String {
+        match self.selected.and_then(|idx| self.locales.get(idx)) {
+            Some(locale) => locale
+                .translations
+                .get(key)
+                .and_then(|t| t.clone())
+                .unwrap_or("#####".to_string()),
+            None => FALLBACK_TRANSLATIONS
+                .get(key)
+                .map(|t| t.to_string())
+                .unwrap_or("#####".to_string()),
+        }
+    }
+
+    pub fn get_params<T: Display>(&self, key: usize, params: &[T]) -> String {
+        let mut translation = self.get(key);
+        for (i, arg) in params.iter().enumerate() {
+            let placeholder = alloc::format!("{{{i}}}");
+            translation = translation.replace(&placeholder, &arg.to_string());
+        }
+
+        translation
+    }
+}
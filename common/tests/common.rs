@@ -0,0 +1,180 @@
+use fkm_common::{
+    lcd::{LcdAbstract, LcdError, PrintAlign},
+    packets::{PreviousAttempt, TimerPacket, TimerPacketInner, TranslationRecord},
+    settings::Settings,
+    time::{ms_to_time_str, previous_attempts_str},
+    translations::{TranslationKey, Translations},
+};
+
+type Lcd = LcdAbstract<80, 16, 2, 3>;
+
+fn line(lcd: &Lcd, y: usize) -> &str {
+    std::str::from_utf8(lcd.line(y)).unwrap()
+}
+
+fn record(key: &str, translation: &str) -> TranslationRecord {
+    TranslationRecord {
+        key: key.to_string(),
+        translation: translation.to_string(),
+    }
+}
+
+#[test]
+fn lcd_print_align_and_pad() {
+    let mut lcd = Lcd::new();
+    lcd.print(0, "abc", PrintAlign::Center, true).unwrap();
+    lcd.print(1, "12.34", PrintAlign::Right, true).unwrap();
+    assert_eq!(line(&lcd, 0), "      abc       ");
+    assert_eq!(line(&lcd, 1), "           12.34");
+
+    // without pad text is drawn over current content
+    lcd.print(0, "<", PrintAlign::Left, false).unwrap();
+    lcd.print(0, ">", PrintAlign::Right, false).unwrap();
+    assert_eq!(line(&lcd, 0), "<     abc      >");
+
+    assert!(matches!(
+        lcd.print(2, "x", PrintAlign::Left, true),
+        Err(LcdError::OutOfRange)
+    ));
+    assert!(matches!(lcd.clear(2), Err(LcdError::OutOfRange)));
+}
+
+#[test]
+fn lcd_scrolls_long_lines() {
+    let mut lcd = Lcd::new();
+    lcd.print(0, "0123456789abcdefXY", PrintAlign::Left, true)
+        .unwrap();
+    lcd.print(1, "short", PrintAlign::Left, true).unwrap();
+    assert_eq!(line(&lcd, 0), "0123456789abcdefXY");
+
+    let (lines, _) = lcd.display_data();
+    assert_eq!(lines[0].0, b"0123456789abcdef");
+    assert!(lines[0].1);
+
+    // waits at start, then scrolls by one char until end of longest line
+    assert!(!lcd.scroll_step().unwrap());
+    assert!(!lcd.scroll_step().unwrap());
+    for _ in 0..5 {
+        lcd.scroll_step().unwrap();
+    }
+
+    let (lines, _) = lcd.display_data();
+    assert_eq!(lines[0].0, b"23456789abcdefXY");
+    assert_eq!(lines[1].0, b"short           ");
+
+    lcd.clear_all().unwrap();
+    assert!(!lcd.scroll_step().unwrap());
+}
+
+#[test]
+fn time_formatting() {
+    assert_eq!(ms_to_time_str(0), "0.00");
+    assert_eq!(ms_to_time_str(9_999), "9.99");
+    assert_eq!(ms_to_time_str(61_230), "1:01.23");
+
+    let attempts = [
+        PreviousAttempt {
+            solve_time: 12_340,
+            penalty: 0,
+        },
+        PreviousAttempt {
+            solve_time: 0,
+            penalty: -1,
+        },
+        PreviousAttempt {
+            solve_time: 11_020,
+            penalty: 2,
+        },
+        PreviousAttempt {
+            solve_time: 0,
+            penalty: -2,
+        },
+    ];
+    assert_eq!(previous_attempts_str(&attempts), "12.34 DNF 11.02+2 DNS");
+    assert_eq!(previous_attempts_str(&[]), "");
+}
+
+#[test]
+fn translations_fallback_and_locales() {
+    let mut t = Translations::new();
+    assert_eq!(
+        t.get(TranslationKey::SCAN_COMPETITOR_CARD_HEADER),
+        "Scan card"
+    );
+    assert_eq!(
+        t.get_params(TranslationKey::ATTEMPT_NUMBER, &[2, 5]),
+        "Attempt 2/5"
+    );
+    assert_eq!(t.get(usize::MAX), "#####");
+
+    t.process_locale(
+        "PL".to_string(),
+        vec![
+            record("scanCompetitorCardHeader", "Zeskanuj karte"),
+            record("unknownKey", "x"),
+        ],
+    );
+    t.process_locale("en".to_string(), vec![]);
+
+    assert!(t.select_locale("pl"));
+    assert!(!t.select_locale("PL"));
+    t.set_default_locale();
+    assert_eq!(
+        t.get(TranslationKey::SCAN_COMPETITOR_CARD_HEADER),
+        "Zeskanuj karte"
+    );
+
+    // key missing in pushed locale
+    assert_eq!(t.get(TranslationKey::ATTEMPT_NUMBER), "#####");
+
+    // unknown locale selects default one
+    assert!(t.select_locale("en"));
+    assert_eq!(t.selected(), t.locale_index("en"));
+    assert!(t.select_locale("de"));
+    assert_eq!(t.selected(), t.locale_index("pl"));
+
+    t.select_locale("en");
+    t.restore_default_locale();
+    assert_eq!(t.selected(), t.locale_index("pl"));
+
+    t.clear();
+    assert_eq!(t.selected(), None);
+    assert_eq!(
+        t.get(TranslationKey::SCAN_COMPETITOR_CARD_HEADER),
+        "Scan card"
+    );
+}
+
+#[test]
+fn packets_serialization() {
+    let packet: TimerPacket = serde_json::from_str(
+        r#"{"tag":5,"data":{"set_settings":{"version":3,"settings":{"inspection_warnings":true}}}}"#,
+    )
+    .unwrap();
+    assert_eq!(packet.tag, Some(5));
+    let TimerPacketInner::SetSettings { version, settings } = packet.data else {
+        panic!("wrong packet: {:?}", packet.data);
+    };
+    assert_eq!(version, 3);
+    assert_eq!(settings.inspection_warnings, Some(true));
+    assert_eq!(settings.inspection_plus2_ms, None);
+
+    let packet = TimerPacket {
+        tag: None,
+        data: TimerPacketInner::EffectiveSettings(Settings::DEFAULT),
+    };
+    let json = serde_json::to_value(&packet).unwrap();
+    assert!(json.get("tag").is_none());
+    assert_eq!(
+        json["data"]["effective_settings"]["inspection_dnf_ms"],
+        17000
+    );
+
+    // missing settings fields keep defaults
+    let settings: Settings = serde_json::from_str(r#"{"inspection_plus2_ms":10000}"#).unwrap();
+    assert_eq!(settings.inspection_plus2_ms, 10000);
+    assert_eq!(
+        settings.inspection_dnf_ms,
+        Settings::DEFAULT.inspection_dnf_ms
+    );
+}
@@ -0,0 +1,643 @@
+//! Competition flow of the timer (scenes, penalties, card ordering, delegate) as pure
+//! state machine. Firmware feeds typed events (buttons, stackmat, server responses)
+//! into [`Flow::handle`] and executes returned [`Command`]s (side effects), so whole
+//! flow can be tested on host without a board.
+
+#![no_std]
+
+extern crate alloc;
+
+mod scene;
+
+use alloc::{string::String, vec::Vec};
+pub use scene::Scene;
+
+pub const PENALTY_DNF: i8 = -1;
+
+/// Did not start (submitted with `solve_time` 0)
+pub const PENALTY_DNS: i8 = -2;
+
+/// Highest +2 penalty (in seconds) reachable with penalty button
+pub const PENALTY_MAX: i8 = 16;
+
+/// Inspection calls judge has to make (WCA regulation A3a1)
+pub const INSPECTION_CALL_8S_MS: u64 = 8000;
+pub const INSPECTION_CALL_12S_MS: u64 = 12000;
+
+/// Round (group) competitor can solve in
+pub trait Group: Clone + PartialEq {
+    fn use_inspection(&self) -> bool;
+
+    /// Time limit in ms
+    fn limit(&self) -> Option<u64>;
+
+    /// Result in ms attempt has to beat to make cutoff (only while it's still to be made)
+    fn cutoff(&self) -> Option<u64> {
+        None
+    }
+
+    /// Remaining cumulative time limit of the round in ms
+    fn cumulative_remaining(&self) -> Option<u64> {
+        None
+    }
+
+    /// Time at which running timer is stopped with DNF
+    fn time_cap(&self) -> Option<u64> {
+        match (self.limit(), self.cumulative_remaining()) {
+            (Some(limit), Some(remaining)) => Some(limit.min(remaining)),
+            (limit, remaining) => limit.or(remaining),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlowConfig {
+    pub inspection_plus2_ms: u64,
+    pub inspection_dnf_ms: u64,
+
+    /// Submit button stops running timer
+    pub submit_stops_timer: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    InspectionStart,
+    InspectionCancel,
+    Dnf,
+    Penalty,
+    Submit,
+    ResetCompetitor,
+    SelectPrev,
+    SelectNext,
+    CallDelegate,
+
+    /// Long hold of DNF button asking to record DNS (confirmed with submit, other buttons
+    /// cancel and restore penalty from before DNF toggle of the same hold)
+    DnsRequest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stackmat {
+    Started,
+
+    /// `limit_reached` is set when timer was stopped by group time limit (DNF)
+    Stopped {
+        time: u64,
+        limit_reached: bool,
+    },
+    Reset,
+    Disconnected,
+}
+
+/// Server response to scanned card
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardInfo<G> {
+    pub card_id: u64,
+    pub display: String,
+    pub can_compete: bool,
+    pub possible_groups: Vec<G>,
+}
+
+/// Server response to delegate call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DelegateDecision {
+    pub solve_time: Option<u64>,
+    pub penalty: Option<i64>,
+    pub should_scan_cards: bool,
+}
+
+/// Inspection threshold signalled to judge and competitor (beep/flash)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InspectionWarning {
+    Call8s,
+    Call12s,
+    Plus2,
+    Dnf,
+}
+
+impl InspectionWarning {
+    /// Threshold crossed between two readings of inspection time (most severe one wins)
+    pub fn crossed(prev_ms: u64, elapsed_ms: u64, config: &FlowConfig) -> Option<Self> {
+        [
+            (config.inspection_dnf_ms, Self::Dnf),
+            (config.inspection_plus2_ms, Self::Plus2),
+            (INSPECTION_CALL_12S_MS, Self::Call12s),
+            (INSPECTION_CALL_8S_MS, Self::Call8s),
+        ]
+        .into_iter()
+        .find(|(threshold, _)| prev_ms < *threshold && elapsed_ms >= *threshold)
+        .map(|(_, warning)| warning)
+    }
+
+    /// Signal pattern as (on, off) pairs in ms
+    pub fn pattern(self) -> &'static [(u64, u64)] {
+        match self {
+            Self::Call8s => &[(150, 0)],
+            Self::Call12s => &[(150, 100), (150, 0)],
+            Self::Plus2 => &[(150, 100), (150, 100), (150, 0)],
+            Self::Dnf => &[(800, 0)],
+        }
+    }
+
+    /// Signal is on `ms` after warning was triggered
+    pub fn is_on(self, mut ms: u64) -> bool {
+        for &(on, off) in self.pattern() {
+            if ms < on + off {
+                return ms < on;
+            }
+
+            ms -= on + off;
+        }
+
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<G> {
+    Button(Button),
+    Stackmat(Stackmat),
+    CardInfo(CardInfo<G>),
+
+    /// Solve was accepted by server (or queued to be sent later)
+    SolveSent,
+    Delegate(DelegateDecision),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submission<G> {
+    pub competitor_id: u64,
+    pub judge_id: u64,
+    pub solve_time: u64,
+    pub penalty: i8,
+    pub inspection_time: i64,
+    pub group: G,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notice {
+    EmptyGroups,
+    CardsCannotBeTheSame,
+    SolveGroupMissing,
+}
+
+/// Side effects requested by state machine
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command<G> {
+    SetGroupLimit(Option<u64>),
+
+    /// Competitor card accepted (firmware switches to competitor locale)
+    CompetitorSelected {
+        card_id: u64,
+    },
+
+    /// Persist solve, so it survives reboot
+    SaveState,
+    StopTimer,
+
+    /// Stackmat time tracking should start over
+    TimerReset,
+    SubmitSolve(Submission<G>),
+    CallDelegate(Submission<G>),
+
+    /// Flow state was reset (session, locale and saved state are handled by firmware)
+    ResetSolve {
+        clear_saved: bool,
+    },
+    Notify(Notice),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition<G> {
+    /// Flow state changed (screen should be redrawn)
+    pub changed: bool,
+    pub commands: Vec<Command<G>>,
+}
+
+/// State of single solve. Instants are ms of device uptime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flow<G> {
+    pub scene: Scene,
+    pub inspection_start: Option<u64>,
+    pub inspection_end: Option<u64>,
+    pub solve_time: Option<u64>,
+    pub penalty: Option<i8>,
+    pub time_confirmed: bool,
+    pub solve_group: Option<G>,
+    pub possible_groups: Vec<G>,
+    pub group_selected_idx: usize,
+    pub current_competitor: Option<u64>,
+    pub current_judge: Option<u64>,
+    pub competitor_display: Option<String>,
+    pub delegate_used: bool,
+
+    /// DNS was requested and waits for confirmation
+    pub dns_pending: bool,
+
+    /// Penalty before DNF was toggled in `Finished` by last button (DNS is requested
+    /// by holding DNF button longer, so cancelled DNS request restores it)
+    penalty_before_dnf: Option<Option<i8>>,
+}
+
+impl<G: Group> Flow<G> {
+    pub fn new(scene: Scene) -> Self {
+        Self {
+            scene,
+            inspection_start: None,
+            inspection_end: None,
+            solve_time: None,
+            penalty: None,
+            time_confirmed: false,
+            solve_group: None,
+            possible_groups: Vec::new(),
+            group_selected_idx: 0,
+            current_competitor: None,
+            current_judge: None,
+            competitor_display: None,
+            delegate_used: false,
+            dns_pending: false,
+            penalty_before_dnf: None,
+        }
+    }
+
+    pub fn use_inspection(&self) -> bool {
+        self.solve_group
+            .as_ref()
+            .map(|g| g.use_inspection())
+            .unwrap_or(true)
+    }
+
+    /// Inspection time sent with solve (0 if group doesn't use inspection)
+    pub fn inspection_time(&self) -> i64 {
+        match (
+            self.use_inspection(),
+            self.inspection_start,
+            self.inspection_end,
+        ) {
+            (true, Some(start), Some(end)) => end.saturating_sub(start) as i64,
+            _ => 0,
+        }
+    }
+
+    /// Current result (with penalty) doesn't make cutoff of solve group
+    pub fn cutoff_missed(&self) -> bool {
+        let (Some(cutoff), Some(solve_time)) = (
+            self.solve_group.as_ref().and_then(|g| g.cutoff()),
+            self.solve_time,
+        ) else {
+            return false;
+        };
+
+        match self.penalty.unwrap_or(0) {
+            penalty if penalty < 0 => true,
+            penalty => solve_time + penalty as u64 * 1000 >= cutoff,
+        }
+    }
+
+    /// Card is scanned as competitor card (`is_competitor` in card info request)
+    pub fn is_competitor_card(&self, card_id: u64) -> bool {
+        self.current_competitor.is_none() || self.current_competitor == Some(card_id)
+    }
+
+    pub fn can_call_delegate(&self) -> bool {
+        !self.delegate_used && self.current_competitor.is_some() && self.solve_group.is_some()
+    }
+
+    /// Resets solve related state (scene goes back to `WaitingForCompetitor`)
+    pub fn reset(&mut self) {
+        *self = Self::new(Scene::WaitingForCompetitor);
+    }
+
+    pub fn handle(&mut self, event: Event<G>, now: u64, config: &FlowConfig) -> Transition<G> {
+        let old = self.clone();
+        let mut commands = Vec::new();
+
+        match event {
+            Event::Button(button) => self.on_button(button, now, config, &mut commands),
+            Event::Stackmat(stackmat) => self.on_stackmat(stackmat, now, config, &mut commands),
+            Event::CardInfo(info) => self.on_card_info(info, &mut commands),
+            Event::SolveSent => {
+                self.reset();
+                commands.push(Command::ResetSolve { clear_saved: true });
+            }
+            Event::Delegate(decision) => self.on_delegate(decision, &mut commands),
+        }
+
+        Transition {
+            changed: *self != old,
+            commands,
+        }
+    }
+
+    fn on_button(
+        &mut self,
+        button: Button,
+        now: u64,
+        config: &FlowConfig,
+        commands: &mut Vec<Command<G>>,
+    ) {
+        let penalty_before_dnf = self.penalty_before_dnf.take();
+
+        // reset competitor still works, any other button cancels DNS (submit confirms it)
+        if self.dns_pending && button != Button::ResetCompetitor {
+            self.dns_pending = false;
+            if button == Button::Submit {
+                self.confirm_dns(commands);
+            } else if let Some(penalty) = penalty_before_dnf {
+                self.penalty = penalty;
+            }
+
+            return;
+        }
+
+        match button {
+            Button::InspectionStart => {
+                if self.use_inspection()
+                    && self.scene < Scene::Inspection
+                    && self.inspection_start.is_none()
+                    && self.solve_time.is_none()
+                {
+                    self.inspection_start = Some(now);
+                    self.scene = Scene::Inspection;
+                }
+            }
+            Button::InspectionCancel => {
+                if self.scene == Scene::Inspection {
+                    self.scene = self.idle_scene();
+                    self.inspection_start = None;
+                    self.inspection_end = None;
+                }
+            }
+            Button::Dnf => {
+                if self.scene == Scene::Inspection {
+                    self.inspection_end = Some(now);
+                    self.solve_time = Some(0);
+                    self.penalty = Some(PENALTY_DNF);
+                    self.time_confirmed = true;
+                    self.scene = if self.current_competitor.is_some() {
+                        Scene::Finished
+                    } else {
+                        Scene::WaitingForCompetitor
+                    };
+                } else if self.scene == Scene::Finished && !self.time_confirmed {
+                    self.penalty_before_dnf = Some(self.penalty);
+                    let old_penalty = self.penalty.unwrap_or(0);
+                    self.penalty = Some(if old_penalty == PENALTY_DNF {
+                        0
+                    } else {
+                        PENALTY_DNF
+                    });
+                }
+            }
+            Button::Penalty => {
+                if self.scene == Scene::Finished && !self.time_confirmed {
+                    let old_penalty = self.penalty.unwrap_or(0);
+                    self.penalty = Some(if !(0..PENALTY_MAX).contains(&old_penalty) {
+                        0
+                    } else {
+                        old_penalty + 2
+                    });
+                }
+            }
+            Button::Submit => {
+                if self.scene == Scene::GroupSelect {
+                    let Some(group) = self.possible_groups.get(self.group_selected_idx) else {
+                        return;
+                    };
+
+                    commands.push(Command::SetGroupLimit(group.time_cap()));
+                    self.solve_group = Some(group.clone());
+                    self.scene = if self.solve_time.is_some() {
+                        Scene::Finished
+                    } else {
+                        Scene::CompetitorInfo
+                    };
+
+                    return;
+                } else if self.scene == Scene::Timer && config.submit_stops_timer {
+                    commands.push(Command::StopTimer);
+                }
+
+                if self.scene == Scene::Finished && !self.time_confirmed {
+                    self.time_confirmed = true;
+                }
+            }
+            Button::ResetCompetitor => {
+                commands.push(Command::StopTimer);
+                self.reset();
+                commands.push(Command::ResetSolve { clear_saved: false });
+            }
+            Button::SelectPrev => {
+                if self.scene == Scene::GroupSelect && !self.possible_groups.is_empty() {
+                    self.group_selected_idx = self
+                        .group_selected_idx
+                        .wrapping_sub(1)
+                        .min(self.possible_groups.len() - 1);
+                }
+            }
+            Button::SelectNext => {
+                if self.scene == Scene::GroupSelect && !self.possible_groups.is_empty() {
+                    self.group_selected_idx =
+                        (self.group_selected_idx + 1) % self.possible_groups.len();
+                }
+            }
+            Button::DnsRequest => {
+                let scene_allows = match self.scene {
+                    Scene::CompetitorInfo => true,
+                    Scene::Finished => !self.time_confirmed,
+                    _ => false,
+                };
+
+                if scene_allows && self.current_competitor.is_some() && self.current_judge.is_none()
+                {
+                    self.dns_pending = true;
+                    self.penalty_before_dnf = penalty_before_dnf;
+                }
+            }
+            Button::CallDelegate => {
+                if !self.can_call_delegate() {
+                    return;
+                }
+
+                if let Some(submission) = self.submission(self.current_judge.unwrap_or(0)) {
+                    commands.push(Command::CallDelegate(submission));
+                }
+            }
+        }
+    }
+
+    fn confirm_dns(&mut self, commands: &mut Vec<Command<G>>) {
+        self.inspection_start = None;
+        self.inspection_end = None;
+        self.solve_time = Some(0);
+        self.penalty = Some(PENALTY_DNS);
+        self.time_confirmed = true;
+        self.scene = Scene::Finished;
+        commands.push(Command::SaveState);
+    }
+
+    fn on_stackmat(
+        &mut self,
+        stackmat: Stackmat,
+        now: u64,
+        config: &FlowConfig,
+        commands: &mut Vec<Command<G>>,
+    ) {
+        match stackmat {
+            Stackmat::Started => {
+                self.dns_pending = false;
+                if self.scene <= Scene::Inspection && self.solve_time.is_none() {
+                    if self.use_inspection() {
+                        self.inspection_end = Some(now);
+                    }
+
+                    self.scene = Scene::Timer;
+                }
+            }
+            Stackmat::Stopped {
+                time,
+                limit_reached,
+            } => {
+                if self.solve_time.is_none() {
+                    let inspection_time = match (self.inspection_start, self.inspection_end) {
+                        (Some(start), Some(end)) => end.saturating_sub(start),
+                        _ => 0,
+                    };
+
+                    self.delegate_used = false;
+                    self.solve_time = Some(time);
+                    self.penalty = if inspection_time >= config.inspection_dnf_ms || limit_reached {
+                        Some(PENALTY_DNF)
+                    } else if inspection_time >= config.inspection_plus2_ms {
+                        Some(2)
+                    } else {
+                        None
+                    };
+
+                    if self.current_competitor.is_some() {
+                        if self.possible_groups.len() > 1 && self.solve_group.is_none() {
+                            self.scene = Scene::GroupSelect;
+                        } else {
+                            self.scene = Scene::Finished;
+                        }
+                    } else if self.scene >= Scene::WaitingForCompetitor {
+                        self.scene = Scene::WaitingForCompetitor;
+                    }
+
+                    commands.push(Command::SaveState);
+                } else if self.scene == Scene::Timer {
+                    self.scene = Scene::WaitingForCompetitor;
+                }
+
+                commands.push(Command::TimerReset);
+            }
+            Stackmat::Reset => {
+                if self.current_competitor.is_none()
+                    && self.penalty.is_none()
+                    && (self.scene == Scene::Timer || self.scene == Scene::WaitingForCompetitor)
+                {
+                    self.scene = Scene::WaitingForCompetitor;
+                    self.solve_time = None;
+                    self.penalty = None;
+                    self.inspection_start = None;
+                    self.inspection_end = None;
+                    commands.push(Command::TimerReset);
+                } else if self.current_competitor.is_some() && self.scene == Scene::Timer {
+                    // timer reset while running is DNF
+                    self.scene = Scene::Finished;
+                    self.solve_time = Some(0);
+                    self.penalty = Some(PENALTY_DNF);
+                    self.time_confirmed = true;
+                    commands.push(Command::TimerReset);
+                }
+            }
+            Stackmat::Disconnected => {
+                if self.scene == Scene::Timer {
+                    self.scene = self.idle_scene();
+                }
+            }
+        }
+    }
+
+    fn on_card_info(&mut self, info: CardInfo<G>, commands: &mut Vec<Command<G>>) {
+        match self.scene {
+            Scene::WaitingForCompetitor
+                if self.current_competitor.is_none() && info.can_compete =>
+            {
+                self.competitor_display = Some(info.display);
+                self.current_competitor = Some(info.card_id);
+                commands.push(Command::CompetitorSelected {
+                    card_id: info.card_id,
+                });
+
+                match info.possible_groups.len() {
+                    1 => {
+                        let group = info.possible_groups[0].clone();
+                        commands.push(Command::SetGroupLimit(group.time_cap()));
+                        self.solve_group = Some(group);
+                        self.scene = if self.solve_time.is_some() {
+                            Scene::Finished
+                        } else {
+                            Scene::CompetitorInfo
+                        };
+                    }
+                    2.. => {
+                        self.possible_groups = info.possible_groups;
+                        self.scene = Scene::GroupSelect;
+                    }
+                    _ => {
+                        self.reset();
+                        commands.push(Command::ResetSolve { clear_saved: false });
+                        commands.push(Command::Notify(Notice::EmptyGroups));
+                    }
+                }
+            }
+            Scene::Finished if self.time_confirmed => {
+                if self.current_competitor != Some(info.card_id) {
+                    self.current_judge = Some(info.card_id);
+                } else if let Some(judge_id) = self.current_judge {
+                    match self.submission(judge_id) {
+                        Some(submission) => commands.push(Command::SubmitSolve(submission)),
+                        None => commands.push(Command::Notify(Notice::SolveGroupMissing)),
+                    }
+                } else {
+                    commands.push(Command::Notify(Notice::CardsCannotBeTheSame));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_delegate(&mut self, decision: DelegateDecision, commands: &mut Vec<Command<G>>) {
+        self.solve_time = Some(decision.solve_time.unwrap_or(self.solve_time.unwrap_or(0)));
+        self.penalty = Some(decision.penalty.unwrap_or(self.penalty.unwrap_or(0) as i64) as i8);
+        self.scene = Scene::Finished;
+        self.time_confirmed = true;
+        self.delegate_used = true;
+
+        if !decision.should_scan_cards {
+            self.reset();
+            commands.push(Command::ResetSolve { clear_saved: true });
+        }
+    }
+
+    fn submission(&self, judge_id: u64) -> Option<Submission<G>> {
+        Some(Submission {
+            competitor_id: self.current_competitor?,
+            judge_id,
+            solve_time: self.solve_time.unwrap_or(0),
+            penalty: self.penalty.unwrap_or(0),
+            inspection_time: self.inspection_time(),
+            group: self.solve_group.clone()?,
+        })
+    }
+
+    /// Scene shown when nothing is in progress
+    fn idle_scene(&self) -> Scene {
+        if self.current_competitor.is_none() {
+            Scene::WaitingForCompetitor
+        } else {
+            Scene::CompetitorInfo
+        }
+    }
+}
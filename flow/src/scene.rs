@@ -0,0 +1,66 @@
+#[derive(Debug, PartialEq, Clone)]
+#[allow(dead_code)]
+pub enum Scene {
+    Update,
+
+    /// Waiting for wifi connection
+    WifiConnect,
+
+    /// Connect to wifi to setup
+    AutoSetupWait,
+
+    /// Waiting for MDNS
+    MdnsWait,
+
+    WaitingForCompetitor,
+    GroupSelect,
+    CompetitorInfo,
+    Inspection,
+    Timer,
+    Finished,
+}
+
+impl Scene {
+    pub fn can_be_lcd_overwritten(&self) -> bool {
+        match self {
+            Scene::Update => false,
+            Scene::WifiConnect => false,
+            Scene::AutoSetupWait => false,
+            Scene::MdnsWait => false,
+            Scene::WaitingForCompetitor => true,
+            Scene::GroupSelect => true,
+            Scene::CompetitorInfo => true,
+            Scene::Inspection => false,
+            Scene::Timer => false,
+            Scene::Finished => false,
+        }
+    }
+
+    pub fn to_index(&self) -> usize {
+        match self {
+            Scene::Update => 0,
+            Scene::WifiConnect => 1,
+            Scene::AutoSetupWait => 2,
+            Scene::MdnsWait => 3,
+            Scene::WaitingForCompetitor => 4,
+            Scene::GroupSelect => 5,
+            Scene::CompetitorInfo => 6,
+            Scene::Inspection => 7,
+            Scene::Timer => 8,
+            Scene::Finished => 9,
+        }
+    }
+
+    pub fn can_sleep(&self) -> bool {
+        !matches!(
+            self,
+            Scene::Update | Scene::WifiConnect | Scene::AutoSetupWait
+        )
+    }
+}
+
+impl PartialOrd for Scene {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.to_index().cmp(&other.to_index()))
+    }
+}
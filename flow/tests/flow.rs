@@ -0,0 +1,756 @@
+use fkm_flow::{
+    Button, CardInfo, Command, DelegateDecision, Event, Flow, FlowConfig, Group, InspectionWarning,
+    Notice, PENALTY_DNF, PENALTY_DNS, Scene, Stackmat, Submission,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+struct TestGroup {
+    id: &'static str,
+    inspection: bool,
+    limit: Option<u64>,
+    cutoff: Option<u64>,
+    cumulative_remaining: Option<u64>,
+}
+
+impl Group for TestGroup {
+    fn use_inspection(&self) -> bool {
+        self.inspection
+    }
+
+    fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    fn cutoff(&self) -> Option<u64> {
+        self.cutoff
+    }
+
+    fn cumulative_remaining(&self) -> Option<u64> {
+        self.cumulative_remaining
+    }
+}
+
+const CONFIG: FlowConfig = FlowConfig {
+    inspection_plus2_ms: 15000,
+    inspection_dnf_ms: 17000,
+    submit_stops_timer: false,
+};
+
+const COMPETITOR: u64 = 1111;
+const JUDGE: u64 = 2222;
+
+fn group(id: &'static str) -> TestGroup {
+    TestGroup {
+        id,
+        inspection: true,
+        limit: None,
+        cutoff: None,
+        cumulative_remaining: None,
+    }
+}
+
+fn card(card_id: u64, groups: Vec<TestGroup>) -> Event<TestGroup> {
+    Event::CardInfo(CardInfo {
+        card_id,
+        display: format!("Card {card_id}"),
+        can_compete: true,
+        possible_groups: groups,
+    })
+}
+
+fn button(button: Button) -> Event<TestGroup> {
+    Event::Button(button)
+}
+
+fn stopped(time: u64) -> Event<TestGroup> {
+    Event::Stackmat(Stackmat::Stopped {
+        time,
+        limit_reached: false,
+    })
+}
+
+struct Harness {
+    flow: Flow<TestGroup>,
+    now: u64,
+}
+
+impl Harness {
+    fn new() -> Self {
+        Self {
+            flow: Flow::new(Scene::WaitingForCompetitor),
+            now: 1000,
+        }
+    }
+
+    /// Competitor with single group scanned
+    fn with_competitor() -> Self {
+        let mut harness = Self::new();
+        harness.send(card(COMPETITOR, vec![group("333-r1")]));
+        assert_eq!(harness.flow.scene, Scene::CompetitorInfo);
+        harness
+    }
+
+    fn send(&mut self, event: Event<TestGroup>) -> Vec<Command<TestGroup>> {
+        self.flow.handle(event, self.now, &CONFIG).commands
+    }
+
+    fn wait(&mut self, ms: u64) {
+        self.now += ms;
+    }
+
+    /// Inspection of given length followed by solve
+    fn solve(&mut self, inspection_ms: u64, time: u64) -> Vec<Command<TestGroup>> {
+        self.send(button(Button::InspectionStart));
+        assert_eq!(self.flow.scene, Scene::Inspection);
+        self.wait(inspection_ms);
+        self.send(Event::Stackmat(Stackmat::Started));
+        assert_eq!(self.flow.scene, Scene::Timer);
+        self.wait(time);
+        self.send(stopped(time))
+    }
+}
+
+fn submitted(commands: &[Command<TestGroup>]) -> Option<&Submission<TestGroup>> {
+    commands.iter().find_map(|c| match c {
+        Command::SubmitSolve(submission) => Some(submission),
+        _ => None,
+    })
+}
+
+#[test]
+fn inspection_without_penalty() {
+    let mut h = Harness::with_competitor();
+    let commands = h.solve(14999, 9000);
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(9000));
+    assert_eq!(h.flow.penalty, None);
+    assert_eq!(h.flow.inspection_time(), 14999);
+    assert!(commands.contains(&Command::SaveState));
+    assert!(commands.contains(&Command::TimerReset));
+}
+
+#[test]
+fn inspection_over_15s_is_plus2() {
+    let mut h = Harness::with_competitor();
+    h.solve(15000, 9000);
+
+    assert_eq!(h.flow.penalty, Some(2));
+}
+
+#[test]
+fn inspection_over_17s_is_dnf() {
+    let mut h = Harness::with_competitor();
+    h.solve(17000, 9000);
+
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+}
+
+#[test]
+fn group_without_inspection() {
+    let mut h = Harness::new();
+    h.send(card(
+        COMPETITOR,
+        vec![TestGroup {
+            id: "333bf-r1",
+            inspection: false,
+            ..group("333bf-r1")
+        }],
+    ));
+
+    h.send(button(Button::InspectionStart));
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.wait(60000);
+    h.send(stopped(60000));
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.penalty, None);
+    assert_eq!(h.flow.inspection_time(), 0);
+}
+
+#[test]
+fn inspection_cancel() {
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::InspectionStart));
+    h.wait(3000);
+    h.send(button(Button::InspectionCancel));
+
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+    assert_eq!(h.flow.inspection_start, None);
+
+    let mut h = Harness::new();
+    h.send(button(Button::InspectionStart));
+    h.send(button(Button::InspectionCancel));
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+}
+
+#[test]
+fn dnf_button_during_inspection() {
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::InspectionStart));
+    h.wait(5000);
+    h.send(button(Button::Dnf));
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(0));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+    assert!(h.flow.time_confirmed);
+}
+
+#[test]
+fn dnf_button_during_inspection_without_competitor() {
+    let mut h = Harness::new();
+    h.send(button(Button::InspectionStart));
+    h.send(button(Button::Dnf));
+
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+
+    // competitor scanned after DNF goes straight to finished scene
+    h.send(card(COMPETITOR, vec![group("333-r1")]));
+    assert_eq!(h.flow.scene, Scene::Finished);
+}
+
+#[test]
+fn dnf_button_toggles_before_confirm() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+
+    h.send(button(Button::Dnf));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+    h.send(button(Button::Dnf));
+    assert_eq!(h.flow.penalty, Some(0));
+
+    h.send(button(Button::Submit));
+    assert!(h.flow.time_confirmed);
+    h.send(button(Button::Dnf));
+    assert_eq!(h.flow.penalty, Some(0));
+}
+
+#[test]
+fn penalty_button_cycles() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+
+    for expected in [2, 4, 6, 8, 10, 12, 14, 16, 0, 2] {
+        h.send(button(Button::Penalty));
+        assert_eq!(h.flow.penalty, Some(expected));
+    }
+
+    h.send(button(Button::Dnf));
+    h.send(button(Button::Penalty));
+    assert_eq!(h.flow.penalty, Some(0));
+}
+
+#[test]
+fn dns_from_competitor_info() {
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::DnsRequest));
+    assert!(h.flow.dns_pending);
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+
+    let commands = h.send(button(Button::Submit));
+    assert!(commands.contains(&Command::SaveState));
+    assert!(!h.flow.dns_pending);
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(0));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNS));
+    assert!(h.flow.time_confirmed);
+
+    h.send(card(JUDGE, vec![]));
+    let commands = h.send(card(COMPETITOR, vec![]));
+    let submission = submitted(&commands).expect("solve submitted");
+    assert_eq!(submission.solve_time, 0);
+    assert_eq!(submission.penalty, -2);
+    assert_eq!(submission.inspection_time, 0);
+}
+
+#[test]
+fn dns_overrides_finished_solve() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::Dnf));
+
+    h.send(button(Button::DnsRequest));
+    h.send(button(Button::Submit));
+    assert_eq!(h.flow.solve_time, Some(0));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNS));
+    assert_eq!(h.flow.inspection_time(), 0);
+
+    // not after judge was scanned
+    h.send(card(JUDGE, vec![]));
+    h.send(button(Button::DnsRequest));
+    assert!(!h.flow.dns_pending);
+}
+
+#[test]
+fn dns_cancel() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+
+    h.send(button(Button::DnsRequest));
+    h.send(button(Button::Penalty));
+    assert!(!h.flow.dns_pending);
+    assert_eq!(h.flow.penalty, None);
+    assert_eq!(h.flow.solve_time, Some(9000));
+
+    // inspection isn't started by cancelling press
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::DnsRequest));
+    h.send(button(Button::InspectionStart));
+    assert!(!h.flow.dns_pending);
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+
+    h.send(button(Button::DnsRequest));
+    h.send(Event::Stackmat(Stackmat::Started));
+    assert!(!h.flow.dns_pending);
+
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::DnsRequest));
+    h.send(button(Button::ResetCompetitor));
+    assert_eq!(h.flow, Flow::new(Scene::WaitingForCompetitor));
+}
+
+#[test]
+fn dns_hold_cancel_restores_penalty() {
+    // DNS is requested by holding DNF button, which toggles DNF first
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::Penalty));
+    h.send(button(Button::Dnf));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+    h.send(button(Button::DnsRequest));
+    assert!(h.flow.dns_pending);
+
+    h.send(button(Button::Penalty));
+    assert!(!h.flow.dns_pending);
+    assert_eq!(h.flow.penalty, Some(2));
+    assert_eq!(h.flow.solve_time, Some(9000));
+
+    // DNF pressed before isn't part of the hold
+    h.send(button(Button::Dnf));
+    h.send(button(Button::SelectNext));
+    h.send(button(Button::DnsRequest));
+    h.send(button(Button::InspectionStart));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+}
+
+#[test]
+fn dns_not_after_time_confirmed() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::Submit));
+    h.send(button(Button::DnsRequest));
+    assert!(!h.flow.dns_pending);
+
+    // holding DNF button in inspection only DNFs the attempt
+    let mut h = Harness::with_competitor();
+    h.send(button(Button::InspectionStart));
+    h.send(button(Button::Dnf));
+    h.send(button(Button::DnsRequest));
+    assert!(!h.flow.dns_pending);
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+}
+
+#[test]
+fn dns_needs_competitor() {
+    let mut h = Harness::new();
+    h.send(button(Button::DnsRequest));
+    assert!(!h.flow.dns_pending);
+
+    h.send(button(Button::InspectionStart));
+    h.send(button(Button::DnsRequest));
+    assert!(!h.flow.dns_pending);
+}
+
+#[test]
+fn time_limit_is_dnf() {
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(Event::Stackmat(Stackmat::Stopped {
+        time: 60000,
+        limit_reached: true,
+    }));
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(60000));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+}
+
+#[test]
+fn group_limit_is_set() {
+    let mut h = Harness::new();
+    let commands = h.send(card(
+        COMPETITOR,
+        vec![TestGroup {
+            id: "666-r1",
+            limit: Some(180000),
+            ..group("666-r1")
+        }],
+    ));
+
+    assert!(commands.contains(&Command::SetGroupLimit(Some(180000))));
+    assert!(commands.contains(&Command::CompetitorSelected {
+        card_id: COMPETITOR
+    }));
+}
+
+#[test]
+fn cumulative_remaining_caps_group_limit() {
+    let groups = [
+        (Some(600000), Some(250000), Some(250000)),
+        (Some(180000), Some(250000), Some(180000)),
+        (None, Some(250000), Some(250000)),
+        (None, None, None),
+    ];
+
+    for (limit, cumulative_remaining, cap) in groups {
+        let mut h = Harness::new();
+        let commands = h.send(card(
+            COMPETITOR,
+            vec![TestGroup {
+                limit,
+                cumulative_remaining,
+                ..group("444bf-r1")
+            }],
+        ));
+
+        assert!(commands.contains(&Command::SetGroupLimit(cap)));
+    }
+}
+
+#[test]
+fn cutoff_missed() {
+    let mut h = Harness::new();
+    h.send(card(
+        COMPETITOR,
+        vec![TestGroup {
+            cutoff: Some(10000),
+            ..group("333-r1")
+        }],
+    ));
+
+    h.solve(1000, 9000);
+    assert!(!h.flow.cutoff_missed());
+
+    // +2 makes it exactly cutoff, which isn't under it
+    h.send(button(Button::Penalty));
+    assert!(h.flow.cutoff_missed());
+
+    // cycle penalty back to none
+    while h.flow.penalty != Some(0) {
+        h.send(button(Button::Penalty));
+    }
+
+    assert!(!h.flow.cutoff_missed());
+
+    h.send(button(Button::Dnf));
+    assert!(h.flow.cutoff_missed());
+}
+
+#[test]
+fn cutoff_unknown_without_group() {
+    let mut h = Harness::new();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(stopped(50000));
+    assert!(!h.flow.cutoff_missed());
+}
+
+#[test]
+fn stackmat_reset_while_running_is_dnf() {
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    let commands = h.send(Event::Stackmat(Stackmat::Reset));
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(0));
+    assert_eq!(h.flow.penalty, Some(PENALTY_DNF));
+    assert!(h.flow.time_confirmed);
+    assert!(commands.contains(&Command::TimerReset));
+}
+
+#[test]
+fn stackmat_reset_clears_time_without_competitor() {
+    let mut h = Harness::new();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(stopped(5000));
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+    assert_eq!(h.flow.solve_time, Some(5000));
+
+    h.send(Event::Stackmat(Stackmat::Reset));
+    assert_eq!(h.flow.solve_time, None);
+}
+
+#[test]
+fn stackmat_disconnect_stops_timer_scene() {
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(Event::Stackmat(Stackmat::Disconnected));
+
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+}
+
+#[test]
+fn solve_before_competitor_scan() {
+    let mut h = Harness::new();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(stopped(7000));
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+
+    h.send(card(COMPETITOR, vec![group("333-r1")]));
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(7000));
+}
+
+#[test]
+fn judge_then_competitor_submits() {
+    let mut h = Harness::with_competitor();
+    h.solve(8000, 9000);
+
+    // cards are ignored until time is confirmed
+    assert!(h.send(card(JUDGE, vec![])).is_empty());
+    assert_eq!(h.flow.current_judge, None);
+
+    h.send(button(Button::Submit));
+    h.send(card(JUDGE, vec![]));
+    assert_eq!(h.flow.current_judge, Some(JUDGE));
+
+    let commands = h.send(card(COMPETITOR, vec![]));
+    let submission = submitted(&commands).expect("solve submitted");
+    assert_eq!(submission.competitor_id, COMPETITOR);
+    assert_eq!(submission.judge_id, JUDGE);
+    assert_eq!(submission.solve_time, 9000);
+    assert_eq!(submission.penalty, 0);
+    assert_eq!(submission.inspection_time, 8000);
+    assert_eq!(submission.group.id, "333-r1");
+
+    let commands = h.send(Event::SolveSent);
+    assert!(commands.contains(&Command::ResetSolve { clear_saved: true }));
+    assert_eq!(h.flow, Flow::new(Scene::WaitingForCompetitor));
+}
+
+#[test]
+fn competitor_before_judge_is_rejected() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::Submit));
+
+    let commands = h.send(card(COMPETITOR, vec![]));
+    assert_eq!(
+        commands,
+        vec![Command::Notify(Notice::CardsCannotBeTheSame)]
+    );
+    assert_eq!(h.flow.current_judge, None);
+}
+
+#[test]
+fn last_judge_card_wins() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::Submit));
+    h.send(card(JUDGE, vec![]));
+    h.send(card(3333, vec![]));
+
+    let commands = h.send(card(COMPETITOR, vec![]));
+    assert_eq!(submitted(&commands).map(|s| s.judge_id), Some(3333));
+}
+
+#[test]
+fn competitor_card_is_only_accepted_once() {
+    let mut h = Harness::with_competitor();
+    assert!(h.flow.is_competitor_card(COMPETITOR));
+    assert!(!h.flow.is_competitor_card(JUDGE));
+
+    h.send(card(JUDGE, vec![group("333-r1")]));
+    assert_eq!(h.flow.current_competitor, Some(COMPETITOR));
+}
+
+#[test]
+fn competitor_that_cannot_compete_is_ignored() {
+    let mut h = Harness::new();
+    h.send(Event::CardInfo(CardInfo {
+        card_id: COMPETITOR,
+        display: "Card".into(),
+        can_compete: false,
+        possible_groups: vec![group("333-r1")],
+    }));
+
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+    assert_eq!(h.flow.current_competitor, None);
+}
+
+#[test]
+fn empty_groups_reset() {
+    let mut h = Harness::new();
+    let commands = h.send(card(COMPETITOR, vec![]));
+
+    assert!(commands.contains(&Command::Notify(Notice::EmptyGroups)));
+    assert_eq!(h.flow.current_competitor, None);
+}
+
+#[test]
+fn group_select() {
+    let mut h = Harness::new();
+    h.send(card(
+        COMPETITOR,
+        vec![group("333-r1"), group("444-r1"), group("555-r1")],
+    ));
+    assert_eq!(h.flow.scene, Scene::GroupSelect);
+
+    h.send(button(Button::SelectPrev));
+    assert_eq!(h.flow.group_selected_idx, 2);
+    h.send(button(Button::SelectNext));
+    assert_eq!(h.flow.group_selected_idx, 0);
+    h.send(button(Button::SelectNext));
+
+    h.send(button(Button::Submit));
+    assert_eq!(h.flow.scene, Scene::CompetitorInfo);
+    assert_eq!(h.flow.solve_group.as_ref().map(|g| g.id), Some("444-r1"));
+}
+
+#[test]
+fn group_select_after_solve() {
+    let mut h = Harness::new();
+    h.send(card(COMPETITOR, vec![group("333-r1"), group("444-r1")]));
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(stopped(9000));
+    assert_eq!(h.flow.scene, Scene::GroupSelect);
+
+    h.send(button(Button::Submit));
+    assert_eq!(h.flow.scene, Scene::Finished);
+}
+
+#[test]
+fn submit_stops_timer() {
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    assert!(h.send(button(Button::Submit)).is_empty());
+
+    let config = FlowConfig {
+        submit_stops_timer: true,
+        ..CONFIG
+    };
+    let transition = h.flow.handle(button(Button::Submit), h.now, &config);
+    assert_eq!(transition.commands, vec![Command::StopTimer]);
+}
+
+#[test]
+fn reset_competitor() {
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    let commands = h.send(button(Button::ResetCompetitor));
+
+    assert_eq!(
+        commands,
+        vec![
+            Command::StopTimer,
+            Command::ResetSolve { clear_saved: false }
+        ]
+    );
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+    assert_eq!(h.flow.current_competitor, None);
+}
+
+#[test]
+fn delegate_requires_competitor_and_group() {
+    let mut h = Harness::new();
+    assert!(!h.flow.can_call_delegate());
+    assert!(h.send(button(Button::CallDelegate)).is_empty());
+
+    let mut h = Harness::with_competitor();
+    h.send(Event::Stackmat(Stackmat::Started));
+    h.send(stopped(9000));
+
+    let commands = h.send(button(Button::CallDelegate));
+    let Some(Command::CallDelegate(submission)) = commands.first() else {
+        panic!("delegate not called: {commands:?}");
+    };
+    assert_eq!(submission.solve_time, 9000);
+    assert_eq!(submission.judge_id, 0);
+}
+
+#[test]
+fn delegate_changes_result() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+    h.send(button(Button::CallDelegate));
+
+    h.send(Event::Delegate(DelegateDecision {
+        solve_time: Some(8500),
+        penalty: Some(2),
+        should_scan_cards: true,
+    }));
+
+    assert_eq!(h.flow.scene, Scene::Finished);
+    assert_eq!(h.flow.solve_time, Some(8500));
+    assert_eq!(h.flow.penalty, Some(2));
+    assert!(h.flow.time_confirmed);
+    assert!(h.flow.delegate_used);
+
+    // delegate can be called only once per solve
+    assert!(h.send(button(Button::CallDelegate)).is_empty());
+
+    // cards still have to be scanned
+    h.send(card(JUDGE, vec![]));
+    let commands = h.send(card(COMPETITOR, vec![]));
+    assert_eq!(submitted(&commands).map(|s| s.solve_time), Some(8500));
+}
+
+#[test]
+fn delegate_keeps_result_and_finishes() {
+    let mut h = Harness::with_competitor();
+    h.solve(1000, 9000);
+
+    let commands = h.send(Event::Delegate(DelegateDecision {
+        solve_time: None,
+        penalty: None,
+        should_scan_cards: false,
+    }));
+
+    assert!(commands.contains(&Command::ResetSolve { clear_saved: true }));
+    assert_eq!(h.flow.scene, Scene::WaitingForCompetitor);
+    assert_eq!(h.flow.solve_time, None);
+}
+
+#[test]
+fn changed_flag() {
+    let mut h = Harness::with_competitor();
+    let transition = h.flow.handle(button(Button::Penalty), h.now, &CONFIG);
+    assert!(!transition.changed);
+
+    let transition = h
+        .flow
+        .handle(button(Button::InspectionStart), h.now, &CONFIG);
+    assert!(transition.changed);
+}
+
+#[test]
+fn inspection_warnings_crossed() {
+    let crossed = |prev, elapsed| InspectionWarning::crossed(prev, elapsed, &CONFIG);
+
+    assert_eq!(crossed(0, 7999), None);
+    assert_eq!(crossed(7990, 8000), Some(InspectionWarning::Call8s));
+    assert_eq!(crossed(8000, 8030), None);
+    assert_eq!(crossed(11990, 12020), Some(InspectionWarning::Call12s));
+    assert_eq!(crossed(14990, 15020), Some(InspectionWarning::Plus2));
+    assert_eq!(crossed(16990, 17020), Some(InspectionWarning::Dnf));
+
+    // frame skipped over multiple thresholds
+    assert_eq!(crossed(7000, 16000), Some(InspectionWarning::Plus2));
+}
+
+#[test]
+fn inspection_warning_patterns() {
+    let warning = InspectionWarning::Call12s;
+    assert!(warning.is_on(0));
+    assert!(!warning.is_on(150));
+    assert!(!warning.is_on(249));
+    assert!(warning.is_on(250));
+    assert!(!warning.is_on(400));
+
+    assert!(InspectionWarning::Dnf.is_on(799));
+    assert!(!InspectionWarning::Dnf.is_on(800));
+}
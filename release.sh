@@ -23,21 +23,28 @@ while [ -z "$RELEASE_VERSION" ]; do
     read RELEASE_VERSION
 done
 
+if [ -z "$OTA_SIGN_KEY" ] || [ ! -f "$OTA_SIGN_KEY" ]; then
+    echo "'OTA_SIGN_KEY' must point to ed25519 private key (pem)"
+    exit
+fi
+
 source ~/export-esp.sh
 EPOCH=$(date +%s)
 
 mkdir -p ./dist
 
-RELEASE_BUILD="$RELEASE_VERSION" cargo build -r --no-default-features --features v3,sleep
+BUILD_TIME="$EPOCH" RELEASE_BUILD="$RELEASE_VERSION" cargo build -r --no-default-features --features v3,sleep
 mkdir -p /tmp/fkm-build &> /dev/null
 espflash save-image --chip esp32c3 ./target/riscv32imc-unknown-none-elf/release/fkm-firmware "/tmp/fkm-build/v3_STATION_${RELEASE_VERSION}.bin"
 ./append_metadata.sh "/tmp/fkm-build/v3_STATION_${RELEASE_VERSION}.bin" "$RELEASE_VERSION" "STATION" "v3" "$EPOCH"
+./sign_firmware.sh "/tmp/fkm-build/v3_STATION_${RELEASE_VERSION}.bin" "$OTA_SIGN_KEY"
 cp ./target/riscv32imc-unknown-none-elf/release/fkm-firmware /tmp/fkm-build/"v3_STATION_${RELEASE_VERSION}"
 espflash save-image --chip esp32c3 --merge --flash-size 4mb --partition-table partitions.csv target/riscv32imc-unknown-none-elf/release/fkm-firmware dist/"v3_STATION_${RELEASE_VERSION}_MERGED.bin"
 
-RELEASE_BUILD="$RELEASE_VERSION" cargo build -r --no-default-features --features v4,sleep
+BUILD_TIME="$EPOCH" RELEASE_BUILD="$RELEASE_VERSION" cargo build -r --no-default-features --features v4,sleep
 espflash save-image --chip esp32c3 ./target/riscv32imc-unknown-none-elf/release/fkm-firmware "/tmp/fkm-build/v4_STATION_${RELEASE_VERSION}.bin"
 ./append_metadata.sh "/tmp/fkm-build/v4_STATION_${RELEASE_VERSION}.bin" "$RELEASE_VERSION" "STATION" "v4" "$EPOCH"
+./sign_firmware.sh "/tmp/fkm-build/v4_STATION_${RELEASE_VERSION}.bin" "$OTA_SIGN_KEY"
 cp ./target/riscv32imc-unknown-none-elf/release/fkm-firmware /tmp/fkm-build/"v4_STATION_${RELEASE_VERSION}"
 espflash save-image --chip esp32c3 --merge --flash-size 4mb --partition-table partitions.csv target/riscv32imc-unknown-none-elf/release/fkm-firmware dist/"v4_STATION_${RELEASE_VERSION}_MERGED.bin"
 
@@ -59,7 +66,7 @@ if gh release view "$RELEASE_VERSION" ; then
 fi
 
 
-BUILD_FILES=$(ls /tmp/fkm-build/*_"$RELEASE_VERSION".bin /tmp/fkm-build/*_"$RELEASE_VERSION")
+BUILD_FILES=$(ls /tmp/fkm-build/*_"$RELEASE_VERSION".bin /tmp/fkm-build/*_"$RELEASE_VERSION".bin.sig /tmp/fkm-build/*_"$RELEASE_VERSION")
 if [ -z "$BUILD_FILES" ]; then
     echo "No build files found"
     exit
@@ -0,0 +1,24 @@
+#!/bin/bash
+# Signs "allow downgrade" flag for OTA (lets stations install older <version>)
+set -e
+
+usage() {
+    echo "Usage: $0 <version> <firmware> <hardware> <private_key.pem>"
+    echo "  <version>:         Version that is allowed to be installed"
+    echo "  <firmware>:        Firmware string (e.g. STATION)"
+    echo "  <hardware>:        Hardware string (e.g. v4)"
+    echo "  <private_key.pem>: Ed25519 private key used for signing releases"
+    echo ""
+    echo "Prints hex signature for StartUpdate 'downgrade_signature'"
+    exit 1
+}
+
+if [ $# -ne 4 ]; then
+    usage
+fi
+
+temp_file=$(mktemp)
+trap 'rm -f "$temp_file"' EXIT
+
+printf "allow_downgrade|%s|%s|%s" "$2" "$3" "$1" > "$temp_file"
+openssl pkeyutl -sign -inkey "$4" -rawin -in "$temp_file" | xxd -p -c 64
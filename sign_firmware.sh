@@ -0,0 +1,33 @@
+#!/bin/bash
+# Signs firmware image for OTA (ed25519 signature of sha256 digest of whole .bin, metadata included)
+set -e
+
+usage() {
+    echo "Usage: $0 <binary_file> <private_key.pem>"
+    echo "  <binary_file>:     Path to the .bin file (after append_metadata.sh)"
+    echo "  <private_key.pem>: Ed25519 private key (openssl genpkey -algorithm ed25519)"
+    echo ""
+    echo "Writes hex signature to <binary_file>.sig"
+    echo "Public key for OTA_SIGN_PUBKEY: openssl pkey -in <private_key.pem> -pubout -outform der | tail -c 32 | xxd -p -c 32"
+    exit 1
+}
+
+if [ $# -ne 2 ]; then
+    usage
+fi
+
+binary_file="$1"
+private_key="$2"
+
+if [ ! -f "$binary_file" ]; then
+    echo "Error: File '$binary_file' does not exist"
+    exit 1
+fi
+
+temp_file=$(mktemp)
+trap 'rm -f "$temp_file"' EXIT
+
+openssl dgst -sha256 -binary "$binary_file" > "$temp_file"
+openssl pkeyutl -sign -inkey "$private_key" -rawin -in "$temp_file" | xxd -p -c 64 > "$binary_file.sig"
+
+echo "Signature written to '$binary_file.sig'"
@@ -0,0 +1,197 @@
+//! Keyboard (buttons) and stdin commands (card scans, stackmat), same actions as
+//! firmware `TestPacketData`.
+
+use crate::{SimEvent, station::Button, term};
+use crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::{io::BufRead, sync::mpsc::Sender, time::Duration};
+
+const SHORT_PRESS_MS: u64 = 50;
+const HOLD_PRESS_MS: u64 = 1500;
+const LONG_HOLD_PRESS_MS: u64 = 3500;
+
+pub const HELP: &str = "\
+Keys:
+  1 2 3 4       press button (first..fourth)
+  q w e r       hold button for 1.5s (inspection cancel, DNF)
+  a s d f       hold button for 3.5s (delegate, reset competitor, DNS)
+  space         start/stop stackmat
+  x             reset stackmat
+  :             type command
+  ctrl+c        quit
+Commands:
+  card <id>                  scan card
+  press <1-4> [ms]           press button for given time
+  stackmat start|stop|reset  control stackmat
+  time <ms>                  stackmat run with given time
+  reset                      reset solve state
+  add                        send add device packet
+  sleep <ms>                 wait (scripts)
+  help                       show this help
+  quit                       exit";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Press(Button, u64),
+    Card(u64),
+    StackmatToggle,
+    StackmatStart,
+    StackmatStop,
+    StackmatReset,
+    StackmatTime(u64),
+    ResetState,
+    Add,
+    Help,
+    Quit,
+}
+
+fn button(idx: &str) -> Result<Button, String> {
+    match idx {
+        "1" => Ok(Button::First),
+        "2" => Ok(Button::Second),
+        "3" => Ok(Button::Third),
+        "4" => Ok(Button::Fourth),
+        _ => Err(format!("Unknown button: {idx}")),
+    }
+}
+
+fn number(arg: Option<&str>) -> Result<u64, String> {
+    let arg = arg.ok_or("Missing number")?;
+    arg.parse().map_err(|_| format!("Invalid number: {arg}"))
+}
+
+/// `Ok(None)` for empty lines and comments
+fn parse_command(line: &str) -> Result<Option<Input>, String> {
+    let mut args = line.split_whitespace();
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+
+    let input = match command {
+        _ if command.starts_with('#') => return Ok(None),
+        "card" | "c" => Input::Card(number(args.next())?),
+        "press" | "p" => {
+            let button = button(args.next().ok_or("Missing button")?)?;
+            let press_ms = match args.next() {
+                Some(ms) => number(Some(ms))?,
+                None => SHORT_PRESS_MS,
+            };
+
+            Input::Press(button, press_ms)
+        }
+        "stackmat" | "s" => match args.next() {
+            Some("start") => Input::StackmatStart,
+            Some("stop") => Input::StackmatStop,
+            Some("reset") => Input::StackmatReset,
+            _ => return Err("Usage: stackmat start|stop|reset".to_string()),
+        },
+        "time" | "t" => Input::StackmatTime(number(args.next())?),
+        "reset" => Input::ResetState,
+        "add" => Input::Add,
+        "help" | "h" => Input::Help,
+        "quit" | "exit" => Input::Quit,
+        _ => return Err(format!("Unknown command: {command}")),
+    };
+
+    Ok(Some(input))
+}
+
+/// Runs command, `sleep` is executed in place (so scripts can wait for server)
+fn run_command(line: &str, events: &Sender<SimEvent>) {
+    if let Some(ms) = line.trim().strip_prefix("sleep ") {
+        match number(Some(ms.trim())) {
+            Ok(ms) => std::thread::sleep(Duration::from_millis(ms)),
+            Err(e) => log::error!("{e}"),
+        }
+
+        return;
+    }
+
+    match parse_command(line) {
+        Ok(Some(input)) => _ = events.send(SimEvent::Input(input)),
+        Ok(None) => {}
+        Err(e) => log::error!("{e}"),
+    }
+}
+
+pub fn spawn(interactive: bool, events: Sender<SimEvent>) {
+    std::thread::spawn(move || {
+        if interactive {
+            keyboard_loop(&events);
+        } else {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                run_command(&line, &events);
+            }
+        }
+
+        _ = events.send(SimEvent::Input(Input::Quit));
+    });
+}
+
+fn key_input(key: KeyEvent) -> Option<Input> {
+    let press = |button, ms| Some(Input::Press(button, ms));
+    match key.code {
+        KeyCode::Char('1') => press(Button::First, SHORT_PRESS_MS),
+        KeyCode::Char('2') => press(Button::Second, SHORT_PRESS_MS),
+        KeyCode::Char('3') => press(Button::Third, SHORT_PRESS_MS),
+        KeyCode::Char('4') => press(Button::Fourth, SHORT_PRESS_MS),
+        KeyCode::Char('q') => press(Button::First, HOLD_PRESS_MS),
+        KeyCode::Char('w') => press(Button::Second, HOLD_PRESS_MS),
+        KeyCode::Char('e') => press(Button::Third, HOLD_PRESS_MS),
+        KeyCode::Char('r') => press(Button::Fourth, HOLD_PRESS_MS),
+        KeyCode::Char('a') => press(Button::First, LONG_HOLD_PRESS_MS),
+        KeyCode::Char('s') => press(Button::Second, LONG_HOLD_PRESS_MS),
+        KeyCode::Char('d') => press(Button::Third, LONG_HOLD_PRESS_MS),
+        KeyCode::Char('f') => press(Button::Fourth, LONG_HOLD_PRESS_MS),
+        KeyCode::Char(' ') => Some(Input::StackmatToggle),
+        KeyCode::Char('x') => Some(Input::StackmatReset),
+        KeyCode::Char('h') => Some(Input::Help),
+        _ => None,
+    }
+}
+
+fn keyboard_loop(events: &Sender<SimEvent>) {
+    let mut command: Option<String> = None;
+    while let Ok(event) = event::read() {
+        let event::Event::Key(key) = event else {
+            continue;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return;
+        }
+
+        let Some(line) = command.as_mut() else {
+            if key.code == KeyCode::Char(':') {
+                command = Some(String::new());
+                term::set_prompt(command.clone());
+            } else if let Some(input) = key_input(key) {
+                _ = events.send(SimEvent::Input(input));
+            }
+
+            continue;
+        };
+
+        match key.code {
+            KeyCode::Char(c) => line.push(c),
+            KeyCode::Backspace => _ = line.pop(),
+            KeyCode::Enter => {
+                let line = command.take().unwrap_or_default();
+                term::set_prompt(None);
+                run_command(&line, events);
+                continue;
+            }
+            KeyCode::Esc => _ = command.take(),
+            _ => {}
+        }
+
+        term::set_prompt(command.clone());
+    }
+}
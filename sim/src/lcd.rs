@@ -0,0 +1,247 @@
+//! Text version of v3 LCD (16x2). Screens mirror `lcd_v3::process_lcd`.
+
+use crate::station::Station;
+use fkm_common::{
+    lcd::{LcdAbstract, PrintAlign},
+    time::{ms_to_time_str, previous_attempts_str},
+    translations::TranslationKey,
+};
+use fkm_flow::Scene;
+
+pub const LCD_WIDTH: usize = 16;
+
+/// Same buffer as firmware `lcd_v3` (lines longer than `LCD_WIDTH` are kept whole,
+/// firmware scrolls them)
+pub type Lcd = LcdAbstract<80, LCD_WIDTH, 2, 3>;
+
+pub fn render(station: &Station, now: u64) -> Lcd {
+    let mut lcd = Lcd::new();
+    let t = &station.translations;
+    let flow = &station.flow;
+
+    if let Some(error_text) = &station.error_text {
+        _ = lcd.print(
+            0,
+            &t.get(TranslationKey::ERROR_HEADER),
+            PrintAlign::Center,
+            true,
+        );
+        _ = lcd.print(1, error_text, PrintAlign::Center, true);
+        return lcd;
+    }
+
+    if let Some((line1, line2)) = &station.custom_message {
+        _ = lcd.print(0, line1, PrintAlign::Center, true);
+        _ = lcd.print(1, line2, PrintAlign::Center, true);
+        return lcd;
+    }
+
+    if render_overwrite(station, &mut lcd) {
+        return lcd;
+    }
+
+    if let Some(time) = station.delegate_hold {
+        let delegate_remaining = 3 - time;
+        if delegate_remaining == 0 {
+            _ = lcd.print(
+                0,
+                &t.get(TranslationKey::WAITING_FOR_DELEGATE_HEADER),
+                PrintAlign::Center,
+                true,
+            );
+            _ = lcd.print(
+                1,
+                &t.get(TranslationKey::WAITING_FOR_DELEGATE_FOOTER),
+                PrintAlign::Center,
+                true,
+            );
+        } else {
+            _ = lcd.print(
+                0,
+                &t.get(TranslationKey::CALLING_FOR_DELEGATE_HEADER),
+                PrintAlign::Center,
+                true,
+            );
+            _ = lcd.print(
+                1,
+                &t.get_params(
+                    TranslationKey::CALLING_FOR_DELEGATE_FOOTER,
+                    &[delegate_remaining],
+                ),
+                PrintAlign::Center,
+                true,
+            );
+        }
+
+        return lcd;
+    }
+
+    if flow.dns_pending {
+        _ = lcd.print(
+            0,
+            &t.get(TranslationKey::DNS_CONFIRM_HEADER),
+            PrintAlign::Center,
+            true,
+        );
+        _ = lcd.print(
+            1,
+            &t.get(TranslationKey::DNS_CONFIRM_FOOTER),
+            PrintAlign::Center,
+            true,
+        );
+
+        return lcd;
+    }
+
+    match flow.scene {
+        Scene::GroupSelect => {
+            _ = lcd.print(0, "<", PrintAlign::Left, false);
+            _ = lcd.print(1, "<", PrintAlign::Left, false);
+            _ = lcd.print(0, ">", PrintAlign::Right, false);
+            _ = lcd.print(1, ">", PrintAlign::Right, false);
+            _ = lcd.print(
+                0,
+                &t.get(TranslationKey::SELECT_GROUP),
+                PrintAlign::Center,
+                false,
+            );
+
+            if let Some(group) = flow.possible_groups.get(flow.group_selected_idx) {
+                _ = lcd.print(1, &group.name, PrintAlign::Center, false);
+            }
+        }
+        Scene::WaitingForCompetitor => {
+            _ = lcd.print(
+                0,
+                &t.get(TranslationKey::SCAN_COMPETITOR_CARD_HEADER),
+                PrintAlign::Center,
+                true,
+            );
+
+            let footer = match flow.solve_time {
+                Some(solve_time) => t.get_params(
+                    TranslationKey::SCAN_COMPETITOR_CARD_WITH_TIME_FOOTER,
+                    &[ms_to_time_str(solve_time)],
+                ),
+                None => t.get(TranslationKey::SCAN_COMPETITOR_CARD_FOOTER),
+            };
+            _ = lcd.print(1, &footer, PrintAlign::Center, true);
+        }
+        Scene::CompetitorInfo => {
+            _ = lcd.print(
+                0,
+                flow.competitor_display.as_deref().unwrap_or("??????"),
+                PrintAlign::Center,
+                true,
+            );
+
+            if let Some(group) = &flow.solve_group {
+                let footer = match (group.attempt_number, group.attempts_total) {
+                    (Some(number), Some(total)) => {
+                        let mut footer =
+                            t.get_params(TranslationKey::ATTEMPT_NUMBER, &[number, total]);
+                        if !group.previous_attempts.is_empty() {
+                            footer += " ";
+                            footer += &previous_attempts_str(&group.previous_attempts);
+                        }
+
+                        footer
+                    }
+                    _ => group.name.clone(),
+                };
+
+                _ = lcd.print(1, &footer, PrintAlign::Center, true);
+            }
+        }
+        Scene::Inspection => {
+            let elapsed = now.saturating_sub(flow.inspection_start.unwrap_or(now));
+            _ = lcd.print(0, &ms_to_time_str(elapsed), PrintAlign::Center, true);
+            _ = lcd.print(1, "Inspection", PrintAlign::Center, true);
+        }
+        Scene::Timer => {
+            let time = station.stackmat_time(now).unwrap_or(0);
+            _ = lcd.print(0, &ms_to_time_str(time), PrintAlign::Center, true);
+        }
+        Scene::Finished => {
+            let solve_time = flow.solve_time.unwrap_or(0);
+            let time_str = if solve_time > 0 {
+                ms_to_time_str(solve_time).to_string()
+            } else {
+                String::new()
+            };
+
+            let inspection_time = flow.inspection_time() as u64;
+            if flow.use_inspection() && inspection_time > station.settings.inspection_plus2_ms {
+                let inspection_seconds = inspection_time / 1000;
+                _ = lcd.print(
+                    0,
+                    &format!("{time_str} ({inspection_seconds}s)"),
+                    PrintAlign::Left,
+                    true,
+                );
+            } else {
+                _ = lcd.print(0, &time_str, PrintAlign::Left, true);
+            }
+
+            let penalty = flow.penalty.unwrap_or(0);
+            let penalty_str = match penalty {
+                -2 => "DNS".to_string(),
+                -1 => "DNF".to_string(),
+                1.. => format!("+{penalty}"),
+                _ => String::new(),
+            };
+            _ = lcd.print(0, &penalty_str, PrintAlign::Right, false);
+
+            let status = if !flow.time_confirmed {
+                Some(t.get(TranslationKey::CONFIRM_TIME))
+            } else if flow.current_judge.is_none() {
+                Some(t.get(TranslationKey::SCAN_JUDGE_CARD))
+            } else if flow.current_competitor.is_some() {
+                Some(t.get(TranslationKey::SCAN_COMPETITOR_CARD))
+            } else {
+                None
+            };
+
+            let status = match status {
+                Some(status) if flow.cutoff_missed() => Some(format!(
+                    "{} {status}",
+                    t.get(TranslationKey::CUTOFF_NOT_MET)
+                )),
+                status => status,
+            };
+
+            if let Some(status) = status {
+                _ = lcd.print(1, &status, PrintAlign::Right, true);
+            }
+        }
+        Scene::Update | Scene::WifiConnect | Scene::AutoSetupWait | Scene::MdnsWait => {}
+    }
+
+    lcd
+}
+
+/// Connection status screens (only shown in scenes that can be overwritten)
+fn render_overwrite(station: &Station, lcd: &mut Lcd) -> bool {
+    if !station.flow.scene.can_be_lcd_overwritten() {
+        return false;
+    }
+
+    let t = &station.translations;
+    let (header, footer) = if !station.server_connected {
+        (
+            TranslationKey::SERVER_DISCONNECTED_HEADER,
+            TranslationKey::SERVER_DISCONNECTED_FOOTER,
+        )
+    } else if station.device_added == Some(false) {
+        (
+            TranslationKey::DEVICE_NOT_ADDED_HEADER,
+            TranslationKey::DEVICE_NOT_ADDED_FOOTER,
+        )
+    } else {
+        return false;
+    };
+
+    _ = lcd.print(0, &t.get(header), PrintAlign::Center, true);
+    _ = lcd.print(1, &t.get(footer), PrintAlign::Center, true);
+    true
+}
@@ -0,0 +1,156 @@
+//! Station simulator for Linux. Runs competition flow, translations and LCD screens
+//! against local FKM server (plain `ws://` only).
+//!
+//! Usage: `sim [ws://host:port/path] [--id <device id>] [--debug]`
+//!
+//! When stdin isn't a terminal, it's read as command script (see `input::HELP`), so
+//! whole competition flow can be replayed with: `sim < script.txt`
+
+use input::Input;
+use std::{
+    io::IsTerminal,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+mod input;
+mod lcd;
+mod packets;
+mod station;
+mod term;
+mod ws;
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:8080";
+const DEFAULT_DEVICE_ID: u32 = 1;
+const FRAME_MS: u64 = 33;
+
+pub enum SimEvent {
+    Ws(ws::WsEvent),
+    Input(Input),
+}
+
+struct Args {
+    url: String,
+    device_id: u32,
+    debug: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        url: DEFAULT_URL.to_string(),
+        device_id: DEFAULT_DEVICE_ID,
+        debug: false,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--id" => {
+                let id = iter.next().ok_or("Missing device id")?;
+                args.device_id = id.parse().map_err(|_| format!("Invalid device id: {id}"))?;
+            }
+            "--debug" => args.debug = true,
+            "-h" | "--help" => {
+                return Err(format!(
+                    "Usage: sim [url (default: {DEFAULT_URL})] [--id <device id>] [--debug]\n\n{}",
+                    input::HELP
+                ));
+            }
+            _ if arg.starts_with("wss://") => {
+                return Err("wss:// isn't supported, use plain ws:// to local server".to_string());
+            }
+            _ if arg.starts_with("ws://") => args.url = arg,
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+
+    Ok(args)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let interactive = std::io::stdin().is_terminal();
+    let level = if args.debug {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    term::init(interactive, level);
+
+    let (events_tx, events_rx) = mpsc::channel();
+    let (ws_tx, ws_rx) = mpsc::channel();
+    ws::spawn(args.url, args.device_id, events_tx.clone(), ws_rx);
+    input::spawn(interactive, events_tx);
+
+    let mut station = station::Station::new(ws_tx);
+    log::info!("Device id: {} (h: help)", args.device_id);
+
+    loop {
+        let event = events_rx.recv_timeout(Duration::from_millis(FRAME_MS));
+        let ticked = match event {
+            Ok(SimEvent::Input(Input::Quit)) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(SimEvent::Input(input)) => {
+                handle_input(&mut station, input);
+                false
+            }
+            Ok(SimEvent::Ws(ws::WsEvent::Connected)) => {
+                station.on_connected();
+                false
+            }
+            Ok(SimEvent::Ws(ws::WsEvent::Disconnected)) => {
+                station.on_disconnected();
+                false
+            }
+            Ok(SimEvent::Ws(ws::WsEvent::Packet(packet))) => {
+                station.on_packet(*packet);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+        };
+
+        station.tick();
+
+        // running timers would print every frame in script mode
+        if interactive || !ticked {
+            term::set_lcd(lcd::render(&station, station.now()));
+        }
+    }
+
+    term::shutdown();
+}
+
+fn handle_input(station: &mut station::Station, input: Input) {
+    match input {
+        Input::Press(button, press_ms) => station.press(button, press_ms),
+        Input::Card(card_id) => station.scan_card(card_id),
+        Input::StackmatToggle => {
+            if station.stackmat_time(station.now()).is_some() {
+                station.stackmat_stop(None);
+            } else {
+                station.stackmat_start();
+            }
+        }
+        Input::StackmatStart => station.stackmat_start(),
+        Input::StackmatStop => station.stackmat_stop(None),
+        Input::StackmatReset => station.stackmat_reset(),
+        Input::StackmatTime(time) => {
+            station.stackmat_start();
+            station.stackmat_stop(Some(time));
+        }
+        Input::ResetState => station.reset_state(),
+        Input::Add => station.add_device(),
+        Input::Help => {
+            for line in input::HELP.lines() {
+                term::print_line(line);
+            }
+        }
+        Input::Quit => {}
+    }
+}
@@ -0,0 +1,46 @@
+//! Packets and settings are shared with firmware (`fkm_common`), simulator only
+//! understands subset of them.
+
+pub use fkm_common::{
+    packets::*,
+    settings::{Settings, SettingsUpdate},
+};
+
+/// Names of `TimerPacketInner` variants simulator understands (sent in Hello packet)
+pub const SUPPORTED_PACKETS: &[&str] = &[
+    "solve",
+    "solve_confirm",
+    "delegate_response",
+    "api_error",
+    "custom_message",
+    "card_info_request",
+    "card_info_response",
+    "device_settings",
+    "add",
+    "epoch_time",
+    "hello",
+    "hello_response",
+    "set_settings",
+    "effective_settings",
+];
+
+/// Applies inspection settings (other settings aren't simulated)
+pub fn apply_settings(settings: &mut Settings, update: SettingsUpdate) {
+    settings.inspection_warnings = update
+        .inspection_warnings
+        .unwrap_or(settings.inspection_warnings);
+    settings.inspection_plus2_ms = update
+        .inspection_plus2_ms
+        .unwrap_or(settings.inspection_plus2_ms);
+    settings.inspection_dnf_ms = update
+        .inspection_dnf_ms
+        .unwrap_or(settings.inspection_dnf_ms);
+
+    if settings.inspection_plus2_ms == 0
+        || settings.inspection_dnf_ms < settings.inspection_plus2_ms
+    {
+        let default = Settings::default();
+        settings.inspection_plus2_ms = default.inspection_plus2_ms;
+        settings.inspection_dnf_ms = default.inspection_dnf_ms;
+    }
+}
@@ -0,0 +1,617 @@
+//! Station state. Competition flow is shared with firmware (`fkm_flow`), side effects
+//! mirror firmware tasks (`buttons.rs`, `rfid.rs`, `stackmat.rs` and `ws.rs`).
+
+use crate::packets::{
+    CardInfoResponsePacket, DELEGATE_TAG, DelegateResponsePacket, HelloPacket, PROTOCOL_SETTINGS,
+    PROTOCOL_VERSION, PossibleGroup, SUPPORTED_PACKETS, Settings, SolveConfirmPacket, TimerPacket,
+    TimerPacketInner, apply_settings,
+};
+use fkm_common::translations::{TRANSLATIONS_COUNT, TranslationKey, Translations};
+use fkm_flow::{
+    Button as FlowButton, CardInfo, Command, DelegateDecision, Event, Flow, FlowConfig,
+    InspectionWarning, Notice, Scene, Stackmat, Submission, Transition,
+};
+use std::{
+    collections::HashMap,
+    sync::mpsc::Sender,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const FIRMWARE: &str = "SIM";
+pub const HW_VER: &str = "sim";
+
+const SOLVE_MESSAGE_MS: u64 = 3000;
+const CARDS_CANNOT_BE_THE_SAME_MS: u64 = 8000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    First,
+    Second,
+    Third,
+    Fourth,
+}
+
+#[derive(Debug)]
+enum Request {
+    CardInfo,
+    Solve,
+    Delegate,
+}
+
+pub struct Station {
+    pub flow: Flow<PossibleGroup>,
+    pub settings: Settings,
+    pub translations: Translations,
+    pub error_text: Option<String>,
+    pub custom_message: Option<(String, String)>,
+    pub delegate_hold: Option<u8>,
+    pub server_connected: bool,
+    pub device_added: Option<bool>,
+
+    session_id: Option<String>,
+    group_limit: Option<u64>,
+
+    /// Uptime (ms) when custom message should be cleared
+    custom_message_until: Option<u64>,
+
+    /// Uptime (ms) when stackmat started running
+    stackmat_start: Option<u64>,
+
+    /// Inspection time at last tick (warning thresholds are checked between ticks)
+    last_inspection_elapsed: Option<u64>,
+    requests: HashMap<u64, Request>,
+    next_tag: u64,
+    boot: Instant,
+    ws_tx: Sender<TimerPacket>,
+}
+
+impl Station {
+    pub fn new(ws_tx: Sender<TimerPacket>) -> Self {
+        Self {
+            flow: Flow::new(Scene::WaitingForCompetitor),
+            settings: Settings::default(),
+            translations: Translations::default(),
+            error_text: None,
+            custom_message: None,
+            delegate_hold: None,
+            server_connected: false,
+            device_added: None,
+
+            session_id: None,
+            group_limit: None,
+            custom_message_until: None,
+            stackmat_start: None,
+            last_inspection_elapsed: None,
+            requests: HashMap::new(),
+            next_tag: 1,
+            boot: Instant::now(),
+            ws_tx,
+        }
+    }
+
+    /// Uptime in ms (flow instants use it)
+    pub fn now(&self) -> u64 {
+        self.boot.elapsed().as_millis() as u64
+    }
+
+    pub fn stackmat_time(&self, now: u64) -> Option<u64> {
+        self.stackmat_start.map(|start| now.saturating_sub(start))
+    }
+
+    /// Called periodically (clears timed messages, enforces group time limit)
+    pub fn tick(&mut self) {
+        let now = self.now();
+        if self.custom_message_until.is_some_and(|until| now >= until) {
+            self.custom_message = None;
+            self.custom_message_until = None;
+        }
+
+        self.check_inspection_warning(now);
+        if let Some(time) = self.stackmat_time(now)
+            && let Some(limit) = self.group_limit
+            && time > limit
+        {
+            log::warn!("Group time limit reached ({limit}ms)");
+            self.group_limit = None;
+            self.time_end(limit, true);
+        }
+    }
+
+    /// Same as firmware `inspection::inspection_warnings_task` (buzzer and flash are logged)
+    fn check_inspection_warning(&mut self, now: u64) {
+        let elapsed = match (&self.flow.scene, self.flow.inspection_start) {
+            (Scene::Inspection, Some(start)) => now.saturating_sub(start),
+            _ => {
+                self.last_inspection_elapsed = None;
+                return;
+            }
+        };
+
+        let last_elapsed = self
+            .last_inspection_elapsed
+            .replace(elapsed)
+            .unwrap_or(elapsed);
+        if self.settings.inspection_warnings
+            && let Some(warning) =
+                InspectionWarning::crossed(last_elapsed, elapsed, &self.flow_config())
+        {
+            log::warn!(
+                "Inspection warning: {warning:?} (pattern: {:?})",
+                warning.pattern()
+            );
+        }
+    }
+
+    fn should_skip_other_actions(&self) -> bool {
+        self.error_text.is_some()
+            || (self.flow.scene.can_be_lcd_overwritten() && !self.server_connected)
+            || self.flow.scene <= Scene::MdnsWait
+    }
+
+    fn flow_config(&self) -> FlowConfig {
+        FlowConfig {
+            inspection_plus2_ms: self.settings.inspection_plus2_ms,
+            inspection_dnf_ms: self.settings.inspection_dnf_ms,
+            submit_stops_timer: true,
+        }
+    }
+
+    /// Same as `SignaledGlobalStateInner::handle_flow_event` in firmware
+    fn handle_flow(&mut self, event: Event<PossibleGroup>) -> Transition<PossibleGroup> {
+        let config = self.flow_config();
+        let transition = self.flow.handle(event, self.now(), &config);
+
+        for command in &transition.commands {
+            match command {
+                Command::SetGroupLimit(limit) => self.group_limit = *limit,
+                Command::SaveState => {
+                    if self.session_id.is_none() {
+                        self.session_id = Some(uuid::Uuid::new_v4().to_string());
+                    }
+                }
+                Command::ResetSolve { .. } => self.clear_solve_session(),
+                Command::Notify(Notice::EmptyGroups) => {
+                    self.error_text =
+                        Some(self.translations.get(TranslationKey::EMPTY_GROUPS_ERROR));
+                }
+                Command::Notify(Notice::SolveGroupMissing) => {
+                    log::error!("Solve group is none! (How would that happen?)");
+                }
+                _ => {}
+            }
+        }
+
+        transition
+    }
+
+    fn clear_solve_session(&mut self) {
+        self.group_limit = None;
+        self.session_id = None;
+        self.translations.restore_default_locale();
+    }
+
+    fn reset_solve_state(&mut self) {
+        self.flow.reset();
+        self.clear_solve_session();
+    }
+
+    fn set_custom_message(&mut self, line1: String, line2: String, show_ms: Option<u64>) {
+        self.custom_message = Some((line1, line2));
+        self.custom_message_until = show_ms.map(|ms| self.now() + ms);
+    }
+
+    fn request(&mut self, request: Request, data: TimerPacketInner) {
+        let tag = match request {
+            Request::Delegate => DELEGATE_TAG,
+            _ => {
+                self.next_tag += 1;
+                self.next_tag
+            }
+        };
+
+        self.requests.insert(tag, request);
+        self.send(Some(tag), data);
+    }
+
+    fn send(&self, tag: Option<u64>, data: TimerPacketInner) {
+        _ = self.ws_tx.send(TimerPacket { tag, data });
+    }
+
+    pub fn on_connected(&mut self) {
+        self.server_connected = true;
+        self.send(
+            None,
+            TimerPacketInner::Hello(HelloPacket {
+                protocol_version: PROTOCOL_VERSION,
+                features: vec!["sim".to_string()],
+                packets: SUPPORTED_PACKETS.iter().map(|p| p.to_string()).collect(),
+                translations_count: TRANSLATIONS_COUNT,
+                ws_buf_size: 8192,
+                tls_buf_size: 0,
+            }),
+        );
+    }
+
+    pub fn on_disconnected(&mut self) {
+        self.server_connected = false;
+        if !self.requests.is_empty() {
+            log::warn!("Dropping {} pending request(s)", self.requests.len());
+            self.requests.clear();
+            self.delegate_hold = None;
+        }
+    }
+
+    pub fn on_packet(&mut self, packet: TimerPacket) {
+        let request = packet.tag.and_then(|tag| self.requests.remove(&tag));
+        match packet.data {
+            TimerPacketInner::CardInfoResponse(resp) => match request {
+                Some(Request::CardInfo) => self.on_card_info(resp),
+                _ => log::warn!("Unexpected card info response: {resp:?}"),
+            },
+            TimerPacketInner::SolveConfirm(confirm) => match request {
+                Some(Request::Solve) => self.on_solve_confirm(confirm),
+                _ => log::info!("Solve confirmed: {}", confirm.session_id),
+            },
+            TimerPacketInner::DelegateResponse(resp) => self.on_delegate_response(resp),
+            TimerPacketInner::ApiError(e) => {
+                log::error!("Api error ({}): {}", e.should_reset_time, e.error);
+                match request {
+                    Some(Request::CardInfo) if e.should_reset_time => self.reset_solve_state(),
+                    Some(Request::Delegate) => self.delegate_hold = None,
+                    _ => {}
+                }
+
+                self.error_text = Some(e.error);
+            }
+            TimerPacketInner::CustomMessage { line1, line2 } => {
+                self.set_custom_message(line1, line2, None);
+            }
+            TimerPacketInner::DeviceSettings {
+                added,
+                locales,
+                default_locale,
+                ..
+            } => {
+                self.device_added = Some(added);
+                self.translations.clear();
+                for locale in locales {
+                    self.translations
+                        .process_locale(locale.locale, locale.translations);
+                }
+
+                self.translations.select_locale(&default_locale);
+                self.translations.set_default_locale();
+            }
+            TimerPacketInner::SetSettings { version, settings } => {
+                log::info!("Settings update (version {version}): {settings:?}");
+                apply_settings(&mut self.settings, settings);
+                self.send(
+                    packet.tag,
+                    TimerPacketInner::EffectiveSettings(self.settings),
+                );
+            }
+            TimerPacketInner::HelloResponse { protocol_version } => {
+                log::info!("Server protocol version: {protocol_version}");
+                if protocol_version.min(PROTOCOL_VERSION) >= PROTOCOL_SETTINGS {
+                    self.send(None, TimerPacketInner::EffectiveSettings(self.settings));
+                }
+            }
+            TimerPacketInner::EpochTime { .. } => {
+                // host clock is used for solve timestamps
+            }
+            data => log::debug!("Unhandled packet: {data:?}"),
+        }
+    }
+
+    /// Same as `TestPacketData::ResetState`
+    pub fn reset_state(&mut self) {
+        self.stackmat_start = None;
+        self.error_text = None;
+        self.custom_message = None;
+        self.reset_solve_state();
+    }
+
+    pub fn add_device(&self) {
+        self.send(
+            None,
+            TimerPacketInner::Add {
+                firmware: FIRMWARE.to_string(),
+                sign_key: 0,
+                secret_key: None,
+                hmac: None,
+            },
+        );
+    }
+
+    // --- rfid ---
+
+    pub fn scan_card(&mut self, card_id: u64) {
+        log::info!("Card scanned: {card_id}");
+        let is_competitor = self.flow.is_competitor_card(card_id);
+        self.request(
+            Request::CardInfo,
+            TimerPacketInner::CardInfoRequest {
+                card_id,
+                is_competitor,
+                attendance_device: None,
+                sign_key: 0,
+                hmac: None,
+            },
+        );
+    }
+
+    fn on_card_info(&mut self, resp: CardInfoResponsePacket) {
+        if self.should_skip_other_actions() {
+            return;
+        }
+
+        let transition = self.handle_flow(Event::CardInfo(CardInfo {
+            card_id: resp.card_id,
+            display: resp.display,
+            can_compete: resp.can_compete,
+            possible_groups: resp.possible_groups,
+        }));
+
+        for command in transition.commands {
+            match command {
+                Command::CompetitorSelected { card_id }
+                    if self.flow.current_competitor == Some(card_id) =>
+                {
+                    self.translations.select_locale(&resp.country_iso2);
+                }
+                Command::SubmitSolve(submission) => self.send_solve(submission, false),
+                Command::Notify(Notice::CardsCannotBeTheSame) => {
+                    self.set_custom_message(
+                        self.translations
+                            .get(TranslationKey::CARDS_CANNOT_BE_THE_SAME_HEADER),
+                        self.translations
+                            .get(TranslationKey::CARDS_CANNOT_BE_THE_SAME_FOOTER),
+                        Some(CARDS_CANNOT_BE_THE_SAME_MS),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send_solve(&mut self, submission: Submission<PossibleGroup>, delegate: bool) {
+        let session_id = self
+            .session_id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+
+        let epoch_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let packet = TimerPacketInner::Solve {
+            solve_time: submission.solve_time,
+            penalty: submission.penalty as i64,
+            competitor_id: submission.competitor_id,
+            judge_id: submission.judge_id,
+            timestamp: epoch_ms / 1000,
+            timestamp_ms: epoch_ms,
+            session_id,
+            delegate,
+            inspection_time: submission.inspection_time,
+            group_id: submission.group.group_id,
+            sign_key: 0,
+            hmac: None,
+        };
+
+        let request = if delegate {
+            Request::Delegate
+        } else {
+            Request::Solve
+        };
+        self.request(request, packet);
+    }
+
+    fn on_solve_confirm(&mut self, confirm: SolveConfirmPacket) {
+        log::info!("Solve confirmed: {confirm:?}");
+        self.handle_flow(Event::SolveSent);
+
+        let words: Vec<&str> = confirm.message.split(' ').collect();
+        let (line1, line2) = if words.len() >= 2 {
+            (words[..2].join(" "), words[2..].join(" "))
+        } else {
+            ("Solve".to_string(), "sent".to_string())
+        };
+
+        self.set_custom_message(line1, line2, Some(SOLVE_MESSAGE_MS));
+    }
+
+    fn on_delegate_response(&mut self, resp: DelegateResponsePacket) {
+        log::info!("Delegate resp: {resp:?}");
+        self.delegate_hold = None;
+        self.handle_flow(Event::Delegate(DelegateDecision {
+            solve_time: resp.solve_time,
+            penalty: resp.penalty,
+            should_scan_cards: resp.should_scan_cards,
+        }));
+    }
+
+    // --- stackmat ---
+
+    pub fn stackmat_start(&mut self) {
+        if self.stackmat_start.is_some() {
+            return;
+        }
+
+        self.stackmat_start = Some(self.now());
+        self.handle_flow(Event::Stackmat(Stackmat::Started));
+    }
+
+    /// Stops running stackmat, `time` overrides measured time
+    pub fn stackmat_stop(&mut self, time: Option<u64>) {
+        let Some(time) = time.or(self.stackmat_time(self.now())) else {
+            log::warn!("Stackmat isn't running");
+            return;
+        };
+
+        self.time_end(time, false);
+    }
+
+    pub fn stackmat_reset(&mut self) {
+        self.stackmat_start = None;
+        self.handle_flow(Event::Stackmat(Stackmat::Reset));
+    }
+
+    fn time_end(&mut self, time: u64, limit_reached: bool) {
+        self.stackmat_start = None;
+        let (time, limit_reached) = match self.group_limit {
+            Some(limit) if time > limit => (limit, true),
+            _ => (time, limit_reached),
+        };
+
+        log::info!(
+            "Timer stopped: {time}ms (inspection: {}ms)",
+            self.flow.inspection_time()
+        );
+
+        self.handle_flow(Event::Stackmat(Stackmat::Stopped {
+            time,
+            limit_reached,
+        }));
+    }
+
+    // --- buttons ---
+
+    /// Simulates press of given length. Handlers run in the same order as registered in
+    /// firmware `buttons_task` (down, holds, up), down handler returning true skips the rest.
+    pub fn press(&mut self, button: Button, press_ms: u64) {
+        log::info!("Button {button:?} pressed for {press_ms}ms");
+        match button {
+            Button::First => {
+                if self.sel_left() || self.inspection_start() {
+                    return;
+                }
+
+                if press_ms > 1000 {
+                    self.inspection_hold_stop();
+                }
+            }
+            Button::Second => {
+                if press_ms > 3000 {
+                    self.call_delegate();
+                }
+
+                if !self
+                    .requests
+                    .values()
+                    .any(|r| matches!(r, Request::Delegate))
+                {
+                    self.delegate_hold = None;
+                }
+            }
+            Button::Third => {
+                if press_ms > 3000 {
+                    self.submit_reset_competitor();
+                }
+
+                if press_ms > 10000 {
+                    log::warn!("Config menu isn't simulated");
+                }
+
+                self.submit_up();
+            }
+            Button::Fourth => {
+                if self.sel_right() {
+                    return;
+                }
+
+                if press_ms > 1000 {
+                    self.flow_button(FlowButton::Dnf);
+                }
+
+                if press_ms > 3000 && self.flow_button(FlowButton::DnsRequest).changed {
+                    return;
+                }
+
+                if press_ms < 1000 {
+                    self.flow_button(FlowButton::Penalty);
+                }
+            }
+        }
+    }
+
+    fn flow_button(&mut self, button: FlowButton) -> Transition<PossibleGroup> {
+        if self.should_skip_other_actions() {
+            return Transition {
+                changed: false,
+                commands: Vec::new(),
+            };
+        }
+
+        self.handle_flow(Event::Button(button))
+    }
+
+    fn sel_left(&mut self) -> bool {
+        if self.flow.scene != Scene::GroupSelect {
+            return false;
+        }
+
+        self.handle_flow(Event::Button(FlowButton::SelectPrev));
+        true
+    }
+
+    fn sel_right(&mut self) -> bool {
+        if self.flow.scene != Scene::GroupSelect {
+            return false;
+        }
+
+        self.handle_flow(Event::Button(FlowButton::SelectNext));
+        true
+    }
+
+    fn inspection_start(&mut self) -> bool {
+        if !self.flow.use_inspection() {
+            return false;
+        }
+
+        if self.stackmat_start.is_some() {
+            log::warn!("Skipping inspection start because current timer time is not 0");
+            return false;
+        }
+
+        self.flow_button(FlowButton::InspectionStart).changed
+    }
+
+    fn inspection_hold_stop(&mut self) {
+        self.flow_button(FlowButton::InspectionCancel);
+    }
+
+    fn submit_up(&mut self) {
+        if self.error_text.is_some() {
+            self.error_text = None;
+            return;
+        }
+
+        let transition = self.flow_button(FlowButton::Submit);
+        if transition.commands.contains(&Command::StopTimer) {
+            self.stackmat_stop(None);
+        }
+    }
+
+    fn submit_reset_competitor(&mut self) {
+        let transition = self.flow_button(FlowButton::ResetCompetitor);
+        if transition.commands.contains(&Command::StopTimer) && self.stackmat_start.is_some() {
+            self.stackmat_stop(None);
+        }
+    }
+
+    fn call_delegate(&mut self) {
+        let transition = self.flow_button(FlowButton::CallDelegate);
+        let Some(submission) = transition.commands.into_iter().find_map(|c| match c {
+            Command::CallDelegate(submission) => Some(submission),
+            _ => None,
+        }) else {
+            log::error!("Delegate hold: competitor or solve_group none!");
+            return;
+        };
+
+        self.delegate_hold = Some(3);
+        self.send_solve(submission, true);
+    }
+}
@@ -0,0 +1,148 @@
+//! Terminal output. In interactive mode log lines scroll above LCD frame which is redrawn
+//! in place, otherwise every changed frame is printed as plain text.
+
+use crate::lcd::{LCD_WIDTH, Lcd};
+use crossterm::{
+    cursor, queue,
+    terminal::{self, Clear, ClearType},
+};
+use std::{
+    io::{Stdout, Write},
+    sync::Mutex,
+};
+
+/// LCD frame (4 lines) + prompt line
+const FOOTER_HEIGHT: u16 = 5;
+
+struct Terminal {
+    interactive: bool,
+    lcd: Option<Lcd>,
+    prompt: Option<String>,
+    footer_drawn: bool,
+}
+
+static TERMINAL: Mutex<Terminal> = Mutex::new(Terminal {
+    interactive: false,
+    lcd: None,
+    prompt: None,
+    footer_drawn: false,
+});
+
+struct Logger;
+static LOGGER: Logger = Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            print_line(&format!("{:<5} {}", record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init(interactive: bool, level: log::LevelFilter) {
+    TERMINAL.lock().unwrap().interactive = interactive;
+    _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+
+    if interactive {
+        _ = terminal::enable_raw_mode();
+    }
+}
+
+pub fn shutdown() {
+    let mut term = TERMINAL.lock().unwrap();
+    if term.interactive {
+        _ = terminal::disable_raw_mode();
+        term.interactive = false;
+        println!();
+    }
+}
+
+/// Prints line above LCD frame
+pub fn print_line(line: &str) {
+    let mut term = TERMINAL.lock().unwrap();
+    let mut out = std::io::stdout();
+    if !term.interactive {
+        println!("{line}");
+        return;
+    }
+
+    term.clear_footer(&mut out);
+    _ = write!(out, "{line}\r\n");
+    term.draw_footer(&mut out);
+}
+
+pub fn set_lcd(lcd: Lcd) {
+    let mut term = TERMINAL.lock().unwrap();
+    if term.lcd.as_ref() == Some(&lcd) {
+        return;
+    }
+
+    let mut out = std::io::stdout();
+    if term.interactive {
+        term.clear_footer(&mut out);
+        term.lcd = Some(lcd);
+        term.draw_footer(&mut out);
+    } else {
+        println!("[lcd] |{}|", String::from_utf8_lossy(lcd.line(0)));
+        println!("[lcd] |{}|", String::from_utf8_lossy(lcd.line(1)));
+        term.lcd = Some(lcd);
+    }
+}
+
+/// Command line being typed (`None` hides it)
+pub fn set_prompt(prompt: Option<String>) {
+    let mut term = TERMINAL.lock().unwrap();
+    if !term.interactive {
+        return;
+    }
+
+    let mut out = std::io::stdout();
+    term.clear_footer(&mut out);
+    term.prompt = prompt;
+    term.draw_footer(&mut out);
+}
+
+impl Terminal {
+    fn clear_footer(&mut self, out: &mut Stdout) {
+        if self.footer_drawn {
+            _ = queue!(
+                out,
+                cursor::MoveToColumn(0),
+                cursor::MoveUp(FOOTER_HEIGHT - 1),
+                Clear(ClearType::FromCursorDown)
+            );
+        }
+
+        self.footer_drawn = false;
+    }
+
+    fn draw_footer(&mut self, out: &mut Stdout) {
+        let border = format!("+{}+", "-".repeat(LCD_WIDTH));
+        let (line0, line1) = match &self.lcd {
+            Some(lcd) => (
+                String::from_utf8_lossy(lcd.line(0)).into_owned(),
+                String::from_utf8_lossy(lcd.line(1)).into_owned(),
+            ),
+            None => (" ".repeat(LCD_WIDTH), " ".repeat(LCD_WIDTH)),
+        };
+
+        let prompt = match &self.prompt {
+            Some(prompt) => format!(":{prompt}"),
+            None => "(h: help)".to_string(),
+        };
+
+        _ = write!(
+            out,
+            "{border}\r\n|{line0}|\r\n|{line1}|\r\n{border}\r\n{prompt}"
+        );
+        _ = out.flush();
+        self.footer_drawn = true;
+    }
+}
@@ -0,0 +1,118 @@
+//! Plain `ws://` connection to server (TLS isn't supported). Runs on its own thread and
+//! reconnects like firmware `ws_task`.
+
+use crate::{
+    SimEvent,
+    packets::TimerPacket,
+    station::{FIRMWARE, HW_VER},
+};
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    sync::mpsc::{Receiver, Sender, TryRecvError},
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket, stream::MaybeTlsStream};
+
+const WS_RETRY_MS: u64 = 1000;
+const WS_POLL_MS: u64 = 20;
+
+pub enum WsEvent {
+    Connected,
+    Disconnected,
+    Packet(Box<TimerPacket>),
+}
+
+pub fn spawn(
+    url: String,
+    device_id: u32,
+    events: Sender<SimEvent>,
+    outgoing: Receiver<TimerPacket>,
+) {
+    std::thread::spawn(move || {
+        loop {
+            // packets queued while offline are stale
+            while outgoing.try_recv().is_ok() {}
+
+            match connect(&url, device_id) {
+                Ok(socket) => {
+                    log::info!("Connected to {url}");
+                    _ = events.send(SimEvent::Ws(WsEvent::Connected));
+
+                    match ws_rw(socket, &events, &outgoing) {
+                        Ok(()) => return,
+                        Err(e) => log::error!("ws_rw_error: {e}"),
+                    }
+
+                    _ = events.send(SimEvent::Ws(WsEvent::Disconnected));
+                }
+                Err(e) => log::error!("Connect error: {e}"),
+            }
+
+            std::thread::sleep(Duration::from_millis(WS_RETRY_MS));
+        }
+    });
+}
+
+fn connect(url: &str, device_id: u32) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, String> {
+    let random = uuid::Uuid::new_v4().as_u64_pair().0;
+    let has_path = url
+        .strip_prefix("ws://")
+        .is_some_and(|host_path| host_path.contains('/'));
+    let separator = match (url.contains('?'), has_path) {
+        (true, _) => "&",
+        (false, true) => "?",
+        (false, false) => "/?",
+    };
+    let url = format!(
+        "{url}{separator}id={device_id}&ver={}&hw={HW_VER}&firmware={FIRMWARE}&random={random}&auth=legacy",
+        env!("CARGO_PKG_VERSION"),
+    );
+
+    let (socket, _) = tungstenite::connect(url).map_err(|e| e.to_string())?;
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        stream
+            .set_read_timeout(Some(Duration::from_millis(WS_POLL_MS)))
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(socket)
+}
+
+/// Returns Ok when simulator is closing (outgoing channel dropped)
+fn ws_rw(
+    mut socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    events: &Sender<SimEvent>,
+    outgoing: &Receiver<TimerPacket>,
+) -> Result<(), String> {
+    loop {
+        loop {
+            let packet = match outgoing.try_recv() {
+                Ok(packet) => packet,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            };
+
+            let text = serde_json::to_string(&packet).map_err(|e| e.to_string())?;
+            log::debug!("[ws] send: {text}");
+            socket
+                .send(Message::text(text))
+                .map_err(|e| e.to_string())?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<TimerPacket>(&text) {
+                Ok(packet) => {
+                    log::debug!("[ws] recv: {text}");
+                    _ = events.send(SimEvent::Ws(WsEvent::Packet(Box::new(packet))));
+                }
+                Err(e) => log::debug!("[ws] Unsupported packet ({e}): {text}"),
+            },
+            Ok(Message::Close(frame)) => return Err(format!("closed by server: {frame:?}")),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
@@ -77,6 +77,7 @@ pub async fn battery_read_task(
         battery_start = Instant::now();
         let bat_calc_mv = calculate(read as f64);
         let bat_percentage = bat_percentage(bat_calc_mv);
+        unsafe { crate::state::BATTERY_STATUS = Some((bat_percentage, bat_calc_mv, None)) };
 
         if state.state.lock().await.server_connected == Some(true) {
             crate::ws::send_packet(crate::structs::TimerPacket {
@@ -89,6 +89,7 @@ pub async fn battery_read_task(i2c: SharedI2C, state: crate::state::GlobalState)
             }
         };
         let charging = ma >= 0;
+        unsafe { crate::state::BATTERY_STATUS = Some((soc, mv, Some(ma))) };
 
         if last_soc != soc || last_charging != charging {
             {
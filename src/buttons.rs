@@ -1,16 +1,18 @@
 use crate::{
-    consts::{NVS_BONDING_KEY, NVS_ERROR_LOG, NVS_SIGN_KEY},
+    consts::{NVS_BONDING_KEY, NVS_SECRET_KEY, NVS_SIGN_KEY},
     stackmat::CURRENT_TIME,
     state::{
-        BleAction, ErrorLogEntryStage, GlobalState, MenuScene, Scene, current_epoch,
+        BleAction, ErrorLogEntryStage, GlobalState, MenuScene, Scene, SignaledGlobalStateInner,
         deeper_sleep_state, sleep_state,
     },
     structs::DelegateResponsePacket,
+    time_sync::current_epoch_ms,
     utils::buttons::{Button, ButtonTrigger, ButtonsHandler},
 };
 use alloc::string::ToString;
-use embassy_time::{Instant, Timer};
+use embassy_time::Timer;
 use esp_hal::gpio::Input;
+use fkm_flow::{Button as FlowButton, Command, DelegateDecision, Event};
 
 macros::generate_button_handler_enum!(triggered: &ButtonTrigger, hold_time: u64, state: &GlobalState);
 
@@ -18,16 +20,20 @@ static ERROR_LOG_PARSE_FAILED_LOGGED: core::sync::atomic::AtomicBool =
     core::sync::atomic::AtomicBool::new(false);
 
 #[cfg(feature = "v3")]
-const CONFIG_MENU_ERROR_LOG_IDX: usize = 4;
+const CONFIG_MENU_ATTENDANCE_IDX: usize = 4;
 #[cfg(feature = "v3")]
-const CONFIG_MENU_EXIT_IDX: usize = 5;
+const CONFIG_MENU_ERROR_LOG_IDX: usize = 5;
+#[cfg(feature = "v3")]
+const CONFIG_MENU_EXIT_IDX: usize = 6;
 
 #[cfg(feature = "v4")]
 const CONFIG_MENU_BUZZER_IDX: usize = 4;
 #[cfg(feature = "v4")]
-const CONFIG_MENU_ERROR_LOG_IDX: usize = 5;
+const CONFIG_MENU_ATTENDANCE_IDX: usize = 5;
 #[cfg(feature = "v4")]
-const CONFIG_MENU_EXIT_IDX: usize = 6;
+const CONFIG_MENU_ERROR_LOG_IDX: usize = 6;
+#[cfg(feature = "v4")]
+const CONFIG_MENU_EXIT_IDX: usize = 7;
 
 #[embassy_executor::task]
 pub async fn buttons_task(
@@ -59,6 +65,7 @@ pub async fn buttons_task(
 
     handler.add_handler(Button::Fourth, ButtonTrigger::Down, sel_right());
     handler.add_handler(Button::Fourth, ButtonTrigger::HoldOnce(1000), dnf_button());
+    handler.add_handler(Button::Fourth, ButtonTrigger::HoldOnce(3000), dns_button());
     handler.add_handler(Button::Fourth, ButtonTrigger::Up, penalty_button());
 
     handler.add_handler(
@@ -164,11 +171,10 @@ async fn sel_left(
         return Ok(true);
     }
 
-    if state_val.scene == Scene::GroupSelect {
-        state_val.group_selected_idx = state_val
-            .group_selected_idx
-            .wrapping_sub(1)
-            .min(state_val.possible_groups.len() - 1);
+    if state_val.flow.scene == Scene::GroupSelect {
+        state_val
+            .handle_flow_event(Event::Button(FlowButton::SelectPrev), &state.nvs)
+            .await;
 
         return Ok(true);
     }
@@ -243,11 +249,10 @@ async fn sel_right(
         return Ok(true);
     }
 
-    if state_val.scene == Scene::GroupSelect {
-        state_val.group_selected_idx += 1;
-        if state_val.group_selected_idx == state_val.possible_groups.len() {
-            state_val.group_selected_idx = 0;
-        }
+    if state_val.flow.scene == Scene::GroupSelect {
+        state_val
+            .handle_flow_event(Event::Button(FlowButton::SelectNext), &state.nvs)
+            .await;
 
         return Ok(true);
     }
@@ -388,11 +393,7 @@ async fn submit_up(
             match sel {
                 0 => {
                     // Reset settings
-
-                    _ = state.nvs.delete(esp_hal_wifimanager::WIFI_NVS_KEY).await;
-                    _ = state.nvs.delete(NVS_SIGN_KEY).await;
-                    _ = state.nvs.delete(NVS_BONDING_KEY).await;
-                    _ = state.nvs.delete(NVS_ERROR_LOG).await;
+                    crate::utils::factory_reset(&state.nvs).await;
 
                     Timer::after_millis(250).await;
                     esp_hal::system::software_reset();
@@ -419,6 +420,9 @@ async fn submit_up(
 
                     state_val.menu_scene = Some(MenuScene::Unsigning);
                 }
+                CONFIG_MENU_ATTENDANCE_IDX => {
+                    toggle_attendance_mode(state, &mut state_val).await;
+                }
                 CONFIG_MENU_ERROR_LOG_IDX => {
                     state_val.error_log_entries =
                         match crate::utils::error_log::parse_error_log_entries() {
@@ -454,11 +458,7 @@ async fn submit_up(
             match sel {
                 0 => {
                     // Reset settings
-
-                    _ = state.nvs.delete(esp_hal_wifimanager::WIFI_NVS_KEY).await;
-                    _ = state.nvs.delete(NVS_SIGN_KEY).await;
-                    _ = state.nvs.delete(NVS_BONDING_KEY).await;
-                    _ = state.nvs.delete(NVS_ERROR_LOG).await;
+                    crate::utils::factory_reset(&state.nvs).await;
 
                     Timer::after_millis(250).await;
                     esp_hal::system::software_reset();
@@ -488,6 +488,9 @@ async fn submit_up(
                 4 => {
                     state_val.menu_scene = Some(MenuScene::BuzzerVolume);
                 }
+                CONFIG_MENU_ATTENDANCE_IDX => {
+                    toggle_attendance_mode(state, &mut state_val).await;
+                }
                 CONFIG_MENU_ERROR_LOG_IDX => {
                     state_val.error_log_entries =
                         match crate::utils::error_log::parse_error_log_entries() {
@@ -535,11 +538,32 @@ async fn submit_up(
         unsafe { crate::state::SIGN_KEY = sign_key };
         unsafe { crate::state::TRUST_SERVER = true };
 
+        // new secret can only be sent over pinned connection, otherwise secret provisioned
+        // in setup panel is kept (and proven by packet hmac)
+        let secret_key = if unsafe { crate::state::PINNED_CONNECTION } {
+            let secret_key = crate::utils::signing::generate_secret_key();
+            _ = state.nvs.delete(NVS_SECRET_KEY).await;
+            _ = state.nvs.set(NVS_SECRET_KEY, &secret_key[..]).await;
+            unsafe { crate::state::SECRET_KEY = Some(secret_key) };
+
+            Some(crate::utils::signing::to_hex(&secret_key))
+        } else {
+            if crate::utils::signing::secret_key().is_none() {
+                log::error!(
+                    "Device secret not sent over unpinned connection! Set TLS pin or secret in setup panel."
+                );
+            }
+
+            None
+        };
+
         crate::ws::send_packet(crate::structs::TimerPacket {
             tag: None,
             data: crate::structs::TimerPacketInner::Add {
                 firmware: alloc::string::ToString::to_string(crate::version::FIRMWARE),
                 sign_key: unsafe { crate::state::SIGN_KEY },
+                secret_key,
+                hmac: None,
             },
         })
         .await;
@@ -551,38 +575,50 @@ async fn submit_up(
         return Ok(false);
     }
 
-    if state_val.scene == Scene::GroupSelect {
-        state_val.solve_group =
-            Some(state_val.possible_groups[state_val.group_selected_idx].clone());
-        if state_val.solve_time.is_some() {
-            state_val.scene = crate::state::Scene::Finished;
-        } else {
-            state_val.scene = crate::state::Scene::CompetitorInfo;
-        }
-
-        unsafe {
-            crate::state::GROUP_LIMIT =
-                state_val.possible_groups[state_val.group_selected_idx].limit;
-        }
-
-        state.state.signal();
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::Submit), &state.nvs)
+        .await;
 
-        return Ok(false);
-    } else if state_val.scene == Scene::Timer {
-        #[cfg(not(feature = "timer-func"))]
+    if transition.commands.contains(&Command::StopTimer) {
         state.timer_stop_signal.signal(());
     }
 
-    if state_val.scene == Scene::Finished && !state_val.time_confirmed {
-        state_val.time_confirmed = true;
+    if transition.changed {
         state.state.signal();
-
-        return Ok(false);
     }
 
     Ok(false)
 }
 
+/// Toggles attendance mode from config menu (not allowed while solve is in progress)
+async fn toggle_attendance_mode(state: &GlobalState, state_val: &mut SignaledGlobalStateInner) {
+    if state_val.flow.solve_time.is_some() || state_val.flow.current_competitor.is_some() {
+        state_val.error_text = Some("Finish solve first".to_string());
+        return;
+    }
+
+    let effective = crate::settings::apply_settings(
+        &state.nvs,
+        crate::settings::SETTINGS_VERSION,
+        crate::settings::SettingsUpdate {
+            attendance_mode: Some(!crate::settings::attendance_mode()),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    // let server know about locally changed settings
+    if !crate::state::server_supports(crate::consts::PROTOCOL_SETTINGS) {
+        return;
+    }
+
+    crate::ws::send_packet(crate::structs::TimerPacket {
+        tag: None,
+        data: crate::structs::TimerPacketInner::EffectiveSettings(effective),
+    })
+    .await;
+}
+
 #[macros::button_handler]
 async fn inspection_start(
     _triggered: &ButtonTrigger,
@@ -590,7 +626,10 @@ async fn inspection_start(
     state: &GlobalState,
 ) -> Result<bool, ()> {
     let mut state_val = state.state.value().await;
-    if !state_val.use_inspection() || state_val.should_skip_other_actions() {
+    if !state_val.flow.use_inspection()
+        || state_val.should_skip_other_actions()
+        || crate::settings::attendance_mode()
+    {
         return Ok(false);
     }
 
@@ -599,18 +638,15 @@ async fn inspection_start(
         return Ok(false);
     }
 
-    if state_val.scene < Scene::Inspection
-        && state_val.inspection_start.is_none()
-        && state_val.solve_time.is_none()
-    {
-        state_val.inspection_start = Some(Instant::now());
-        state_val.scene = Scene::Inspection;
-        state.state.signal();
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::InspectionStart), &state.nvs)
+        .await;
 
-        return Ok(true);
+    if transition.changed {
+        state.state.signal();
     }
 
-    Ok(false)
+    Ok(transition.changed)
 }
 
 #[macros::button_handler]
@@ -624,21 +660,15 @@ async fn inspection_hold_stop(
         return Ok(false);
     }
 
-    if state_val.scene == Scene::Inspection {
-        let scene = if state_val.current_competitor.is_none() {
-            Scene::WaitingForCompetitor
-        } else {
-            Scene::CompetitorInfo
-        };
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::InspectionCancel), &state.nvs)
+        .await;
 
-        state_val.scene = scene;
-        state_val.inspection_start = None;
-        state_val.inspection_end = None;
+    if transition.changed {
         state.state.signal();
-        return Ok(true);
     }
 
-    Ok(false)
+    Ok(transition.changed)
 }
 
 #[macros::button_handler]
@@ -652,33 +682,20 @@ async fn dnf_button(
         return Ok(false);
     }
 
-    if state_val.scene == Scene::Inspection {
-        state_val.inspection_end = Some(Instant::now());
-        state_val.solve_time = Some(0);
-        state_val.penalty = Some(-1);
-        state_val.time_confirmed = true;
-
-        if state_val.current_competitor.is_some() {
-            state_val.scene = Scene::Finished;
-        } else {
-            state_val.scene = Scene::WaitingForCompetitor;
-        }
-
-        state.state.signal();
-        return Ok(true);
-    } else if state_val.scene == Scene::Finished && !state_val.time_confirmed {
-        let old_penalty = state_val.penalty.unwrap_or(0);
-        state_val.penalty = Some(if old_penalty == -1 { 0 } else { -1 });
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::Dnf), &state.nvs)
+        .await;
 
+    if transition.changed {
         state.state.signal();
-        return Ok(true);
     }
 
+    // keep holding for DNS (release after hold is skipped in penalty_button)
     Ok(false)
 }
 
 #[macros::button_handler]
-async fn penalty_button(
+async fn dns_button(
     _triggered: &ButtonTrigger,
     _hold_time: u64,
     state: &GlobalState,
@@ -688,18 +705,40 @@ async fn penalty_button(
         return Ok(false);
     }
 
-    if state_val.scene == Scene::Finished && !state_val.time_confirmed {
-        let old_penalty = state_val.penalty.unwrap_or(0);
-        state_val.penalty = Some(if old_penalty >= 16 || old_penalty == -1 {
-            0
-        } else {
-            old_penalty + 2
-        });
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::DnsRequest), &state.nvs)
+        .await;
 
+    if transition.changed {
         state.state.signal();
+    }
+
+    Ok(transition.changed)
+}
+
+#[macros::button_handler]
+async fn penalty_button(
+    _triggered: &ButtonTrigger,
+    hold_time: u64,
+    state: &GlobalState,
+) -> Result<bool, ()> {
+    if hold_time >= 1000 {
+        return Ok(false);
+    }
+
+    let mut state_val = state.state.value().await;
+    if state_val.should_skip_other_actions() {
         return Ok(false);
     }
 
+    let transition = state_val
+        .handle_flow_event(Event::Button(FlowButton::Penalty), &state.nvs)
+        .await;
+
+    if transition.changed {
+        state.state.signal();
+    }
+
     Ok(false)
 }
 
@@ -714,8 +753,17 @@ async fn submit_reset_competitor(
         return Ok(false);
     }
 
-    global_state.timer_stop_signal.signal(());
-    state.reset_solve_state(None).await;
+    let transition = state
+        .handle_flow_event(
+            Event::Button(FlowButton::ResetCompetitor),
+            &global_state.nvs,
+        )
+        .await;
+
+    if transition.commands.contains(&Command::StopTimer) {
+        global_state.timer_stop_signal.signal(());
+    }
+
     Ok(false)
 }
 
@@ -745,45 +793,38 @@ async fn delegate_hold(
         }
         ButtonTrigger::HoldTimed(_, _) => {
             let mut state_val = state.state.value().await;
-            if state_val.should_skip_other_actions() || state_val.delegate_used {
+            if state_val.should_skip_other_actions() || !state_val.flow.can_call_delegate() {
                 return Ok(false);
             }
 
-            if state_val.current_competitor.is_some() && state_val.solve_group.is_some() {
-                let hold_secs = hold_time / 1000;
-                let hold_secs = if hold_secs > 3 { 3 } else { hold_secs as u8 };
+            let hold_secs = hold_time / 1000;
+            let hold_secs = if hold_secs > 3 { 3 } else { hold_secs as u8 };
 
-                state_val.delegate_hold = Some(hold_secs);
-                state.state.signal();
-            }
+            state_val.delegate_hold = Some(hold_secs);
+            state.state.signal();
         }
         ButtonTrigger::HoldOnce(_) => {
             let mut state_val = state.state.lock().await;
-            if state_val.should_skip_other_actions() || state_val.delegate_used {
+            if state_val.should_skip_other_actions() {
                 return Ok(false);
             }
 
-            let (Some(current_competitor), Some(solve_group)) = (
-                state_val.current_competitor,
-                state_val.solve_group.clone().map(|x| x.group_id),
-            ) else {
-                log::error!("Delegate hold: competitor or solve_group none!");
-                return Ok(false);
-            };
-
             #[cfg(not(feature = "e2e"))]
             if unsafe { !crate::state::TRUST_SERVER } {
                 log::error!("Skipping delegate hold. Server not trusted!");
                 return Ok(false);
             }
 
-            let inspection_time = if state_val.use_inspection()
-                && let Some(start) = state_val.inspection_start
-                && let Some(end) = state_val.inspection_end
-            {
-                (end - start).as_millis() as i64
-            } else {
-                0
+            let transition = state_val
+                .handle_flow_event(Event::Button(FlowButton::CallDelegate), &state.nvs)
+                .await;
+
+            let Some(submission) = transition.commands.into_iter().find_map(|c| match c {
+                Command::CallDelegate(submission) => Some(submission),
+                _ => None,
+            }) else {
+                log::error!("Delegate hold: competitor or solve_group none!");
+                return Ok(false);
             };
 
             let session_id = match &state_val.session_id {
@@ -795,43 +836,45 @@ async fn delegate_hold(
                 }
             };
 
+            let epoch_ms = current_epoch_ms();
             let packet = crate::structs::TimerPacketInner::Solve {
-                solve_time: state_val.solve_time.unwrap_or(0),
-                penalty: state_val.penalty.unwrap_or(0) as i64,
-                competitor_id: current_competitor,
-                judge_id: state_val.current_judge.unwrap_or(0),
-                timestamp: current_epoch(),
+                solve_time: submission.solve_time,
+                penalty: submission.penalty as i64,
+                competitor_id: submission.competitor_id,
+                judge_id: submission.judge_id,
+                timestamp: epoch_ms / 1000,
+                timestamp_ms: epoch_ms,
                 session_id,
                 delegate: true,
-                inspection_time,
-                group_id: solve_group,
+                inspection_time: submission.inspection_time,
+                group_id: submission.group.group_id,
                 sign_key: unsafe { crate::state::SIGN_KEY },
+                hmac: None,
             };
 
             state_val.delegate_hold = Some(3);
             drop(state_val);
 
-            let resp =
-                crate::ws::send_tagged_request::<DelegateResponsePacket>(69420, packet, false)
-                    .await;
+            let resp = crate::ws::send_tagged_request::<DelegateResponsePacket>(
+                crate::structs::DELEGATE_TAG,
+                packet,
+                crate::ws::RetryPolicy::delegate(),
+            )
+            .await;
             log::info!("Delegate resp: {resp:?}");
 
             if let Ok(resp) = resp {
                 let mut state_val = state.state.lock().await;
-                state_val.solve_time =
-                    Some(resp.solve_time.unwrap_or(state_val.solve_time.unwrap_or(0)));
-
-                state_val.penalty = Some(
-                    resp.penalty
-                        .unwrap_or(state_val.penalty.unwrap_or(0) as i64) as i8,
-                );
-                state_val.scene = Scene::Finished;
-
-                state_val.time_confirmed = true;
-                state_val.delegate_used = true;
-                if !resp.should_scan_cards {
-                    state_val.reset_solve_state(Some(&state.nvs)).await;
-                }
+                state_val
+                    .handle_flow_event(
+                        Event::Delegate(DelegateDecision {
+                            solve_time: resp.solve_time,
+                            penalty: resp.penalty,
+                            should_scan_cards: resp.should_scan_cards,
+                        }),
+                        &state.nvs,
+                    )
+                    .await;
             }
         }
         _ => {}
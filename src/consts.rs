@@ -1,25 +1,47 @@
-#[cfg(feature = "sleep")]
-pub const SLEEP_AFTER_MS: u64 = 60000 * 5;
-#[cfg(feature = "sleep")]
-pub const DEEPER_SLEEP_AFTER_MS: u64 = 60000 * 15;
-
-#[cfg(not(feature = "sleep"))]
-pub const SLEEP_AFTER_MS: u64 = 60000 * 9999;
-#[cfg(not(feature = "sleep"))]
-pub const DEEPER_SLEEP_AFTER_MS: u64 = 60000 * 99999;
-
 #[cfg(not(feature = "release_build"))]
 pub const PRINT_HEAP_INTERVAL_MS: u64 = 30000;
-pub const LOG_SEND_INTERVAL_MS: u64 = 1000;
+pub const LOG_TARGET_OVERRIDES_MAX: usize = 8;
 
 pub const BATTERY_SEND_INTERVAL_MS: u64 = 60000;
 
 #[allow(dead_code)]
 pub const SCROLL_TICKER_INVERVAL_MS: u64 = 500;
 pub const LCD_INSPECTION_FRAME_TIME: u64 = 1000 / 30;
+pub const INSPECTION_WARNING_POLL_MS: u64 = 20;
 
 pub const RFID_RETRY_INIT_MS: u64 = 1500;
 pub const WS_RETRY_MS: u64 = 1000;
+pub const OTA_RESUME_TIMEOUT_MS: u64 = 60000 * 5;
+pub const OTA_VALIDATE_AFTER_MS: u64 = 60000 * 3;
+pub const OTA_VALIDATE_TIMEOUT_MS: u64 = 60000 * 10;
+pub const OTA_INFLATE_CHUNK_SIZE: usize = 2048;
+/// Size of metadata trailer appended by `append_metadata.sh`
+pub const OTA_METADATA_LEN: usize = 32 + 16 + 16 + 8;
+pub const WS_REQUEST_ATTEMPTS: u8 = 3;
+pub const TIME_SYNC_INTERVAL_MS: u64 = 60000;
+/// Sync responses with higher round trip time are ignored
+pub const TIME_SYNC_MAX_RTT_MS: u64 = 2000;
+/// Offset differences above this are applied immediately (instead of slewing)
+pub const TIME_STEP_THRESHOLD_MS: i64 = 1000;
+/// Clock is slewed by at most 1ms per this many ms
+pub const TIME_SLEW_RATE_DIV: u64 = 20;
+pub const WS_REQUEST_TIMEOUT_MS: u64 = 1500;
+pub const WS_REQUEST_MAX_TIMEOUT_MS: u64 = 6000;
+pub const WS_ENDPOINT_MAX_FAILURES: u8 = 3;
+
+/// Connection lost sooner after upgrade counts as endpoint failure
+pub const WS_STABLE_CONNECTION_MS: u64 = 10000;
+pub const WS_MDNS_FALLBACK_TIMEOUT_MS: u64 = 10000;
+pub const WS_HEARTBEAT_INTERVAL_MS: u64 = 5000;
+pub const WS_HEARTBEAT_MAX_MISSED: u8 = 3;
+#[cfg(feature = "v4")]
+pub const WS_RTT_SLOW_MS: u64 = 500;
+
+/// Protocol versions are shared with simulator (`fkm_common::packets`)
+pub use fkm_common::packets::{
+    PROTOCOL_OTA_RESUME, PROTOCOL_SETTINGS, PROTOCOL_STRUCTURED_LOGS, PROTOCOL_TIME_SYNC,
+    PROTOCOL_VERSION,
+};
 
 /// v3 buttons are shift-register scanned with no hardware debounce / strong
 /// pull-down filtering; require this long of a stable sample before edges fire.
@@ -28,8 +50,10 @@ pub const BUTTON_DEBOUNCE_MS: u64 = 30;
 
 pub const MDNS_RESEND_INTERVAL: u64 = 500;
 
-pub const INSPECTION_TIME_DNF: u64 = 17000;
-pub const INSPECTION_TIME_PLUS2: u64 = 15000;
+pub const SOLVE_QUEUE_MAX_LEN: usize = 10;
+pub const SOLVE_QUEUE_REPLAY_DELAY_MS: u64 = 2000;
+
+pub const ATTENDANCE_RESULT_SHOW_MS: u64 = 2000;
 
 #[cfg(feature = "v4")]
 pub const NVS_BUZZER_VOLUME: &str = "BUZZER_VOLUME";
@@ -40,7 +64,15 @@ pub const BUZZER_VOLUME_MAX: u8 = 25;
 #[cfg(feature = "v4")]
 pub const BUZZER_VOLUME_DEFAULT: u8 = 5;
 
+pub const REMOTE_COMMAND_ACTION_DELAY_MS: u64 = 1000;
+
 pub const NVS_BONDING_KEY: &str = "BONDING_KEY";
 pub const NVS_SIGN_KEY: &str = "SIGN_KEY";
+pub const NVS_SECRET_KEY: &str = "SECRET_KEY";
 pub const NVS_SAVED_STATE: &str = "SAVED_STATE";
 pub const NVS_ERROR_LOG: &str = "ERROR_LOG";
+pub const NVS_SOLVE_QUEUE: &str = "SOLVE_QUEUE";
+pub const NVS_TLS_PIN: &str = "TLS_PIN";
+pub const NVS_WS_URLS: &str = "WS_URLS";
+pub const NVS_SETTINGS: &str = "SETTINGS";
+pub const NVS_LOG_CONFIG: &str = "LOG_CONFIG";
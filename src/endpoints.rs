@@ -0,0 +1,130 @@
+use crate::{
+    consts::{NVS_WS_URLS, WS_ENDPOINT_MAX_FAILURES, WS_MDNS_FALLBACK_TIMEOUT_MS},
+    structs::ConnSettings,
+};
+use alloc::{string::String, vec::Vec};
+use embassy_net::Stack;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::{Duration, WithTimeout};
+use esp_hal_wifimanager::Nvs;
+use ws_framer::{WsUrl, WsUrlOwned};
+
+/// Urls configured in setup panel (primary first)
+static PANEL_URLS: Mutex<CriticalSectionRawMutex, Vec<String>> = Mutex::new(Vec::new());
+
+/// Backup urls pushed by server (persisted in nvs)
+static SERVER_URLS: Mutex<CriticalSectionRawMutex, Vec<String>> = Mutex::new(Vec::new());
+
+pub async fn load_endpoints(nvs: &Nvs, conn_settings: &ConnSettings) {
+    if !conn_settings.mdns {
+        let mut panel_urls = PANEL_URLS.lock().await;
+        panel_urls.extend(conn_settings.ws_url.iter().cloned());
+        panel_urls.extend(conn_settings.ws_urls.iter().flatten().cloned());
+        panel_urls.retain(|url| !url.trim().is_empty());
+    }
+
+    if let Ok(buf) = nvs.get::<Vec<u8>>(NVS_WS_URLS).await {
+        match serde_json::from_slice::<Vec<String>>(&buf) {
+            Ok(urls) => *SERVER_URLS.lock().await = urls,
+            Err(e) => {
+                log::error!("Server urls parse failed: {e:?}");
+                _ = nvs.delete(NVS_WS_URLS).await;
+            }
+        }
+    }
+
+    log::info!("Ws endpoints: {:?}", endpoints().await);
+}
+
+/// Replaces server pushed backup urls (empty list removes them)
+pub async fn set_server_urls(nvs: &Nvs, urls: Vec<String>) {
+    let urls: Vec<String> = urls
+        .into_iter()
+        .filter(|url| WsUrl::from_str(url).is_some())
+        .collect();
+
+    _ = nvs.delete(NVS_WS_URLS).await;
+    if !urls.is_empty()
+        && let Ok(vec) = serde_json::to_vec(&urls)
+        && let Err(e) = nvs.set(NVS_WS_URLS, vec.as_slice()).await
+    {
+        log::error!("{e:?} Faile to write to nvs! (WS_URLS {})", vec.len());
+    }
+
+    log::info!("Server urls set: {urls:?}");
+    *SERVER_URLS.lock().await = urls;
+}
+
+/// Ordered list of server urls (panel urls first, then server pushed ones)
+pub async fn endpoints() -> Vec<String> {
+    let mut urls = PANEL_URLS.lock().await.clone();
+    for url in SERVER_URLS.lock().await.iter() {
+        if !urls.contains(url) {
+            urls.push(url.clone());
+        }
+    }
+
+    urls
+}
+
+/// Tracks consecutive connection failures and rotates through endpoints.
+/// Slot after the last url is mDNS lookup (final fallback).
+pub struct EndpointRotation {
+    idx: usize,
+    failures: u8,
+}
+
+impl EndpointRotation {
+    pub async fn new(mdns_first: bool) -> Self {
+        Self {
+            idx: if mdns_first {
+                endpoints().await.len()
+            } else {
+                0
+            },
+            failures: 0,
+        }
+    }
+
+    pub fn on_success(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Returns next endpoint if current one failed too many times
+    pub async fn on_failure(&mut self, stack: Stack<'static>) -> Option<WsUrlOwned> {
+        self.failures += 1;
+        if self.failures < WS_ENDPOINT_MAX_FAILURES {
+            return None;
+        }
+
+        self.failures = 0;
+        let urls = endpoints().await;
+        for _ in 0..=urls.len() {
+            self.idx = (self.idx + 1) % (urls.len() + 1);
+
+            let url = if let Some(url) = urls.get(self.idx) {
+                url.clone()
+            } else {
+                log::info!("Ws endpoints failed, starting mdns lookup...");
+                let Ok(mdns_res) = crate::mdns::mdns_query(stack)
+                    .with_timeout(Duration::from_millis(WS_MDNS_FALLBACK_TIMEOUT_MS))
+                    .await
+                else {
+                    log::error!("Mdns lookup timeout!");
+                    continue;
+                };
+
+                mdns_res.as_str().into()
+            };
+
+            if let Some(ws_url) = WsUrl::from_str(&url) {
+                log::warn!("Switching ws endpoint to: {url}");
+                return Some(WsUrlOwned::new(&ws_url));
+            }
+
+            log::error!("Cannot parse ws endpoint: {url}");
+        }
+
+        None
+    }
+}
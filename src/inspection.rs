@@ -0,0 +1,72 @@
+use crate::{
+    consts::INSPECTION_WARNING_POLL_MS,
+    state::{GlobalState, Scene, sleep_state},
+};
+use embassy_time::{Instant, Timer};
+use fkm_flow::InspectionWarning;
+
+/// Last warning of current inspection and when it was triggered (LCD flashes it)
+static mut INSPECTION_WARNING: Option<(InspectionWarning, Instant)> = None;
+
+#[inline(always)]
+pub fn inspection_warning() -> Option<(InspectionWarning, Instant)> {
+    unsafe { INSPECTION_WARNING }
+}
+
+/// Detects crossed inspection thresholds (8s/12s calls, +2, DNF) on one clock,
+/// so LCD redraws can't skip or repeat them. Buzzer only beeps if sound is enabled.
+#[embassy_executor::task]
+pub async fn inspection_warnings_task(global_state: GlobalState) {
+    // (inspection start, elapsed ms at last poll)
+    let mut last: Option<(u64, u64)> = None;
+
+    loop {
+        Timer::after_millis(INSPECTION_WARNING_POLL_MS).await;
+        if sleep_state() {
+            continue;
+        }
+
+        let inspection_start = {
+            let state = global_state.state.value().await;
+            match state.flow.scene {
+                Scene::Inspection => state.flow.inspection_start,
+                _ => None,
+            }
+        };
+
+        let Some(start) = inspection_start else {
+            if last.take().is_some() {
+                unsafe { INSPECTION_WARNING = None };
+            }
+
+            continue;
+        };
+
+        let elapsed = Instant::now().as_millis().saturating_sub(start);
+        let prev = match last {
+            Some((last_start, prev)) if last_start == start => prev,
+            _ => {
+                unsafe { INSPECTION_WARNING = None };
+                elapsed
+            }
+        };
+        last = Some((start, elapsed));
+
+        if !crate::settings::settings().inspection_warnings {
+            continue;
+        }
+
+        let Some(warning) = InspectionWarning::crossed(prev, elapsed, &crate::state::flow_config())
+        else {
+            continue;
+        };
+
+        log::debug!("Inspection warning: {warning:?}");
+        unsafe { INSPECTION_WARNING = Some((warning, Instant::now())) };
+
+        #[cfg(feature = "v4")]
+        if global_state.state.value().await.sound_enabled {
+            global_state.buzzer_inspection_warning.signal(warning);
+        }
+    }
+}
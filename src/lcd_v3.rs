@@ -4,20 +4,16 @@ use embassy_sync::{blocking_mutex::raw::NoopRawMutex, signal::Signal};
 use embassy_time::{Delay, Duration, Instant, Timer};
 use embedded_hal::digital::OutputPin;
 use embedded_hal_async::delay::DelayNs;
+use fkm_common::time::{ms_to_time_str, previous_attempts_str};
 
 use crate::{
-    consts::{
-        DEEPER_SLEEP_AFTER_MS, INSPECTION_TIME_PLUS2, LCD_INSPECTION_FRAME_TIME,
-        SCROLL_TICKER_INVERVAL_MS, SLEEP_AFTER_MS,
-    },
+    consts::{LCD_INSPECTION_FRAME_TIME, SCROLL_TICKER_INVERVAL_MS},
+    settings::settings,
     state::{
         GlobalState, MenuScene, Scene, SignaledGlobalStateInner, deeper_sleep_state, sleep_state,
     },
     translations::{TranslationKey, get_translation, get_translation_params},
-    utils::{
-        lcd_abstract::{LcdAbstract, PrintAlign},
-        stackmat::ms_to_time_str,
-    },
+    utils::lcd_abstract::{LcdAbstract, PrintAlign, display_on_lcd},
 };
 
 pub async fn lcd_show_critical_code(
@@ -93,7 +89,7 @@ pub async fn lcd_task(
         true,
     );
     _ = lcd_driver.print(1, crate::version::VERSION, PrintAlign::Center, true);
-    lcd_driver.display_on_lcd(&mut lcd).await;
+    display_on_lcd(&mut lcd_driver, &mut lcd).await;
 
     _ = lcd_driver.print(
         0,
@@ -101,7 +97,7 @@ pub async fn lcd_task(
         PrintAlign::Right,
         false,
     );
-    lcd_driver.display_on_lcd(&mut lcd).await;
+    display_on_lcd(&mut lcd_driver, &mut lcd).await;
 
     #[cfg(not(feature = "bat_dev_lcd"))]
     Timer::after_millis(2500).await;
@@ -114,14 +110,15 @@ pub async fn lcd_task(
         last_update = Instant::now();
 
         if sleep_state() {
-            lcd.backlight_on();
-
             unsafe {
                 crate::state::SLEEP_STATE = false;
             }
         }
 
-        let current_scene = current_state.scene.clone();
+        // also restores backlight after inspection warning flash
+        lcd.backlight_on();
+
+        let current_scene = current_state.flow.scene.clone();
         let fut = async {
             let _ = process_lcd(
                 current_state,
@@ -131,7 +128,8 @@ pub async fn lcd_task(
                 &wifi_setup_sig,
             )
             .await;
-            lcd_driver.display_on_lcd(&mut lcd).await;
+            display_on_lcd(&mut lcd_driver, &mut lcd).await;
+            unsafe { crate::state::DISPLAY_FLUSHED = true };
 
             let mut scroll_ticker =
                 embassy_time::Ticker::every(Duration::from_millis(SCROLL_TICKER_INVERVAL_MS));
@@ -139,17 +137,17 @@ pub async fn lcd_task(
                 scroll_ticker.next().await;
                 let changed = lcd_driver.scroll_step();
                 if changed.is_ok_and(|c| c) {
-                    lcd_driver.display_on_lcd(&mut lcd).await;
+                    display_on_lcd(&mut lcd_driver, &mut lcd).await;
                 }
 
                 #[cfg(not(any(feature = "e2e", feature = "qa")))]
                 if !sleep_state()
-                    && (Instant::now() - last_update).as_millis() > SLEEP_AFTER_MS
+                    && (Instant::now() - last_update).as_millis() > settings().sleep_after_ms
                     && current_scene.can_sleep()
                 {
                     _ = lcd_driver.print(0, "Sleep", PrintAlign::Center, true);
                     _ = lcd_driver.print(1, "Press any key", PrintAlign::Center, true);
-                    lcd_driver.display_on_lcd(&mut lcd).await;
+                    display_on_lcd(&mut lcd_driver, &mut lcd).await;
                     lcd.backlight_off();
 
                     {
@@ -167,11 +165,11 @@ pub async fn lcd_task(
                 #[cfg(not(any(feature = "e2e", feature = "qa")))]
                 if sleep_state()
                     && !deeper_sleep_state()
-                    && (Instant::now() - last_update).as_millis() > DEEPER_SLEEP_AFTER_MS
+                    && (Instant::now() - last_update).as_millis() > settings().deeper_sleep_after_ms
                 {
                     _ = lcd_driver.print(0, "Deep Sleep", PrintAlign::Center, true);
                     _ = lcd_driver.print(1, "Press any key", PrintAlign::Center, true);
-                    lcd_driver.display_on_lcd(&mut lcd).await;
+                    display_on_lcd(&mut lcd_driver, &mut lcd).await;
                     crate::utils::deeper_sleep();
                 }
             }
@@ -294,7 +292,7 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                         true,
                     )
                     .ok()?;
-                lcd_driver.display_on_lcd(lcd).await;
+                display_on_lcd(lcd_driver, lcd).await;
 
                 Timer::after_millis(300).await;
                 lcd_driver
@@ -475,7 +473,29 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
         return Some(());
     }
 
-    match current_state.scene {
+    if current_state.flow.dns_pending {
+        lcd_driver
+            .print(
+                0,
+                &get_translation(TranslationKey::DNS_CONFIRM_HEADER),
+                PrintAlign::Center,
+                true,
+            )
+            .ok()?;
+
+        lcd_driver
+            .print(
+                1,
+                &get_translation(TranslationKey::DNS_CONFIRM_FOOTER),
+                PrintAlign::Center,
+                true,
+            )
+            .ok()?;
+
+        return Some(());
+    }
+
+    match current_state.flow.scene {
         Scene::WifiConnect => {
             lcd_driver
                 .print(
@@ -495,9 +515,9 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 )
                 .ok()?;
 
-            lcd_driver.display_on_lcd(lcd).await;
+            display_on_lcd(lcd_driver, lcd).await;
             wifi_setup_sig.wait().await;
-            global_state.state.lock().await.scene = Scene::AutoSetupWait;
+            global_state.state.lock().await.flow.scene = Scene::AutoSetupWait;
         }
         Scene::AutoSetupWait => {
             let wifi_ssid = alloc::format!("FKM-{:X}", crate::utils::get_efuse_u32());
@@ -552,12 +572,31 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
             lcd_driver
                 .print(
                     1,
-                    &current_state.possible_groups[current_state.group_selected_idx].name,
+                    &current_state.flow.possible_groups[current_state.flow.group_selected_idx].name,
                     PrintAlign::Center,
                     false,
                 )
                 .ok()?;
         }
+        Scene::WaitingForCompetitor if crate::settings::attendance_mode() => {
+            lcd_driver
+                .print(
+                    0,
+                    &get_translation(TranslationKey::ATTENDANCE_HEADER),
+                    PrintAlign::Center,
+                    true,
+                )
+                .ok()?;
+
+            lcd_driver
+                .print(
+                    1,
+                    &get_translation(TranslationKey::ATTENDANCE_FOOTER),
+                    PrintAlign::Center,
+                    true,
+                )
+                .ok()?;
+        }
         Scene::WaitingForCompetitor => {
             lcd_driver
                 .print(
@@ -568,7 +607,7 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 )
                 .ok()?;
 
-            if let Some(solve_time) = current_state.solve_time {
+            if let Some(solve_time) = current_state.flow.solve_time {
                 let time_str = ms_to_time_str(solve_time);
                 lcd_driver
                     .print(
@@ -597,6 +636,7 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 .print(
                     0,
                     &current_state
+                        .flow
                         .competitor_display
                         .unwrap_or("??????".to_string()),
                     PrintAlign::Center,
@@ -604,9 +644,26 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 )
                 .ok()?;
 
-            if let Some(group) = current_state.solve_group {
+            if let Some(group) = current_state.flow.solve_group {
+                // attempt + previous results (scrolled) replace group name when known
+                let footer = match (group.attempt_number, group.attempts_total) {
+                    (Some(number), Some(total)) => {
+                        let mut footer = get_translation_params(
+                            TranslationKey::ATTEMPT_NUMBER,
+                            &[number, total],
+                        );
+                        if !group.previous_attempts.is_empty() {
+                            footer += " ";
+                            footer += &previous_attempts_str(&group.previous_attempts);
+                        }
+
+                        footer
+                    }
+                    _ => group.name,
+                };
+
                 lcd_driver
-                    .print(1, &group.name, PrintAlign::Center, true)
+                    .print(1, &footer, PrintAlign::Center, true)
                     .ok()?;
             }
         }
@@ -615,22 +672,39 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 .state
                 .value()
                 .await
+                .flow
                 .inspection_start
+                .map(Instant::from_millis)
                 .unwrap_or(Instant::now());
 
             lcd_driver
                 .print(1, "Inspection", PrintAlign::Center, true)
                 .ok()?;
 
+            let mut flashing = false;
             loop {
                 let elapsed = (Instant::now() - inspection_start).as_millis();
+
+                // backlight blinks in warning pattern (restored at the top of lcd loop)
+                let flash = crate::inspection::inspection_warning()
+                    .is_some_and(|(w, at)| w.is_on(at.elapsed().as_millis()));
+                if flash != flashing {
+                    if flash {
+                        lcd.backlight_off();
+                    } else {
+                        lcd.backlight_on();
+                    }
+
+                    flashing = flash;
+                }
+
                 let time_str = ms_to_time_str(elapsed);
 
                 lcd_driver
                     .print(0, &time_str, PrintAlign::Center, true)
                     .ok()?;
 
-                lcd_driver.display_on_lcd(lcd).await;
+                display_on_lcd(lcd_driver, lcd).await;
                 Timer::after_millis(LCD_INSPECTION_FRAME_TIME).await;
             }
         }
@@ -641,26 +715,26 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 .print(0, &time_str, PrintAlign::Center, true)
                 .ok()?;
 
-            lcd_driver.display_on_lcd(lcd).await;
+            display_on_lcd(lcd_driver, lcd).await;
         },
         Scene::Finished => {
-            let solve_time = current_state.solve_time.unwrap_or(0);
+            let solve_time = current_state.flow.solve_time.unwrap_or(0);
             let time_str = if solve_time > 0 {
                 ms_to_time_str(solve_time)
             } else {
                 heapless::String::new()
             };
 
-            let inspection_time =
-                match (current_state.inspection_start, current_state.inspection_end) {
-                    (Some(start), Some(end)) => {
-                        Some(end.saturating_duration_since(start).as_millis())
-                    }
-                    _ => None,
-                };
+            let inspection_time = match (
+                current_state.flow.inspection_start,
+                current_state.flow.inspection_end,
+            ) {
+                (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+                _ => None,
+            };
 
-            if current_state.use_inspection()
-                && inspection_time.unwrap_or(0) > INSPECTION_TIME_PLUS2
+            if current_state.flow.use_inspection()
+                && inspection_time.unwrap_or(0) > settings().inspection_plus2_ms
             {
                 let inspections_seconds = inspection_time.unwrap_or(0) / 1000;
                 lcd_driver
@@ -677,7 +751,7 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                     .ok()?;
             }
 
-            let penalty = current_state.penalty.unwrap_or(0);
+            let penalty = current_state.flow.penalty.unwrap_or(0);
             let penalty_str = match penalty {
                 -2 => "DNS",
                 -1 => "DNF",
@@ -689,35 +763,26 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 .print(0, penalty_str, PrintAlign::Right, false)
                 .ok()?;
 
-            if !current_state.time_confirmed {
-                lcd_driver
-                    .print(
-                        1,
-                        &get_translation(TranslationKey::CONFIRM_TIME),
-                        PrintAlign::Right,
-                        true,
-                    )
-                    .ok()?;
-            } else if current_state.current_judge.is_none() {
-                lcd_driver
-                    .print(
-                        1,
-                        &get_translation(TranslationKey::SCAN_JUDGE_CARD),
-                        PrintAlign::Right,
-                        true,
-                    )
-                    .ok()?;
-            } else if current_state.current_competitor.is_some()
-                && current_state.current_judge.is_some()
-            {
-                lcd_driver
-                    .print(
-                        1,
-                        &get_translation(TranslationKey::SCAN_COMPETITOR_CARD),
-                        PrintAlign::Right,
-                        true,
-                    )
-                    .ok()?;
+            let status = if !current_state.flow.time_confirmed {
+                Some(get_translation(TranslationKey::CONFIRM_TIME))
+            } else if current_state.flow.current_judge.is_none() {
+                Some(get_translation(TranslationKey::SCAN_JUDGE_CARD))
+            } else if current_state.flow.current_competitor.is_some() {
+                Some(get_translation(TranslationKey::SCAN_COMPETITOR_CARD))
+            } else {
+                None
+            };
+
+            let status = match status {
+                Some(status) if current_state.flow.cutoff_missed() => Some(alloc::format!(
+                    "{} {status}",
+                    get_translation(TranslationKey::CUTOFF_NOT_MET)
+                )),
+                status => status,
+            };
+
+            if let Some(status) = status {
+                lcd_driver.print(1, &status, PrintAlign::Right, true).ok()?;
             }
         }
         Scene::Update => {
@@ -726,7 +791,7 @@ async fn process_lcd<T: OutputPin, D: DelayNs>(
                 let progress = global_state.update_progress.wait().await;
                 _ = lcd_driver.print(1, &alloc::format!("{progress}%"), PrintAlign::Center, true);
 
-                lcd_driver.display_on_lcd(lcd).await;
+                display_on_lcd(lcd_driver, lcd).await;
             }
         }
     }
@@ -739,7 +804,7 @@ async fn process_lcd_overwrite(
     _global_state: &GlobalState,
     lcd_driver: &mut LcdAbstract<80, 16, 2, 3>,
 ) -> bool {
-    if !current_state.scene.can_be_lcd_overwritten() {
+    if !current_state.flow.scene.can_be_lcd_overwritten() {
         return false;
     }
 
@@ -774,7 +839,8 @@ async fn process_lcd_overwrite(
 
         _ = lcd_driver.print(0, lines.0, PrintAlign::Center, true);
         _ = lcd_driver.print(1, lines.1, PrintAlign::Center, true);
-    } else if current_state.stackmat_connected == Some(false) {
+    } else if current_state.stackmat_connected == Some(false) && !crate::settings::attendance_mode()
+    {
         _ = lcd_driver.print(
             0,
             &get_translation(TranslationKey::STACKMAT_DISCONNECTED_HEADER),
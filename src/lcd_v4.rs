@@ -1,7 +1,6 @@
 use crate::{
-    consts::{
-        DEEPER_SLEEP_AFTER_MS, INSPECTION_TIME_PLUS2, LCD_INSPECTION_FRAME_TIME, SLEEP_AFTER_MS,
-    },
+    consts::LCD_INSPECTION_FRAME_TIME,
+    settings::settings,
     state::{
         ErrorLogEntryStage, GlobalState, MenuScene, Scene, SignaledGlobalStateInner,
         deeper_sleep_state, sleep_state,
@@ -10,7 +9,6 @@ use crate::{
     utils::{
         lcd_resourcese::{CrossedIcon, Resources},
         shared_i2c::SharedI2C,
-        stackmat::ms_to_time_str,
     },
 };
 use alloc::{
@@ -43,6 +41,7 @@ use embedded_text::{
     style::TextBoxStyleBuilder,
 };
 use esp_hal::gpio::Output;
+use fkm_common::time::{ms_to_time_str, previous_attempts_str};
 use oled_async::{displays::ssd1309::Ssd1309_128_64, mode::GraphicsMode};
 
 pub const FBUF_WIDTH: usize = 128;
@@ -83,6 +82,12 @@ pub const SMALL_FONT: MonoTextStyle<'_, BinaryColor> = MonoTextStyle::new(
 );
 pub const TIMER_FONT: MonoTextStyle<'_, BinaryColor> =
     MonoTextStyle::new(&profont::PROFONT_14_POINT, BinaryColor::On);
+pub const TIMER_FONT_INVERTED: MonoTextStyle<'_, BinaryColor> =
+    MonoTextStyle::new(&profont::PROFONT_14_POINT, BinaryColor::Off);
+pub const NORMAL_FONT_INVERTED: MonoTextStyle<'_, BinaryColor> = MonoTextStyle::new(
+    &embedded_graphics::mono_font::ascii::FONT_7X13,
+    BinaryColor::Off,
+);
 pub const SMALL_TIMER_FONT: MonoTextStyle<'_, BinaryColor> =
     MonoTextStyle::new(&profont::PROFONT_7_POINT, BinaryColor::On);
 
@@ -432,20 +437,26 @@ pub async fn lcd_task(
             log::warn!("Sleep wakeup!");
         }
 
-        let current_scene = current_state.scene.clone();
+        let current_scene = current_state.flow.scene.clone();
         let fut = async {
             oled.fbuf.clear(BinaryColor::Off);
             _ = process_top_bar(&current_state, &global_state, &mut oled).await;
             _ = process_main(&current_state, &global_state, &wifi_setup_sig, &mut oled).await;
-            if embassy_time::with_timeout(embassy_time::Duration::from_millis(1500), oled.flush())
-                .await
-                .is_err()
+            match embassy_time::with_timeout(
+                embassy_time::Duration::from_millis(1500),
+                oled.flush(),
+            )
+            .await
             {
-                log::error!("OLED FLUSH TIMEOUT");
-                crate::utils::error_log::add_error(
-                    crate::utils::error_log::codes::LCD_FLUSH_TIMEOUT,
-                )
-                .await;
+                Ok(Ok(_)) => unsafe { crate::state::DISPLAY_FLUSHED = true },
+                Ok(Err(_)) => {}
+                Err(_) => {
+                    log::error!("OLED FLUSH TIMEOUT");
+                    crate::utils::error_log::add_error(
+                        crate::utils::error_log::codes::LCD_FLUSH_TIMEOUT,
+                    )
+                    .await;
+                }
             }
 
             loop {
@@ -479,7 +490,7 @@ pub async fn lcd_task(
 
                 #[cfg(not(any(feature = "e2e", feature = "qa")))]
                 if !sleep_state()
-                    && (Instant::now() - last_update).as_millis() > SLEEP_AFTER_MS
+                    && (Instant::now() - last_update).as_millis() > settings().sleep_after_ms
                     && current_scene.can_sleep()
                 {
                     oled.fbuf.clear(BinaryColor::Off);
@@ -518,7 +529,7 @@ pub async fn lcd_task(
                 #[cfg(not(any(feature = "e2e", feature = "qa")))]
                 if sleep_state()
                     && !deeper_sleep_state()
-                    && (Instant::now() - last_update).as_millis() > DEEPER_SLEEP_AFTER_MS
+                    && (Instant::now() - last_update).as_millis() > settings().deeper_sleep_after_ms
                 {
                     use esp_hal::rtc_cntl::{Rtc, sleep::RtcioWakeupSource};
 
@@ -619,6 +630,10 @@ async fn process_top_bar(
 
     topbar_icons_layout(topbar_chain).draw(&mut oled.fbuf)?;
 
+    let slow_link_text = current_state
+        .slow_server_rtt()
+        .map(|rtt| format!("{rtt}ms"));
+
     let text = if current_state.selected_config_menu.is_some() {
         Some("CONFIG")
     } else if let Some(ref menu_scene) = current_state.menu_scene {
@@ -629,12 +644,14 @@ async fn process_top_bar(
             MenuScene::ErrorLog => Some("ERRLOG"),
             MenuScene::BuzzerVolume => Some("BUZZER"),
         }
-    } else if let Some(ref group) = current_state.solve_group
-        && current_state.scene == Scene::CompetitorInfo
+    } else if let Some(ref group) = current_state.flow.solve_group
+        && current_state.flow.scene == Scene::CompetitorInfo
     {
         Some(group.name.as_str())
+    } else if crate::settings::attendance_mode() && slow_link_text.is_none() {
+        Some("ATTEND")
     } else {
-        None
+        slow_link_text.as_deref()
     };
     if let Some(text) = text {
         Text::with_text_style(text, Point::new(64, 5), SMALL_FONT, TEXT_CENTER)
@@ -832,7 +849,18 @@ async fn process_main(
         return Ok(());
     }
 
-    match current_state.scene {
+    if current_state.flow.dns_pending {
+        center_text_layout(&format!(
+            "{}\n{}",
+            get_translation(TranslationKey::DNS_CONFIRM_HEADER),
+            get_translation(TranslationKey::DNS_CONFIRM_FOOTER)
+        ))
+        .draw(&mut oled.fbuf)?;
+
+        return Ok(());
+    }
+
+    match current_state.flow.scene {
         Scene::WifiConnect => {
             center_text_layout(&format!(
                 "{}\n{}",
@@ -843,7 +871,7 @@ async fn process_main(
             oled.flush().await?;
 
             wifi_setup_sig.wait().await;
-            global_state.state.lock().await.scene = Scene::AutoSetupWait;
+            global_state.state.lock().await.flow.scene = Scene::AutoSetupWait;
         }
         Scene::AutoSetupWait => {
             let wifi_ssid = alloc::format!("FKM-{:X}", crate::utils::get_efuse_u32());
@@ -888,12 +916,20 @@ async fn process_main(
             center_text_layout(&format!(
                 "{}\n{}",
                 get_translation(TranslationKey::SELECT_GROUP),
-                current_state.possible_groups[current_state.group_selected_idx].name
+                current_state.flow.possible_groups[current_state.flow.group_selected_idx].name
+            ))
+            .draw(&mut oled.fbuf)?;
+        }
+        Scene::WaitingForCompetitor if crate::settings::attendance_mode() => {
+            center_text_layout(&format!(
+                "{}\n{}",
+                get_translation(TranslationKey::ATTENDANCE_HEADER),
+                get_translation(TranslationKey::ATTENDANCE_FOOTER),
             ))
             .draw(&mut oled.fbuf)?;
         }
         Scene::WaitingForCompetitor => {
-            if let Some(solve_time) = current_state.solve_time {
+            if let Some(solve_time) = current_state.flow.solve_time {
                 let time_str = ms_to_time_str(solve_time);
                 center_text_layout(&format!(
                     "{}\n{}",
@@ -915,15 +951,27 @@ async fn process_main(
         }
         Scene::CompetitorInfo => {
             let mut text = current_state
+                .flow
                 .competitor_display
                 .clone()
                 .unwrap_or("------".to_string())
                 .to_string();
 
-            if let Some(ref group) = current_state.solve_group
-                && let Some(ref secondary_text) = group.secondary_text
-            {
-                text += &format!("\n{}", secondary_text);
+            if let Some(ref group) = current_state.flow.solve_group {
+                if let Some(ref secondary_text) = group.secondary_text {
+                    text += &format!("\n{}", secondary_text);
+                }
+
+                if let (Some(number), Some(total)) = (group.attempt_number, group.attempts_total) {
+                    text += &format!(
+                        "\n{}",
+                        get_translation_params(TranslationKey::ATTEMPT_NUMBER, &[number, total])
+                    );
+                }
+
+                if !group.previous_attempts.is_empty() {
+                    text += &format!("\n{}", previous_attempts_str(&group.previous_attempts));
+                }
             }
 
             center_text_layout(&text).draw(&mut oled.fbuf)?;
@@ -935,24 +983,51 @@ async fn process_main(
                 .state
                 .value()
                 .await
+                .flow
                 .inspection_start
+                .map(Instant::from_millis)
                 .unwrap_or(Instant::now());
 
+            let inspection_label = get_translation(TranslationKey::INSPECTION);
             _ = Text::with_text_style(
-                &get_translation(TranslationKey::INSPECTION),
+                &inspection_label,
                 Point::new(64, 50),
                 NORMAL_FONT,
                 TEXT_CENTER,
             )
             .draw(&mut oled.disp);
 
+            let mut inverted = false;
+
             let text_rect = Rectangle::new(Point::new(0, 28), Size::new(128, 17));
             loop {
                 let elapsed = (Instant::now() - inspection_start).as_millis();
-                let time_str = ms_to_time_str(elapsed);
 
-                _ = oled.disp.fill_solid(&text_rect, BinaryColor::Off);
-                _ = Text::with_text_style(&time_str, Point::new(64, 36), TIMER_FONT, TEXT_CENTER)
+                // main area is inverted in warning pattern
+                let invert = crate::inspection::inspection_warning()
+                    .is_some_and(|(w, at)| w.is_on(at.elapsed().as_millis()));
+                let (background, timer_font, label_font) = if invert {
+                    (BinaryColor::On, TIMER_FONT_INVERTED, NORMAL_FONT_INVERTED)
+                } else {
+                    (BinaryColor::Off, TIMER_FONT, NORMAL_FONT)
+                };
+
+                if invert != inverted {
+                    _ = oled.disp.fill_solid(&MAIN_RECT, background);
+                    _ = Text::with_text_style(
+                        &inspection_label,
+                        Point::new(64, 50),
+                        label_font,
+                        TEXT_CENTER,
+                    )
+                    .draw(&mut oled.disp);
+
+                    inverted = invert;
+                }
+
+                let time_str = ms_to_time_str(elapsed);
+                _ = oled.disp.fill_solid(&text_rect, background);
+                _ = Text::with_text_style(&time_str, Point::new(64, 36), timer_font, TEXT_CENTER)
                     .draw(&mut oled.disp);
                 _ = oled.disp.flush().await;
 
@@ -975,22 +1050,22 @@ async fn process_main(
             }
         }
         Scene::Finished => {
-            let solve_time = current_state.solve_time.unwrap_or(0);
+            let solve_time = current_state.flow.solve_time.unwrap_or(0);
             let time_str = ms_to_time_str(solve_time);
-            let inspection_time =
-                match (current_state.inspection_start, current_state.inspection_end) {
-                    (Some(start), Some(end)) => {
-                        Some(end.saturating_duration_since(start).as_millis())
-                    }
-                    _ => None,
-                };
+            let inspection_time = match (
+                current_state.flow.inspection_start,
+                current_state.flow.inspection_end,
+            ) {
+                (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+                _ => None,
+            };
 
-            let show_inspection = current_state.use_inspection()
-                && inspection_time.unwrap_or(0) > INSPECTION_TIME_PLUS2;
+            let show_inspection = current_state.flow.use_inspection()
+                && inspection_time.unwrap_or(0) > settings().inspection_plus2_ms;
             let inspection_display =
                 show_inspection.then(|| ms_to_time_str(inspection_time.unwrap_or(0)));
 
-            let penalty = current_state.penalty.unwrap_or(0);
+            let penalty = current_state.flow.penalty.unwrap_or(0);
             let main_time_display: String = match penalty {
                 -2 if solve_time == 0 => "DNS".into(),
                 -2 => format!("DNS ({time_str})"),
@@ -1051,17 +1126,25 @@ async fn process_main(
                     .draw(&mut oled.fbuf)?;
             }
 
-            let status = if !current_state.time_confirmed {
+            let status = if !current_state.flow.time_confirmed {
                 Some(get_translation(TranslationKey::CONFIRM_TIME))
-            } else if current_state.current_judge.is_none() {
+            } else if current_state.flow.current_judge.is_none() {
                 Some(get_translation(TranslationKey::SCAN_JUDGE_CARD))
-            } else if current_state.current_competitor.is_some()
-                && current_state.current_judge.is_some()
+            } else if current_state.flow.current_competitor.is_some()
+                && current_state.flow.current_judge.is_some()
             {
                 Some(get_translation(TranslationKey::SCAN_COMPETITOR_CARD))
             } else {
                 None
             };
+            let status = match status {
+                Some(status) if current_state.flow.cutoff_missed() => Some(format!(
+                    "{}\n{status}",
+                    get_translation(TranslationKey::CUTOFF_NOT_MET)
+                )),
+                status => status,
+            };
+
             if let Some(status_str) = status {
                 let textbox_style = TextBoxStyleBuilder::new()
                     .alignment(HorizontalAlignment::Center)
@@ -1119,7 +1202,7 @@ async fn process_main_overwrite(
     _global_state: &GlobalState,
     oled: &mut OledData<'_>,
 ) -> bool {
-    if !current_state.scene.can_be_lcd_overwritten() {
+    if !current_state.flow.scene.can_be_lcd_overwritten() {
         return false;
     }
 
@@ -1146,7 +1229,8 @@ async fn process_main_overwrite(
 
         let text = format!("{}\n{}", lines.0, lines.1);
         _ = center_text_layout(&text).draw(&mut oled.fbuf);
-    } else if current_state.stackmat_connected == Some(false) {
+    } else if current_state.stackmat_connected == Some(false) && !crate::settings::attendance_mode()
+    {
         let text = format!(
             "{}\n{}",
             get_translation(TranslationKey::STACKMAT_DISCONNECTED_HEADER),
@@ -4,18 +4,16 @@
 #![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 
 extern crate alloc;
-use crate::consts::NVS_SIGN_KEY;
+use crate::consts::{NVS_SECRET_KEY, NVS_SIGN_KEY, NVS_TLS_PIN};
 use alloc::rc::Rc;
 use alloc::string::ToString;
 use board::Board;
-use consts::LOG_SEND_INTERVAL_MS;
 use embassy_executor::Spawner;
 use embassy_sync::signal::Signal;
 use embassy_time::{Instant, Timer};
 use esp_backtrace as _;
 use esp_hal::interrupt::software::SoftwareInterruptControl;
 use esp_hal_wifimanager::{Nvs, WIFI_NVS_KEY};
-use esp_storage::FlashStorage;
 use state::{GlobalState, GlobalStateInner, SavedGlobalState, Scene, ota_state, sleep_state};
 use structs::ConnSettings;
 use utils::{logger::FkmLogger, set_brownout_detection, spawn_task};
@@ -25,11 +23,18 @@ mod bluetooth;
 mod board;
 mod buttons;
 mod consts;
+mod endpoints;
+mod inspection;
 mod mdns;
+mod ota;
+mod remote_command;
 mod rfid;
+mod settings;
+mod solve_queue;
 mod stackmat;
 mod state;
 mod structs;
+mod time_sync;
 mod translations;
 mod utils;
 mod version;
@@ -137,6 +142,9 @@ async fn main(spawner: Spawner) {
     log::info!("Wake reason: {:?} {:?}", reason, wake_reason);
 
     utils::error_log::load_error_log(&nvs).await;
+    solve_queue::load_solve_queue(&nvs).await;
+    settings::load_settings(&nvs).await;
+    utils::logger::load_log_config(&nvs).await;
 
     let global_state = Rc::new(GlobalStateInner::new(&nvs, board.aes));
     let wifi_setup_sig = Rc::new(Signal::new());
@@ -145,6 +153,11 @@ async fn main(spawner: Spawner) {
     if let Ok(sign_key) = nvs.get::<u32>(NVS_SIGN_KEY).await {
         unsafe { crate::state::SIGN_KEY = sign_key };
     }
+    if let Ok(secret_key) = nvs.get::<alloc::vec::Vec<u8>>(NVS_SECRET_KEY).await
+        && let Ok(secret_key) = secret_key.try_into()
+    {
+        unsafe { crate::state::SECRET_KEY = Some(secret_key) };
+    }
     #[cfg(feature = "v4")]
     if let Ok(saved_volume) = nvs.get::<u8>(crate::consts::NVS_BUZZER_VOLUME).await {
         if (crate::consts::BUZZER_VOLUME_MIN..=crate::consts::BUZZER_VOLUME_MAX)
@@ -261,17 +274,10 @@ async fn main(spawner: Spawner) {
         format_args!("FKM-{:X}", crate::utils::get_efuse_u32()),
     );
 
-    // mark ota as valid
-    {
-        if let Ok(mut ota) =
-            esp_hal_ota::Ota::new(FlashStorage::new(unsafe { board.flash.clone_unchecked() }))
-        {
-            let res = ota.ota_mark_app_valid();
-            if let Err(e) = res {
-                log::error!("Ota mark app valid failed: {e:?}");
-                utils::error_log::add_error(utils::error_log::codes::OTA_MARK_VALID_FAILED).await;
-            }
-        }
+    // mark ota as valid (freshly updated image is validated by ota_validate_task)
+    let ota_pending_verify = ota::is_pending_verify();
+    if !ota_pending_verify {
+        ota::mark_app_valid().await;
     }
 
     Timer::after_millis(500).await;
@@ -309,17 +315,60 @@ async fn main(spawner: Spawner) {
         .and_then(|d| serde_json::from_value(d).ok())
         .unwrap_or_default();
 
+    if let Some(tls_pin) = conn_settings
+        .tls_pin
+        .as_deref()
+        .filter(|pin| !pin.trim().is_empty())
+    {
+        match utils::tls::parse_pin(tls_pin) {
+            Some(pin) => {
+                unsafe { crate::state::TLS_PIN = Some(pin) };
+                _ = nvs.delete(NVS_TLS_PIN).await;
+                _ = nvs.set(NVS_TLS_PIN, &pin[..]).await;
+            }
+            None => {
+                log::error!("Invalid tls pin: {tls_pin}");
+                utils::error_log::add_error(utils::error_log::codes::WS_TLS_PIN_INVALID).await;
+            }
+        }
+    } else if let Ok(pin) = nvs.get::<alloc::vec::Vec<u8>>(NVS_TLS_PIN).await
+        && let Ok(pin) = pin.try_into()
+    {
+        unsafe { crate::state::TLS_PIN = Some(pin) };
+    }
+
+    if let Some(secret_key) = conn_settings
+        .secret_key
+        .as_deref()
+        .filter(|secret| !secret.trim().is_empty())
+    {
+        match utils::signing::parse_secret_key(secret_key) {
+            Some(secret_key) => {
+                unsafe { crate::state::SECRET_KEY = Some(secret_key) };
+                _ = nvs.delete(NVS_SECRET_KEY).await;
+                _ = nvs.set(NVS_SECRET_KEY, &secret_key[..]).await;
+            }
+            None => {
+                log::error!("Invalid device secret (expected 32 hex chars)");
+                utils::error_log::add_error(utils::error_log::codes::SECRET_KEY_INVALID).await;
+            }
+        }
+    }
+
+    endpoints::load_endpoints(&nvs, &conn_settings).await;
+    let first_endpoint = endpoints::endpoints().await.first().cloned();
+
     let mut parse_retry_count = 0;
     let ws_url = loop {
-        let url = if conn_settings.mdns || conn_settings.ws_url.is_none() || parse_retry_count > 0 {
+        let url = if conn_settings.mdns || first_endpoint.is_none() || parse_retry_count > 0 {
             log::info!("Starting mdns lookup...");
-            global_state.state.lock().await.scene = Scene::MdnsWait;
+            global_state.state.lock().await.flow.scene = Scene::MdnsWait;
             let mdns_res = mdns::mdns_query(wifi_res.sta_stack).await;
             log::info!("Mdns result: {mdns_res:?}");
 
             mdns_res.to_string()
         } else {
-            conn_settings.ws_url.clone().unwrap_or_default()
+            first_endpoint.clone().unwrap_or_default()
         };
 
         let ws_url = WsUrl::from_str(&url);
@@ -354,12 +403,41 @@ async fn main(spawner: Spawner) {
         ws::ws_task(
             wifi_res.sta_stack,
             ws_url,
+            conn_settings.mdns || first_endpoint.is_none() || parse_retry_count > 0,
             global_state.clone(),
             ws_sleep_sig.clone(),
             wifi_conn_sig,
         ),
     );
+    spawn_task(
+        &spawner,
+        "solve_queue::solve_queue_task",
+        solve_queue::solve_queue_task(global_state.clone()),
+    );
+    spawn_task(
+        &spawner,
+        "remote_command::remote_command_task",
+        remote_command::remote_command_task(global_state.clone()),
+    );
     spawn_task(&spawner, "logger_task", logger_task(global_state.clone()));
+    spawn_task(
+        &spawner,
+        "time_sync::time_sync_task",
+        time_sync::time_sync_task(global_state.clone()),
+    );
+    spawn_task(
+        &spawner,
+        "inspection::inspection_warnings_task",
+        inspection::inspection_warnings_task(global_state.clone()),
+    );
+
+    if ota_pending_verify {
+        spawn_task(
+            &spawner,
+            "ota::ota_validate_task",
+            ota::ota_validate_task(global_state.clone()),
+        );
+    }
 
     let ble_sleep_sig = Rc::new(Signal::new());
     spawn_task(
@@ -369,7 +447,7 @@ async fn main(spawner: Spawner) {
     );
 
     set_brownout_detection(true);
-    global_state.state.lock().await.scene = Scene::WaitingForCompetitor;
+    global_state.state.lock().await.flow.scene = Scene::WaitingForCompetitor;
     if let Some(saved_state) = SavedGlobalState::from_nvs(&nvs).await {
         global_state
             .state
@@ -428,7 +506,7 @@ async fn logger_task(global_state: GlobalState) {
     let mut heap_start = Instant::now();
 
     loop {
-        Timer::after_millis(LOG_SEND_INTERVAL_MS).await;
+        Timer::after_millis(settings::settings().log_send_interval_ms).await;
 
         if ota_state() || sleep_state() {
             continue;
@@ -0,0 +1,524 @@
+use crate::{
+    consts::{
+        OTA_INFLATE_CHUNK_SIZE, OTA_METADATA_LEN, OTA_RESUME_TIMEOUT_MS, OTA_VALIDATE_AFTER_MS,
+        OTA_VALIDATE_TIMEOUT_MS,
+    },
+    state::GlobalState,
+    structs::{OtaCompression, StartUpdatePacket},
+};
+use alloc::{boxed::Box, string::String};
+use core::cmp::Ordering;
+use embassy_time::{Instant, Timer};
+use esp_hal_ota::{Ota, OtaError, OtaImgState};
+use esp_storage::FlashStorage;
+use miniz_oxide::{
+    DataFormat, MZError, MZFlush, MZStatus,
+    inflate::stream::{InflateState, inflate},
+};
+use sha2::{Digest, Sha256};
+
+/// In-progress firmware update. Outlives single ws connection, so after reconnect
+/// server can continue sending image from `offset` (crc and decompressor state
+/// is kept in memory). `offset` counts received stream bytes (compressed if
+/// update is compressed), `size`/`crc` are of decompressed image.
+pub struct OtaUpdate {
+    ota: Option<Ota<FlashStorage<'static>>>,
+    version: String,
+    size: u32,
+    crc: u32,
+    offset: u32,
+    last_chunk: Instant,
+    image: ImageTracker,
+    signature: Option<[u8; 64]>,
+    allow_downgrade: bool,
+    inflate: Option<Box<InflateState>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OtaUpdateError {
+    Ota(OtaError),
+    Signature(&'static str),
+    Decompress,
+    Metadata(&'static str),
+    Downgrade,
+}
+
+impl OtaUpdateError {
+    pub fn error_code(&self) -> u8 {
+        use crate::utils::error_log::codes;
+
+        match self {
+            Self::Ota(_) => codes::OTA_FLASH_FAILED,
+            Self::Signature(_) => codes::OTA_SIGNATURE_INVALID,
+            Self::Decompress => codes::OTA_DECOMPRESS_FAILED,
+            Self::Metadata(_) => codes::OTA_METADATA_INVALID,
+            Self::Downgrade => codes::OTA_DOWNGRADE_REJECTED,
+        }
+    }
+
+    /// Short reason (fits on display)
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Ota(_) => "flash error",
+            Self::Signature(_) => "bad signature",
+            Self::Decompress => "corrupted image",
+            Self::Metadata(reason) => *reason,
+            Self::Downgrade => "downgrade",
+        }
+    }
+}
+
+impl core::fmt::Display for OtaUpdateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Ota(e) => write!(f, "Ota error: {e:?}"),
+            Self::Signature(e) => write!(f, "{e}"),
+            Self::Decompress => write!(f, "Image decompress failed"),
+            Self::Metadata(e) => write!(f, "Image metadata: {e}"),
+            Self::Downgrade => write!(f, "Image older than running firmware"),
+        }
+    }
+}
+
+impl From<OtaError> for OtaUpdateError {
+    fn from(value: OtaError) -> Self {
+        Self::Ota(value)
+    }
+}
+
+/// Key release images are signed with. Firmware built without `OTA_SIGN_PUBKEY` refuses
+/// updates, unless `insecure_ota` feature is enabled (development only).
+fn ota_pubkey() -> Result<Option<[u8; 32]>, OtaUpdateError> {
+    match crate::version::OTA_PUBKEY {
+        Some(pubkey) => Ok(Some(pubkey)),
+        None if cfg!(feature = "insecure_ota") => Ok(None),
+        None => Err(OtaUpdateError::Signature("Firmware built without OTA key")),
+    }
+}
+
+/// Parses image signature from `StartUpdate`. Signature is required unless firmware
+/// was built with `insecure_ota` and without `OTA_SIGN_PUBKEY` (then it's ignored).
+fn parse_signature(signature: Option<&str>) -> Result<Option<[u8; 64]>, OtaUpdateError> {
+    if ota_pubkey()?.is_none() {
+        return Ok(None);
+    }
+
+    let signature = signature.ok_or(OtaUpdateError::Signature("Missing image signature"))?;
+    let signature = crate::utils::signing::from_hex(signature)
+        .and_then(|sig| <[u8; 64]>::try_from(sig).ok())
+        .ok_or(OtaUpdateError::Signature("Malformed image signature"))?;
+
+    Ok(Some(signature))
+}
+
+/// Metadata trailer appended to release images by `append_metadata.sh`
+/// (version 32B, firmware 16B, hardware 16B - zero padded, build time u64 BE)
+#[derive(Debug)]
+struct FirmwareMetadata<'a> {
+    version: &'a str,
+    firmware: &'a str,
+    hardware: &'a str,
+    build_time: u64,
+}
+
+impl<'a> FirmwareMetadata<'a> {
+    fn parse(trailer: &'a [u8; OTA_METADATA_LEN]) -> Option<Self> {
+        let field = move |range: core::ops::Range<usize>| {
+            let bytes = &trailer[range];
+            let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            core::str::from_utf8(&bytes[..len]).ok()
+        };
+
+        Some(Self {
+            version: field(0..32)?,
+            firmware: field(32..48)?,
+            hardware: field(48..64)?,
+            build_time: u64::from_be_bytes(trailer[64..72].try_into().ok()?),
+        })
+    }
+
+    /// Checks if image is meant for this device and isn't older than running one
+    fn check(&self, allow_downgrade: bool) -> Result<(), OtaUpdateError> {
+        if self.firmware != crate::version::FIRMWARE {
+            return Err(OtaUpdateError::Metadata("wrong firmware"));
+        }
+
+        if self.hardware != crate::version::HW_VER {
+            return Err(OtaUpdateError::Metadata("wrong hardware"));
+        }
+
+        if allow_downgrade {
+            return Ok(());
+        }
+
+        if self.build_time < crate::version::BUILD_TIME
+            || compare_versions(self.version, crate::version::VERSION) == Some(Ordering::Less)
+        {
+            return Err(OtaUpdateError::Downgrade);
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares dotted numeric versions (optional `v` prefix), None if not comparable
+fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let parse = |version: &str| {
+        version
+            .trim_start_matches(['v', 'V'])
+            .split('.')
+            .map(|part| part.parse::<u32>().ok())
+            .collect::<Option<alloc::vec::Vec<u32>>>()
+    };
+
+    Some(parse(a)?.cmp(&parse(b)?))
+}
+
+/// Downgrade must be allowed by release key holder (signature of
+/// `allow_downgrade|{firmware}|{hardware}|{version}`). Without compiled in
+/// `OTA_SIGN_PUBKEY` flag is accepted as is only in `insecure_ota` builds.
+fn verify_allow_downgrade(version: &str, signature: Option<&str>) -> bool {
+    let Ok(pubkey) = ota_pubkey() else {
+        return false;
+    };
+
+    let Some(pubkey) = pubkey else {
+        return true;
+    };
+
+    let Some(signature) = signature
+        .and_then(crate::utils::signing::from_hex)
+        .and_then(|sig| <[u8; 64]>::try_from(sig).ok())
+    else {
+        return false;
+    };
+
+    let Ok(pubkey) = ed25519_dalek::VerifyingKey::from_bytes(&pubkey) else {
+        return false;
+    };
+
+    let msg = alloc::format!(
+        "allow_downgrade|{}|{}|{version}",
+        crate::version::FIRMWARE,
+        crate::version::HW_VER
+    );
+    pubkey
+        .verify_strict(
+            msg.as_bytes(),
+            &ed25519_dalek::Signature::from_bytes(&signature),
+        )
+        .is_ok()
+}
+
+/// Hash of written image (for signature verification) and its last bytes (metadata trailer)
+struct ImageTracker {
+    hasher: Sha256,
+    tail: [u8; OTA_METADATA_LEN],
+}
+
+impl ImageTracker {
+    fn new() -> Self {
+        Self {
+            hasher: Sha256::new(),
+            tail: [0; OTA_METADATA_LEN],
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        if data.len() >= OTA_METADATA_LEN {
+            self.tail
+                .copy_from_slice(&data[data.len() - OTA_METADATA_LEN..]);
+        } else {
+            self.tail.copy_within(data.len().., 0);
+            self.tail[OTA_METADATA_LEN - data.len()..].copy_from_slice(data);
+        }
+    }
+}
+
+/// Writes decompressed image data
+fn write_image(
+    ota: &mut Ota<FlashStorage<'static>>,
+    image: &mut ImageTracker,
+    data: &[u8],
+) -> Result<bool, OtaUpdateError> {
+    let res = ota.ota_write_chunk(data)?;
+    image.update(data);
+
+    Ok(res)
+}
+
+impl OtaUpdate {
+    pub fn new() -> Self {
+        Self {
+            ota: None,
+            version: String::new(),
+            size: 0,
+            crc: 0,
+            offset: 0,
+            last_chunk: Instant::from_ticks(0),
+            image: ImageTracker::new(),
+            signature: None,
+            allow_downgrade: false,
+            inflate: None,
+        }
+    }
+
+    pub fn begin(&mut self, update: StartUpdatePacket) -> Result<(), OtaUpdateError> {
+        let signature = parse_signature(update.signature.as_deref())?;
+        let allow_downgrade = update.allow_downgrade
+            && verify_allow_downgrade(&update.version, update.downgrade_signature.as_deref());
+        if update.allow_downgrade && !allow_downgrade {
+            return Err(OtaUpdateError::Signature("Invalid downgrade signature"));
+        }
+
+        // early check (before transfer), trailer is checked after whole image is written
+        // (older servers send build_time 0, then only trailer build time is checked)
+        FirmwareMetadata {
+            version: &update.version,
+            firmware: &update.firmware,
+            hardware: crate::version::HW_VER,
+            build_time: match update.build_time {
+                0 => crate::version::BUILD_TIME,
+                build_time => build_time,
+            },
+        }
+        .check(allow_downgrade)?;
+
+        self.abort();
+
+        let mut ota = Ota::new(FlashStorage::new(unsafe {
+            esp_hal::peripherals::FLASH::steal()
+        }))?;
+        ota.ota_begin(update.size, update.crc)?;
+
+        self.ota = Some(ota);
+        self.version = update.version;
+        self.size = update.size;
+        self.crc = update.crc;
+        self.offset = 0;
+        self.last_chunk = Instant::now();
+        self.image = ImageTracker::new();
+        self.signature = signature;
+        self.allow_downgrade = allow_downgrade;
+        self.inflate = match update.compression {
+            Some(OtaCompression::Zlib) => Some(InflateState::new_boxed(DataFormat::Zlib)),
+            None => None,
+        };
+        unsafe {
+            crate::state::OTA_STATE = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn abort(&mut self) {
+        self.ota = None;
+        self.inflate = None;
+        unsafe {
+            crate::state::OTA_STATE = false;
+        }
+    }
+
+    #[inline(always)]
+    pub fn in_progress(&self) -> bool {
+        self.ota.is_some()
+    }
+
+    /// Returns true if whole image was written
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> Result<bool, OtaUpdateError> {
+        let Some(ota) = self.ota.as_mut() else {
+            return Ok(false);
+        };
+
+        self.offset += chunk.len() as u32;
+        self.last_chunk = Instant::now();
+
+        let Some(inflate_state) = self.inflate.as_mut() else {
+            return write_image(ota, &mut self.image, chunk);
+        };
+
+        let mut input = chunk;
+        let mut out = [0; OTA_INFLATE_CHUNK_SIZE];
+        loop {
+            let res = inflate(inflate_state, input, &mut out, MZFlush::None);
+            input = &input[res.bytes_consumed..];
+
+            let done = res.bytes_written > 0
+                && write_image(ota, &mut self.image, &out[..res.bytes_written])?;
+
+            match res.status {
+                Ok(MZStatus::StreamEnd) => return Ok(done),
+                Ok(_) if done => return Ok(true),
+                Ok(_) => {}
+                // no progress possible (needs more input)
+                Err(MZError::Buf) => return Ok(done),
+                Err(_) => return Err(OtaUpdateError::Decompress),
+            }
+
+            if input.is_empty() && res.bytes_written < out.len() {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Checks written image before it's marked bootable (metadata trailer and signature)
+    pub fn verify_image(&self) -> Result<(), OtaUpdateError> {
+        let metadata = FirmwareMetadata::parse(&self.image.tail)
+            .ok_or(OtaUpdateError::Metadata("no metadata"))?;
+        log::info!("OTA image metadata: {metadata:?}");
+
+        metadata.check(self.allow_downgrade)?;
+        if self.allow_downgrade && metadata.version != self.version {
+            return Err(OtaUpdateError::Downgrade);
+        }
+
+        if !self.verify_signature() {
+            return Err(OtaUpdateError::Signature("Invalid image signature"));
+        }
+
+        Ok(())
+    }
+
+    /// Verifies ed25519 signature of sha256 digest of whole image
+    /// (always true in `insecure_ota` builds without `OTA_SIGN_PUBKEY`)
+    fn verify_signature(&self) -> bool {
+        let Ok(pubkey) = ota_pubkey() else {
+            return false;
+        };
+
+        let Some(pubkey) = pubkey else {
+            return true;
+        };
+
+        let Some(signature) = self.signature else {
+            return false;
+        };
+
+        let Ok(pubkey) = ed25519_dalek::VerifyingKey::from_bytes(&pubkey) else {
+            return false;
+        };
+
+        let digest = self.image.hasher.clone().finalize();
+        pubkey
+            .verify_strict(&digest, &ed25519_dalek::Signature::from_bytes(&signature))
+            .is_ok()
+    }
+
+    pub fn flush(&mut self) -> Result<(), OtaUpdateError> {
+        let Some(ota) = self.ota.as_mut() else {
+            return Ok(());
+        };
+
+        Ok(ota.ota_flush(true, true)?)
+    }
+
+    pub fn progress(&self) -> u8 {
+        self.ota
+            .as_ref()
+            .map(|ota| (ota.get_ota_progress() * 100.0) as u8)
+            .unwrap_or(0)
+    }
+
+    /// Packet sent after reconnect, so server continues from last written offset
+    pub fn resume_packet(&self) -> Option<crate::structs::TimerPacketInner> {
+        if !self.in_progress() {
+            return None;
+        }
+
+        Some(crate::structs::TimerPacketInner::OtaResume {
+            version: self.version.clone(),
+            firmware: alloc::string::ToString::to_string(crate::version::FIRMWARE),
+            size: self.size,
+            crc: self.crc,
+            offset: self.offset,
+        })
+    }
+
+    /// Update wasn't continued for too long (server gone or doesn't support resume)
+    pub fn resume_expired(&self) -> bool {
+        self.in_progress() && self.last_chunk.elapsed().as_millis() > OTA_RESUME_TIMEOUT_MS
+    }
+}
+
+fn boot_ota() -> Option<Ota<FlashStorage<'static>>> {
+    Ota::new(FlashStorage::new(unsafe {
+        esp_hal::peripherals::FLASH::steal()
+    }))
+    .ok()
+}
+
+/// True if running image was just installed by OTA and isn't validated yet
+pub fn is_pending_verify() -> bool {
+    let Some(mut ota) = boot_ota() else {
+        return false;
+    };
+
+    matches!(
+        ota.get_ota_image_state(),
+        Ok(OtaImgState::EspOtaImgNew | OtaImgState::EspOtaImgPendingVerify)
+    )
+}
+
+pub async fn mark_app_valid() {
+    let Some(mut ota) = boot_ota() else {
+        return;
+    };
+
+    let res = ota.ota_mark_app_valid();
+    if let Err(e) = res {
+        log::error!("Ota mark app valid failed: {e:?}");
+        crate::utils::error_log::add_error(crate::utils::error_log::codes::OTA_MARK_VALID_FAILED)
+            .await;
+    }
+}
+
+/// Keeps new image in "pending verify" state until device proves it works
+/// (ws connected and trusted, rfid initialized, display flushed and no panic
+/// for `OTA_VALIDATE_AFTER_MS`). Otherwise rolls back to previous partition.
+/// Panic before validation resets device, so bootloader rolls back too.
+#[embassy_executor::task]
+pub async fn ota_validate_task(global_state: GlobalState) {
+    log::warn!("New firmware pending verify!");
+
+    let start = Instant::now();
+    let mut ws_trusted = false;
+    let mut rfid_init = false;
+    let mut display_flushed = false;
+
+    loop {
+        Timer::after_millis(1000).await;
+
+        let server_connected = global_state.state.lock().await.server_connected == Some(true);
+        ws_trusted |=
+            server_connected && (unsafe { crate::state::TRUST_SERVER } || cfg!(feature = "e2e"));
+        rfid_init |= unsafe { crate::state::RFID_INIT };
+        display_flushed |= unsafe { crate::state::DISPLAY_FLUSHED };
+
+        let elapsed = start.elapsed().as_millis();
+        if ws_trusted && rfid_init && display_flushed && elapsed >= OTA_VALIDATE_AFTER_MS {
+            log::info!("New firmware healthy, marking as valid");
+            mark_app_valid().await;
+            return;
+        }
+
+        if elapsed >= OTA_VALIDATE_TIMEOUT_MS {
+            break;
+        }
+    }
+
+    log::error!(
+        "New firmware unhealthy (ws: {ws_trusted}, rfid: {rfid_init}, display: {display_flushed}), rolling back!"
+    );
+    crate::utils::error_log::add_error(crate::utils::error_log::codes::OTA_HEALTH_CHECK_FAILED)
+        .await;
+    crate::utils::error_log::save_error_log(&global_state.nvs).await;
+
+    if let Some(mut ota) = boot_ota()
+        && let Err(e) = ota.ota_mark_app_invalid_rollback()
+    {
+        log::error!("Ota rollback failed: {e:?}");
+    }
+
+    esp_hal::system::software_reset();
+}
@@ -0,0 +1,97 @@
+use crate::{
+    consts::{NVS_BONDING_KEY, REMOTE_COMMAND_ACTION_DELAY_MS},
+    state::{BleAction, GlobalState},
+    structs::{RemoteCommandKind, TimerPacket, TimerPacketInner},
+};
+use alloc::string::{String, ToString};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Timer;
+
+/// Last accepted command nonce (commands must use strictly increasing nonces per connection)
+static mut LAST_NONCE: u64 = 0;
+
+/// Reboot / factory reset to run after ack was written to socket
+static RESTART_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// Called on every new ws connection
+pub fn reset_nonce() {
+    unsafe { LAST_NONCE = 0 };
+}
+
+pub async fn handle_command(
+    global_state: &GlobalState,
+    tag: Option<u64>,
+    command: RemoteCommandKind,
+    nonce: u64,
+    hmac: &str,
+) {
+    let res = authenticate(command, nonce, hmac);
+    crate::ws::send_packet(TimerPacket {
+        tag,
+        data: TimerPacketInner::RemoteCommandAck {
+            command,
+            accepted: res.is_ok(),
+            error: res.clone().err(),
+        },
+    })
+    .await;
+
+    if let Err(e) = res {
+        log::error!("Remote command {command:?} rejected: {e}");
+        return;
+    }
+
+    log::warn!("Remote command: {command:?}");
+    unsafe { LAST_NONCE = nonce };
+
+    match command {
+        RemoteCommandKind::Reboot => RESTART_SIGNAL.signal(false),
+        RemoteCommandKind::FactoryReset => RESTART_SIGNAL.signal(true),
+        RemoteCommandKind::ClearSavedSolve => {
+            global_state
+                .state
+                .lock()
+                .await
+                .reset_solve_state(Some(&global_state.nvs))
+                .await;
+        }
+        RemoteCommandKind::ClearErrorLog => {
+            crate::utils::error_log::clear_error_log(&global_state.nvs).await;
+        }
+        RemoteCommandKind::ForgetBleBond => {
+            _ = global_state.nvs.delete(NVS_BONDING_KEY).await;
+            global_state.ble_sig.signal(BleAction::Unpair);
+        }
+    }
+}
+
+fn authenticate(command: RemoteCommandKind, nonce: u64, hmac: &str) -> Result<(), String> {
+    if unsafe { !crate::state::TRUST_SERVER } {
+        return Err("Server not trusted".to_string());
+    }
+
+    if nonce <= unsafe { LAST_NONCE } {
+        return Err("Nonce reused".to_string());
+    }
+
+    if !crate::utils::signing::verify_command(command.as_str(), nonce, hmac) {
+        return Err("Invalid signature".to_string());
+    }
+
+    Ok(())
+}
+
+#[embassy_executor::task]
+pub async fn remote_command_task(global_state: GlobalState) {
+    let factory_reset = RESTART_SIGNAL.wait().await;
+
+    // give ws task time to flush ack
+    Timer::after_millis(REMOTE_COMMAND_ACTION_DELAY_MS).await;
+    if factory_reset {
+        crate::utils::factory_reset(&global_state.nvs).await;
+    } else {
+        crate::utils::error_log::save_error_log(&global_state.nvs).await;
+    }
+
+    esp_hal::system::software_reset();
+}
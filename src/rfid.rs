@@ -1,11 +1,14 @@
-use crate::consts::RFID_RETRY_INIT_MS;
-use crate::state::{GlobalState, MenuScene, current_epoch, sleep_state};
-use crate::structs::{CardInfoResponsePacket, SolveConfirmPacket};
+use crate::consts::{ATTENDANCE_RESULT_SHOW_MS, RFID_RETRY_INIT_MS};
+use crate::state::{GlobalState, MenuScene, sleep_state};
+use crate::structs::{AttendanceResponse, CardInfoResponsePacket, SolveConfirmPacket};
+use crate::time_sync::current_epoch_ms;
 use crate::translations::{TranslationKey, get_translation};
+use crate::ws::RequestError;
 use alloc::string::ToString;
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use embassy_time::{Duration, Instant, Timer};
 use esp_hal_mfrc522::consts::UidSize;
+use fkm_flow::{CardInfo, Command, Event, Notice};
 
 #[cfg(feature = "v3")]
 use esp_hal::time::Rate;
@@ -113,6 +116,8 @@ pub async fn rfid_task(
         }
     }
 
+    unsafe { crate::state::RFID_INIT = true };
+
     #[cfg(not(feature = "e2e"))]
     log::debug!("PCD ver: {:?}", mfrc522.pcd_get_version().await);
 
@@ -124,7 +129,10 @@ pub async fn rfid_task(
             rfid_sleep = sleep_state();
 
             match rfid_sleep {
-                true => _ = mfrc522.pcd_soft_power_down().await,
+                true => {
+                    _ = mfrc522.pcd_soft_power_down().await;
+                    unsafe { crate::state::RFID_INIT = false };
+                }
                 false => {
                     _ = mfrc522.pcd_soft_power_up().await;
                     Timer::after_millis(100).await;
@@ -147,6 +155,8 @@ pub async fn rfid_task(
                         }
                         Timer::after(Duration::from_millis(RFID_RETRY_INIT_MS)).await;
                     }
+
+                    unsafe { crate::state::RFID_INIT = true };
                 }
             }
         }
@@ -163,6 +173,17 @@ pub async fn rfid_task(
             continue;
         }
 
+        #[cfg(feature = "v4")]
+        if global_state.buzzer_inspection_warning.signaled() {
+            let warning = global_state.buzzer_inspection_warning.wait().await;
+            for &(on, off) in warning.pattern() {
+                beep(&mut buzzer, on).await;
+                Timer::after_millis(off).await;
+            }
+
+            continue;
+        }
+
         #[cfg(feature = "e2e")]
         if !global_state.e2e.card_scan_sig.signaled() {
             continue;
@@ -193,7 +214,10 @@ pub async fn rfid_task(
             global_state.state.value().await.server_connected == Some(true)
         };
 
-        if (last_card.0 == card_uid && last_scan_time < 500) || !is_server_connected {
+        if (last_card.0 == card_uid
+            && last_scan_time < crate::settings::settings().rfid_duplicate_window_ms)
+            || !is_server_connected
+        {
             log::warn!(
                 "Skipping card scan: {last_scan_time:?} {card_uid} {}",
                 last_card.0
@@ -395,11 +419,35 @@ pub async fn rfid_task(
             }
         }
 
-        let is_competitor = {
-            let state = global_state.state.lock().await;
-            state.current_competitor.is_none()
-                || state.current_competitor.unwrap_or_default() == card_uid as u64
-        };
+        if crate::settings::attendance_mode() {
+            let marked = mark_attendance(card_uid as u64, &global_state).await;
+            #[cfg(feature = "v4")]
+            beep_attendance(&mut buzzer, &global_state, marked).await;
+            #[cfg(not(feature = "v4"))]
+            _ = marked;
+
+            #[cfg(feature = "e2e")]
+            crate::ws::send_test_ack(&global_state).await;
+
+            #[cfg(not(feature = "e2e"))]
+            {
+                _ = mfrc522.picc_halta().await;
+                if unsafe { crate::state::SECURE_RFID } {
+                    _ = mfrc522.pcd_stop_crypto1().await;
+                }
+            }
+
+            Timer::after_millis(ATTENDANCE_RESULT_SHOW_MS).await;
+            global_state.state.lock().await.custom_message = None;
+            continue;
+        }
+
+        let is_competitor = global_state
+            .state
+            .lock()
+            .await
+            .flow
+            .is_competitor_card(card_uid as u64);
 
         let resp = crate::ws::send_request::<CardInfoResponsePacket>(
             crate::structs::TimerPacketInner::CardInfoRequest {
@@ -407,6 +455,7 @@ pub async fn rfid_task(
                 is_competitor,
                 attendance_device: None,
                 sign_key: unsafe { crate::state::SIGN_KEY },
+                hmac: None,
             },
         )
         .await;
@@ -418,7 +467,7 @@ pub async fn rfid_task(
                     log::error!("[RFID] Process_card_info_response: {e:?}");
                 }
             }
-            Err(e) => {
+            Err(RequestError::Server(e)) => {
                 log::error!(
                     "[RFID] Resp_error: ({}): {:?}",
                     e.should_reset_time,
@@ -431,6 +480,10 @@ pub async fn rfid_task(
                     state.reset_solve_state(None).await;
                 }
             }
+            Err(e) => {
+                log::error!("[RFID] Card info request failed: {e:?}");
+                global_state.state.lock().await.error_text = Some(e.to_string());
+            }
         }
 
         #[cfg(feature = "e2e")]
@@ -446,6 +499,50 @@ pub async fn rfid_task(
     }
 }
 
+/// Reports attendance of scanned card, result is shown as custom message
+async fn mark_attendance(card_id: u64, global_state: &GlobalState) -> bool {
+    let resp = crate::ws::send_request::<AttendanceResponse>(
+        crate::structs::TimerPacketInner::CardInfoRequest {
+            card_id,
+            is_competitor: false,
+            attendance_device: Some(true),
+            sign_key: unsafe { crate::state::SIGN_KEY },
+            hmac: None,
+        },
+    )
+    .await;
+
+    let (message, marked) = match resp {
+        Ok(resp) => {
+            log::info!("[RFID] Attendance marked: {card_id} ({:?})", resp.display);
+            (
+                (
+                    resp.display.unwrap_or_else(|| card_id.to_string()),
+                    get_translation(TranslationKey::ATTENDANCE_MARKED),
+                ),
+                true,
+            )
+        }
+        Err(RequestError::Server(e)) => {
+            log::error!("[RFID] Attendance resp_error: {:?}", e.error);
+            (
+                (get_translation(TranslationKey::ERROR_HEADER), e.error),
+                false,
+            )
+        }
+        Err(e) => {
+            log::error!("[RFID] Attendance request failed: {e:?}");
+            (
+                (get_translation(TranslationKey::ERROR_HEADER), e.to_string()),
+                false,
+            )
+        }
+    };
+
+    global_state.state.lock().await.custom_message = Some(message);
+    marked
+}
+
 async fn process_card_info_response(
     resp: CardInfoResponsePacket,
     global_state: &GlobalState,
@@ -455,56 +552,35 @@ async fn process_card_info_response(
         return Ok(());
     }
 
-    match state.scene {
-        crate::state::Scene::WaitingForCompetitor
-            if state.current_competitor.is_none() && resp.can_compete =>
-        {
-            state.competitor_display = Some(resp.display);
-            state.current_competitor = Some(resp.card_id);
-
-            let competitor_locale =
-                crate::translations::get_locale_index(&resp.country_iso2.to_lowercase());
-            if competitor_locale != crate::translations::current_locale_index() {
-                crate::translations::select_locale_idx(competitor_locale, global_state);
-            }
-
-            match resp.possible_groups.len() {
-                1 => {
-                    state.solve_group = Some(resp.possible_groups[0].clone());
-                    unsafe {
-                        crate::state::GROUP_LIMIT = resp.possible_groups[0].limit;
-                    }
+    let transition = state
+        .handle_flow_event(
+            Event::CardInfo(CardInfo {
+                card_id: resp.card_id,
+                display: resp.display,
+                can_compete: resp.can_compete,
+                possible_groups: resp.possible_groups,
+            }),
+            &global_state.nvs,
+        )
+        .await;
 
-                    if state.solve_time.is_some() {
-                        state.scene = crate::state::Scene::Finished;
-                    } else {
-                        state.scene = crate::state::Scene::CompetitorInfo;
-                    }
-                }
-                2.. => {
-                    state.possible_groups = resp.possible_groups;
-                    state.scene = crate::state::Scene::GroupSelect;
-                }
-                _ => {
-                    state.reset_solve_state(None).await;
-                    state.error_text = Some(get_translation(TranslationKey::EMPTY_GROUPS_ERROR));
+    for command in transition.commands {
+        match command {
+            Command::CompetitorSelected { card_id }
+                if state.flow.current_competitor == Some(card_id) =>
+            {
+                let competitor_locale =
+                    crate::translations::get_locale_index(&resp.country_iso2.to_lowercase());
+                if competitor_locale != crate::translations::current_locale_index() {
+                    crate::translations::select_locale_idx(competitor_locale, global_state);
                 }
             }
-        }
-        crate::state::Scene::Finished => {
-            if state.current_competitor != Some(resp.card_id) && state.time_confirmed {
-                state.current_judge = Some(resp.card_id);
-            } else if let Some(current_competitor) = state.current_competitor
-                && let Some(current_judge) = state.current_judge
-                && state.current_competitor == Some(resp.card_id)
-                && state.time_confirmed
-            {
-                let inspection_time = state
-                    .use_inspection()
-                    .then_some((state.inspection_end, state.inspection_start))
-                    .and_then(|(end, start)| end.zip(start))
-                    .map(|(end, start)| (end - start).as_millis() as i64)
-                    .unwrap_or(0);
+            Command::SubmitSolve(submission) => {
+                #[cfg(not(feature = "e2e"))]
+                if unsafe { !crate::state::TRUST_SERVER } {
+                    log::error!("Skipping solve send. Server not trusted!");
+                    return Ok(());
+                }
 
                 let session_id = match &state.session_id {
                     Some(sess_id) => sess_id.clone(),
@@ -515,68 +591,77 @@ async fn process_card_info_response(
                     }
                 };
 
-                let Some(ref solve_group) = state.solve_group else {
-                    log::error!("Solve group is none! (How would that happen?)");
-                    static LOGGED: core::sync::atomic::AtomicBool =
-                        core::sync::atomic::AtomicBool::new(false);
-                    if !LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
-                        crate::utils::error_log::add_error(
-                            crate::utils::error_log::codes::RFID_SOLVE_GROUP_MISSING,
-                        )
-                        .await;
-
-                        LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
-                    }
-                    return Ok(());
+                let epoch_ms = current_epoch_ms();
+                let solve_packet = crate::structs::TimerPacketInner::Solve {
+                    solve_time: submission.solve_time,
+                    penalty: submission.penalty as i64,
+                    competitor_id: submission.competitor_id,
+                    judge_id: submission.judge_id,
+                    timestamp: epoch_ms / 1000,
+                    timestamp_ms: epoch_ms,
+                    session_id,
+                    delegate: false,
+                    inspection_time: submission.inspection_time,
+                    group_id: submission.group.group_id,
+                    sign_key: unsafe { crate::state::SIGN_KEY },
+                    hmac: None,
                 };
 
-                #[cfg(not(feature = "e2e"))]
-                if unsafe { !crate::state::TRUST_SERVER } {
-                    log::error!("Skipping solve send. Server not trusted!");
+                // send_request can retry for a while, state can't stay locked for that long
+                drop(state);
+
+                // older solves are still waiting for replay, new one goes after them
+                if !crate::solve_queue::is_empty().await {
+                    log::warn!("Solve queue not empty, queueing solve");
+                    queue_solve(global_state, solve_packet).await;
+                    crate::solve_queue::trigger_replay();
                     return Ok(());
                 }
 
-                let resp = crate::ws::send_request::<SolveConfirmPacket>(
-                    crate::structs::TimerPacketInner::Solve {
-                        solve_time: state.solve_time.ok_or(anyhow!("Solve time is None"))?,
-                        penalty: state.penalty.unwrap_or(0) as i64,
-                        competitor_id: current_competitor,
-                        judge_id: current_judge,
-                        timestamp: current_epoch(),
-                        session_id,
-                        delegate: false,
-                        inspection_time,
-                        group_id: solve_group.group_id.clone(),
-                        sign_key: unsafe { crate::state::SIGN_KEY },
-                    },
-                )
-                .await;
+                let resp =
+                    crate::ws::send_request::<SolveConfirmPacket>(solve_packet.clone()).await;
 
-                if let Ok(resp) = resp {
-                    log::warn!("solve_resp: {resp:?}");
-                    state.reset_solve_state(Some(&global_state.nvs)).await;
+                match resp {
+                    Err(RequestError::Server(e)) => {
+                        // api error is shown by ws task
+                        log::error!("Solve rejected: {:?}", e.error);
+                    }
+                    Err(e) => {
+                        // server didn't respond, keep solve in queue and replay it on reconnect
+                        log::error!("Solve send failed: {e:?}");
+                        queue_solve(global_state, solve_packet).await;
+                    }
+                    Ok(resp) => {
+                        log::warn!("solve_resp: {resp:?}");
+                        crate::solve_queue::ack_solve(&global_state.nvs, &resp.session_id).await;
 
-                    let words: alloc::vec::Vec<&str> = resp.message.split(' ').collect();
-                    if words.len() >= 2 {
-                        let first_line = words[..2].join(" ");
-                        let second_line = words[2..].join(" ");
+                        let mut state = global_state.state.lock().await;
+                        state
+                            .handle_flow_event(Event::SolveSent, &global_state.nvs)
+                            .await;
 
-                        state.custom_message = Some((first_line, second_line));
-                    } else {
-                        state.custom_message = Some(("Solve".to_string(), "sent".to_string()));
-                    }
+                        let words: alloc::vec::Vec<&str> = resp.message.split(' ').collect();
+                        if words.len() >= 2 {
+                            let first_line = words[..2].join(" ");
+                            let second_line = words[2..].join(" ");
 
-                    drop(state);
-                    Timer::after_millis(3000).await;
+                            state.custom_message = Some((first_line, second_line));
+                        } else {
+                            state.custom_message = Some(("Solve".to_string(), "sent".to_string()));
+                        }
+
+                        drop(state);
+                        Timer::after_millis(3000).await;
 
-                    {
-                        global_state.state.lock().await.custom_message = None;
+                        {
+                            global_state.state.lock().await.custom_message = None;
+                        }
                     }
                 }
-            } else if state.current_competitor == Some(resp.card_id)
-                && state.current_judge.is_none()
-                && state.time_confirmed
-            {
+
+                return Ok(());
+            }
+            Command::Notify(Notice::CardsCannotBeTheSame) => {
                 state.custom_message = Some((
                     get_translation(TranslationKey::CARDS_CANNOT_BE_THE_SAME_HEADER),
                     get_translation(TranslationKey::CARDS_CANNOT_BE_THE_SAME_FOOTER),
@@ -584,14 +669,38 @@ async fn process_card_info_response(
                 drop(state);
                 Timer::after_millis(8000).await;
                 global_state.state.lock().await.custom_message = None;
+
+                return Ok(());
             }
+            _ => {}
         }
-        _ => {}
     }
 
     Ok(())
 }
 
+/// Saves solve in solve queue (replayed later), solve is reset only if it was queued
+async fn queue_solve(global_state: &GlobalState, solve_packet: crate::structs::TimerPacketInner) {
+    let queued = crate::solve_queue::push_solve(&global_state.nvs, solve_packet).await;
+
+    {
+        let mut state = global_state.state.lock().await;
+
+        // queue full, solve stays on screen so judge can submit it again later
+        state.custom_message = if queued {
+            state
+                .handle_flow_event(Event::SolveSent, &global_state.nvs)
+                .await;
+            Some(("Solve saved".to_string(), "Will send later".to_string()))
+        } else {
+            Some(("Queue full".to_string(), "Solve not sent".to_string()))
+        };
+    }
+
+    Timer::after_millis(3000).await;
+    global_state.state.lock().await.custom_message = None;
+}
+
 #[cfg(feature = "v4")]
 async fn beep_card_scan(
     buzzer: &mut esp_hal::ledc::channel::Channel<'static, esp_hal::ledc::LowSpeed>,
@@ -599,17 +708,46 @@ async fn beep_card_scan(
     sound_test: bool,
 ) {
     if global_state.state.value().await.sound_enabled || sound_test {
-        use esp_hal::ledc::channel::ChannelIFace;
-        const BEEP_DUTY_PERCENT: u8 = 50;
-        const BEEP_DURATION_MS: u64 = 100;
+        beep(buzzer, 100).await;
+    }
+}
 
-        let volume: f32 = match crate::state::buzzer_volume() {
-            0 => 0.0,
-            v => 0.55 + (v - 1) as f32 * (0.25 / 23.0),
-        };
-        let volume = volume * volume * volume * volume;
-        _ = buzzer.set_duty((BEEP_DUTY_PERCENT as f32 * volume) as u8);
-        Timer::after_millis(BEEP_DURATION_MS).await;
-        _ = buzzer.set_duty(0);
+/// Two short beeps when attendance was marked, one long on error
+#[cfg(feature = "v4")]
+async fn beep_attendance(
+    buzzer: &mut esp_hal::ledc::channel::Channel<'static, esp_hal::ledc::LowSpeed>,
+    global_state: &GlobalState,
+    marked: bool,
+) {
+    if !global_state.state.value().await.sound_enabled {
+        return;
     }
+
+    if marked {
+        Timer::after_millis(100).await;
+        beep(buzzer, 60).await;
+        Timer::after_millis(60).await;
+        beep(buzzer, 60).await;
+    } else {
+        Timer::after_millis(100).await;
+        beep(buzzer, 500).await;
+    }
+}
+
+#[cfg(feature = "v4")]
+async fn beep(
+    buzzer: &mut esp_hal::ledc::channel::Channel<'static, esp_hal::ledc::LowSpeed>,
+    duration_ms: u64,
+) {
+    use esp_hal::ledc::channel::ChannelIFace;
+    const BEEP_DUTY_PERCENT: u8 = 50;
+
+    let volume: f32 = match crate::state::buzzer_volume() {
+        0 => 0.0,
+        v => 0.55 + (v - 1) as f32 * (0.25 / 23.0),
+    };
+    let volume = volume * volume * volume * volume;
+    _ = buzzer.set_duty((BEEP_DUTY_PERCENT as f32 * volume) as u8);
+    Timer::after_millis(duration_ms).await;
+    _ = buzzer.set_duty(0);
 }
@@ -0,0 +1,126 @@
+use crate::consts::NVS_SETTINGS;
+use alloc::vec::Vec;
+use esp_hal_wifimanager::Nvs;
+use fkm_common::settings::{
+    DEEPER_SLEEP_AFTER_MS, INSPECTION_TIME_DNF, INSPECTION_TIME_PLUS2, SLEEP_AFTER_MS,
+};
+
+pub use fkm_common::settings::{SETTINGS_VERSION, Settings, SettingsUpdate};
+
+static mut SETTINGS: Settings = Settings::DEFAULT;
+
+static SETTINGS_WRITE_LOGGED: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
+
+#[inline(always)]
+pub fn settings() -> Settings {
+    unsafe { SETTINGS }
+}
+
+#[inline(always)]
+pub fn attendance_mode() -> bool {
+    unsafe { SETTINGS.attendance_mode }
+}
+
+pub async fn load_settings(nvs: &Nvs) {
+    let Ok(buf) = nvs.get::<Vec<u8>>(NVS_SETTINGS).await else {
+        return;
+    };
+
+    match serde_json::from_slice::<Settings>(&buf) {
+        Ok(mut saved) => {
+            saved.version = SETTINGS_VERSION;
+            let saved = validate(saved);
+            log::info!("Loaded settings: {saved:?}");
+            unsafe { SETTINGS = saved };
+        }
+        Err(e) => {
+            log::error!("Settings parse failed: {e:?}");
+            _ = nvs.delete(NVS_SETTINGS).await;
+        }
+    }
+}
+
+/// Applies update, persists it and returns effective settings
+pub async fn apply_settings(nvs: &Nvs, version: u32, update: SettingsUpdate) -> Settings {
+    if version > SETTINGS_VERSION {
+        log::warn!("Settings version {version} newer than supported ({SETTINGS_VERSION})");
+    }
+
+    let mut new = settings();
+    #[cfg(feature = "sleep")]
+    {
+        new.sleep_after_ms = update.sleep_after_ms.unwrap_or(new.sleep_after_ms);
+        new.deeper_sleep_after_ms = update
+            .deeper_sleep_after_ms
+            .unwrap_or(new.deeper_sleep_after_ms);
+    }
+    new.inspection_plus2_ms = update
+        .inspection_plus2_ms
+        .unwrap_or(new.inspection_plus2_ms);
+    new.inspection_dnf_ms = update.inspection_dnf_ms.unwrap_or(new.inspection_dnf_ms);
+    new.rfid_duplicate_window_ms = update
+        .rfid_duplicate_window_ms
+        .unwrap_or(new.rfid_duplicate_window_ms);
+    new.log_send_interval_ms = update
+        .log_send_interval_ms
+        .unwrap_or(new.log_send_interval_ms);
+    new.attendance_mode = update.attendance_mode.unwrap_or(new.attendance_mode);
+    new.inspection_warnings = update
+        .inspection_warnings
+        .unwrap_or(new.inspection_warnings);
+
+    let new = validate(new);
+    unsafe { SETTINGS = new };
+    save_settings(nvs, &new).await;
+
+    log::info!("Settings applied: {new:?}");
+    new
+}
+
+/// Keeps settings in sane ranges (falls back to defaults on inconsistent values)
+fn validate(mut settings: Settings) -> Settings {
+    #[cfg(not(feature = "sleep"))]
+    {
+        settings.sleep_after_ms = SLEEP_AFTER_MS;
+        settings.deeper_sleep_after_ms = DEEPER_SLEEP_AFTER_MS;
+    }
+
+    settings.sleep_after_ms = settings.sleep_after_ms.max(30000);
+    settings.deeper_sleep_after_ms = settings.deeper_sleep_after_ms.max(settings.sleep_after_ms);
+
+    if settings.inspection_plus2_ms == 0
+        || settings.inspection_dnf_ms < settings.inspection_plus2_ms
+    {
+        settings.inspection_plus2_ms = INSPECTION_TIME_PLUS2;
+        settings.inspection_dnf_ms = INSPECTION_TIME_DNF;
+    }
+
+    settings.rfid_duplicate_window_ms = settings.rfid_duplicate_window_ms.min(10000);
+    settings.log_send_interval_ms = settings.log_send_interval_ms.clamp(100, 60000);
+    settings
+}
+
+async fn save_settings(nvs: &Nvs, settings: &Settings) {
+    _ = nvs.delete(NVS_SETTINGS).await;
+    if *settings == Settings::DEFAULT {
+        return;
+    }
+
+    let Ok(vec) = serde_json::to_vec(settings) else {
+        return;
+    };
+
+    let res = nvs.set(NVS_SETTINGS, vec.as_slice()).await;
+    if let Err(e) = res {
+        log::error!("{e:?} Faile to write to nvs! (SETTINGS {})", vec.len());
+        if !SETTINGS_WRITE_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+            crate::utils::error_log::add_error(
+                crate::utils::error_log::codes::NVS_SETTINGS_WRITE_FAILED,
+            )
+            .await;
+
+            SETTINGS_WRITE_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    consts::{NVS_SOLVE_QUEUE, SOLVE_QUEUE_MAX_LEN, SOLVE_QUEUE_REPLAY_DELAY_MS},
+    state::{GlobalState, ota_state, sleep_state},
+    structs::{SolveConfirmPacket, TimerPacketInner},
+    ws::RequestError,
+};
+use alloc::{string::String, vec::Vec};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::Timer;
+use esp_hal_wifimanager::Nvs;
+
+/// Solves that were confirmed by the judge but never acked by the server.
+/// Oldest solve is always first (replayed in order).
+static SOLVE_QUEUE: Mutex<CriticalSectionRawMutex, Vec<TimerPacketInner>> = Mutex::new(Vec::new());
+static REPLAY_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static SOLVE_QUEUE_WRITE_LOGGED: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
+
+pub async fn load_solve_queue(nvs: &Nvs) {
+    let Ok(buf) = nvs.get::<Vec<u8>>(NVS_SOLVE_QUEUE).await else {
+        return;
+    };
+
+    match serde_json::from_slice::<Vec<TimerPacketInner>>(&buf) {
+        Ok(solves) => {
+            log::warn!("Loaded {} queued solve(s)", solves.len());
+            *SOLVE_QUEUE.lock().await = solves;
+        }
+        Err(e) => {
+            log::error!("Solve queue parse failed: {e:?}");
+            _ = nvs.delete(NVS_SOLVE_QUEUE).await;
+        }
+    }
+}
+
+/// Adds solve packet to the end of the queue (replacing solve with the same session_id).
+/// Returns false if solve wasn't queued (queue full), queued solves are never evicted.
+pub async fn push_solve(nvs: &Nvs, solve: TimerPacketInner) -> bool {
+    let Some(session_id) = solve_session_id(&solve) else {
+        log::error!("Tried to queue non-solve packet!");
+        return false;
+    };
+
+    let mut queue = SOLVE_QUEUE.lock().await;
+    queue.retain(|s| solve_session_id(s).as_ref() != Some(&session_id));
+    if queue.len() >= SOLVE_QUEUE_MAX_LEN {
+        log::error!("Solve queue full! Refusing solve: {session_id}");
+        crate::utils::error_log::add_error(crate::utils::error_log::codes::SOLVE_QUEUE_FULL).await;
+        return false;
+    }
+
+    log::warn!("Solve queued: {session_id} ({} in queue)", queue.len() + 1);
+    queue.push(solve);
+    save_solve_queue(nvs, &queue).await;
+    true
+}
+
+pub async fn is_empty() -> bool {
+    SOLVE_QUEUE.lock().await.is_empty()
+}
+
+/// Removes solve with given session_id from the queue (called on every server ack)
+pub async fn ack_solve(nvs: &Nvs, session_id: &str) {
+    let mut queue = SOLVE_QUEUE.lock().await;
+    let old_len = queue.len();
+    queue.retain(|s| solve_session_id(s).as_deref() != Some(session_id));
+
+    if queue.len() != old_len {
+        log::info!("Queued solve acked: {session_id}");
+        save_solve_queue(nvs, &queue).await;
+    }
+}
+
+/// Schedules replay of queued solves (after ws connection is trusted)
+pub fn trigger_replay() {
+    REPLAY_SIGNAL.signal(());
+}
+
+#[embassy_executor::task]
+pub async fn solve_queue_task(global_state: GlobalState) {
+    loop {
+        REPLAY_SIGNAL.wait().await;
+        Timer::after_millis(SOLVE_QUEUE_REPLAY_DELAY_MS).await;
+
+        replay_solve_queue(&global_state).await;
+    }
+}
+
+async fn replay_solve_queue(global_state: &GlobalState) {
+    loop {
+        if ota_state() || sleep_state() || unsafe { !crate::state::TRUST_SERVER } {
+            return;
+        }
+
+        let Some(solve) = SOLVE_QUEUE.lock().await.first().cloned() else {
+            return;
+        };
+
+        let Some(session_id) = solve_session_id(&solve) else {
+            return;
+        };
+
+        log::info!("Replaying queued solve: {session_id}");
+        let resp = crate::ws::send_request::<SolveConfirmPacket>(solve).await;
+        match resp {
+            Ok(resp) => {
+                log::warn!("queued solve_resp: {resp:?}");
+                ack_solve(&global_state.nvs, &resp.session_id).await;
+                ack_solve(&global_state.nvs, &session_id).await;
+            }
+            Err(RequestError::Server(e)) => {
+                log::error!("Queued solve rejected by server: {:?}", e.error);
+                crate::utils::error_log::add_error(
+                    crate::utils::error_log::codes::SOLVE_QUEUE_REJECTED,
+                )
+                .await;
+
+                ack_solve(&global_state.nvs, &session_id).await;
+            }
+            Err(e) => {
+                log::error!("Queued solve replay failed: {e:?}");
+                return;
+            }
+        }
+    }
+}
+
+async fn save_solve_queue(nvs: &Nvs, queue: &[TimerPacketInner]) {
+    _ = nvs.delete(NVS_SOLVE_QUEUE).await;
+    if queue.is_empty() {
+        return;
+    }
+
+    let Ok(vec) = serde_json::to_vec(queue) else {
+        return;
+    };
+
+    let res = nvs.set(NVS_SOLVE_QUEUE, vec.as_slice()).await;
+    if let Err(e) = res {
+        log::error!("{e:?} Faile to write to nvs! (SOLVE_QUEUE {})", vec.len());
+        if !SOLVE_QUEUE_WRITE_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+            crate::utils::error_log::add_error(
+                crate::utils::error_log::codes::NVS_SOLVE_QUEUE_WRITE_FAILED,
+            )
+            .await;
+
+            SOLVE_QUEUE_WRITE_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+fn solve_session_id(packet: &TimerPacketInner) -> Option<String> {
+    match packet {
+        TimerPacketInner::Solve { session_id, .. } => Some(session_id.clone()),
+        _ => None,
+    }
+}
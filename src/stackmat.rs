@@ -1,12 +1,9 @@
 #[cfg(all(feature = "v3", feature = "timer-func"))]
 compile_error!("feature `timer-func` is not supported in v3");
 
-use crate::{
-    consts::{INSPECTION_TIME_DNF, INSPECTION_TIME_PLUS2},
-    state::{GlobalState, Scene},
-};
-use alloc::string::ToString;
+use crate::state::{GlobalState, Scene};
 use embassy_time::{Instant, Timer};
+use fkm_flow::{Command, Event, Stackmat};
 
 pub static mut CURRENT_TIME: u64 = 0;
 
@@ -27,15 +24,7 @@ pub async fn stackmat_task(
             let timer_start = Instant::now();
             global_state.timer_stop_signal.reset();
 
-            let mut state = global_state.state.lock().await;
-            if state.scene <= Scene::Inspection && state.solve_time.is_none() {
-                if state.use_inspection() {
-                    state.inspection_end = Some(Instant::now());
-                }
-
-                state.scene = Scene::Timer;
-                drop(state);
-
+            if time_start(&global_state).await {
                 loop {
                     let time = timer_start.elapsed().as_millis();
 
@@ -76,8 +65,6 @@ pub async fn stackmat_task(
 
                     Timer::after_millis(1000 / 30).await;
                 }
-            } else {
-                drop(state);
             }
         }
 
@@ -138,14 +125,9 @@ pub async fn stackmat_task(
             if last_time.is_none() {
                 let mut state = global_state.state.lock().await;
                 state.stackmat_connected = Some(false);
-
-                if state.scene == Scene::Timer {
-                    if state.current_competitor.is_some() {
-                        state.scene = Scene::CompetitorInfo;
-                    } else {
-                        state.scene = Scene::WaitingForCompetitor;
-                    }
-                }
+                state
+                    .handle_flow_event(Event::Stackmat(Stackmat::Disconnected), &global_state.nvs)
+                    .await;
             }
         }
 
@@ -273,41 +255,18 @@ pub async fn stackmat_task(
 
                 if parsed.0 != last_stackmat_state && parsed.0 != StackmatTimerState::Unknown {
                     if parsed.0 == StackmatTimerState::Running {
-                        let mut state = global_state.state.lock().await;
-                        if state.scene <= Scene::Inspection && state.solve_time.is_none() {
-                            if state.use_inspection() {
-                                state.inspection_end = Some(Instant::now());
-                            }
-
-                            state.scene = Scene::Timer;
+                        if time_start(&global_state).await {
                             global_state.timer_stop_signal.reset();
                         }
                     } else if parsed.0 == StackmatTimerState::Stopped {
                         time_end(parsed.1, false, &mut last_time, &global_state).await;
                     } else if parsed.0 == StackmatTimerState::Reset {
                         let mut state = global_state.state.lock().await;
-                        if state.current_competitor.is_none()
-                            && state.penalty.is_none()
-                            && (state.scene == Scene::Timer
-                                || state.scene == Scene::WaitingForCompetitor)
-                        {
-                            state.scene = Scene::WaitingForCompetitor;
-                            state.solve_time = None;
-                            state.penalty = None;
-                            state.inspection_start = None;
-                            state.inspection_end = None;
-                            last_time = None;
-                        } else if state.current_competitor.is_some() && state.scene == Scene::Timer
-                        {
-                            state.scene = Scene::Finished;
-                            state.solve_time = Some(0);
-                            state.penalty = Some(-1);
-
-                            if state.session_id.is_none() {
-                                state.session_id = Some(uuid::Uuid::new_v4().to_string());
-                            }
+                        let transition = state
+                            .handle_flow_event(Event::Stackmat(Stackmat::Reset), &global_state.nvs)
+                            .await;
 
-                            state.time_confirmed = true;
+                        if transition.commands.contains(&Command::TimerReset) {
                             last_time = None;
                         }
                     }
@@ -329,58 +288,62 @@ pub async fn stackmat_task(
     }
 }
 
+/// Moves flow to timer scene, returns false if solve can't be started now
+async fn time_start(global_state: &GlobalState) -> bool {
+    if crate::settings::attendance_mode() {
+        return false;
+    }
+
+    let mut state = global_state.state.lock().await;
+    let transition = state
+        .handle_flow_event(Event::Stackmat(Stackmat::Started), &global_state.nvs)
+        .await;
+
+    transition.changed && state.flow.scene == Scene::Timer
+}
+
 async fn time_end(
     time: u64,
     dnf: bool,
     last_time: &mut Option<(Instant, u64)>,
     global_state: &GlobalState,
 ) {
+    if crate::settings::attendance_mode() {
+        log::warn!("Timer stopped in attendance mode: {time}ms (ignored)");
+        *last_time = None;
+        return;
+    }
+
+    // stop can be reported past the cap (e.g. between polls), result is capped DNF then
+    let (time, dnf) = match unsafe { crate::state::GROUP_LIMIT } {
+        Some(limit) if time > limit => (limit, true),
+        _ => (time, dnf),
+    };
+
     let mut state = global_state.state.lock().await;
-    let inspection_time = state
-        .inspection_end
-        .zip(state.inspection_start)
-        .map(|(end, start)| (end - start).as_millis())
-        .unwrap_or(0);
+    let inspection_time = state.flow.inspection_time();
 
     log::info!(
         "Timer stopped: {}ms (inspection: {inspection_time}ms)",
         time
     );
-    if state.solve_time.is_none() {
-        state.delegate_used = false;
-        state.solve_time = Some(time);
-        state.penalty = if inspection_time >= INSPECTION_TIME_DNF || dnf {
-            Some(-1)
-        } else if inspection_time >= INSPECTION_TIME_PLUS2 {
-            Some(2)
-        } else {
-            None
-        };
 
-        if state.session_id.is_none() {
-            state.session_id = Some(uuid::Uuid::new_v4().to_string());
-        }
-
-        if state.current_competitor.is_some() {
-            if state.possible_groups.len() > 1 && state.solve_group.is_none() {
-                state.scene = Scene::GroupSelect;
-            } else {
-                state.scene = Scene::Finished;
-            }
-        } else if state.scene >= Scene::WaitingForCompetitor {
-            state.scene = Scene::WaitingForCompetitor;
-        }
+    let transition = state
+        .handle_flow_event(
+            Event::Stackmat(Stackmat::Stopped {
+                time,
+                limit_reached: dnf,
+            }),
+            &global_state.nvs,
+        )
+        .await;
 
-        #[cfg(not(feature = "qa"))]
-        if let Some(saved_state) = state.to_saved_global_state() {
-            saved_state.to_nvs(&global_state.nvs).await;
+    for command in transition.commands {
+        match command {
+            Command::TimerReset => *last_time = None,
+            #[cfg(feature = "qa")]
+            Command::SaveState => crate::qa::send_qa_resp(crate::qa::QaSignal::Stackmat(time)),
+            _ => {}
         }
-
-        #[cfg(feature = "qa")]
-        crate::qa::send_qa_resp(crate::qa::QaSignal::Stackmat(time));
-    } else if state.scene == Scene::Timer {
-        state.scene = Scene::WaitingForCompetitor;
     }
-
-    *last_time = None;
 }
@@ -1,29 +1,48 @@
 use crate::consts::NVS_SAVED_STATE;
 use crate::{
     structs::{BleDisplayDevice, PossibleGroup},
+    translations::{TranslationKey, get_translation},
     utils::error_log::ErrorLogEntry,
     utils::signaled_mutex::SignaledMutex,
 };
-use alloc::{rc::Rc, string::String, vec::Vec};
+use alloc::{rc::Rc, string::String, string::ToString, vec::Vec};
 use embassy_sync::{
     blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex},
     mutex::Mutex,
     signal::Signal,
 };
-use embassy_time::{Duration, Instant, Timer};
+use embassy_time::{Instant, Timer};
 use esp_hal::aes::Aes;
 use esp_hal_wifimanager::Nvs;
+use fkm_flow::{Command, Event, Flow, FlowConfig, Notice, Transition};
 use serde::{Deserialize, Serialize};
 
+pub use fkm_flow::Scene;
+
 pub static mut SIGN_KEY: u32 = 0;
+pub static mut TLS_PIN: Option<[u8; 32]> = None;
+pub static mut SECRET_KEY: Option<[u8; 16]> = None;
+
+/// Current ws connection is `wss://` with verified TLS pin (only then secret can be sent)
+pub static mut PINNED_CONNECTION: bool = false;
+pub static mut TRUST_CHALLENGE: u64 = 0;
 pub static mut TRUST_SERVER: bool = false;
+
+/// Protocol version agreed in HelloResponse (0 until server replies)
+pub static mut SERVER_PROTOCOL_VERSION: u32 = 0;
 pub static mut FKM_TOKEN: i32 = 0;
 pub static mut SECURE_RFID: bool = false;
 pub static mut AUTO_SETUP: bool = false;
 
+/// Time cap of running solve (attempt limit or remaining cumulative limit)
 pub static mut GROUP_LIMIT: Option<u64> = None;
 
-pub static mut EPOCH_BASE: u64 = 0;
+pub static mut RFID_INIT: bool = false;
+pub static mut DISPLAY_FLUSHED: bool = false;
+
+/// Last battery read (level %, voltage mV, average current mA)
+pub static mut BATTERY_STATUS: Option<(u8, f64, Option<i16>)> = None;
+
 pub static mut SLEEP_STATE: bool = false;
 pub static mut DEEPER_SLEEP: bool = false;
 pub static mut OTA_STATE: bool = false;
@@ -45,9 +64,24 @@ pub fn set_buzzer_volume(volume: u8) {
     }
 }
 
+pub fn flow_config() -> FlowConfig {
+    let settings = crate::settings::settings();
+    FlowConfig {
+        inspection_plus2_ms: settings.inspection_plus2_ms,
+        inspection_dnf_ms: settings.inspection_dnf_ms,
+        submit_stops_timer: cfg!(not(feature = "timer-func")),
+    }
+}
+
 #[inline(always)]
 pub fn current_epoch() -> u64 {
-    unsafe { EPOCH_BASE + Instant::now().as_secs() }
+    crate::time_sync::current_epoch_ms() / 1000
+}
+
+/// Server understands packets added in protocol `version` (see `PROTOCOL_VERSION`)
+#[inline(always)]
+pub fn server_supports(version: u32) -> bool {
+    unsafe { SERVER_PROTOCOL_VERSION >= version }
 }
 
 #[inline(always)]
@@ -65,28 +99,6 @@ pub fn ota_state() -> bool {
     unsafe { OTA_STATE }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-#[allow(dead_code)]
-pub enum Scene {
-    Update,
-
-    /// Waiting for wifi connection
-    WifiConnect,
-
-    /// Connect to wifi to setup
-    AutoSetupWait,
-
-    /// Waiting for MDNS
-    MdnsWait,
-
-    WaitingForCompetitor,
-    GroupSelect,
-    CompetitorInfo,
-    Inspection,
-    Timer,
-    Finished,
-}
-
 #[derive(Debug, PartialEq, Clone)]
 pub enum MenuScene {
     Signing,
@@ -105,51 +117,6 @@ pub enum ErrorLogEntryStage {
     Details,
 }
 
-impl Scene {
-    pub fn can_be_lcd_overwritten(&self) -> bool {
-        match self {
-            Scene::Update => false,
-            Scene::WifiConnect => false,
-            Scene::AutoSetupWait => false,
-            Scene::MdnsWait => false,
-            Scene::WaitingForCompetitor => true,
-            Scene::GroupSelect => true,
-            Scene::CompetitorInfo => true,
-            Scene::Inspection => false,
-            Scene::Timer => false,
-            Scene::Finished => false,
-        }
-    }
-
-    pub fn to_index(&self) -> usize {
-        match self {
-            Scene::Update => 0,
-            Scene::WifiConnect => 1,
-            Scene::AutoSetupWait => 2,
-            Scene::MdnsWait => 3,
-            Scene::WaitingForCompetitor => 4,
-            Scene::GroupSelect => 5,
-            Scene::CompetitorInfo => 6,
-            Scene::Inspection => 7,
-            Scene::Timer => 8,
-            Scene::Finished => 9,
-        }
-    }
-
-    pub fn can_sleep(&self) -> bool {
-        !matches!(
-            self,
-            Scene::Update | Scene::WifiConnect | Scene::AutoSetupWait
-        )
-    }
-}
-
-impl PartialOrd for Scene {
-    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        Some(self.to_index().cmp(&other.to_index()))
-    }
-}
-
 #[cfg(feature = "e2e")]
 #[derive(Default)]
 pub struct End2End {
@@ -187,6 +154,8 @@ pub struct GlobalStateInner {
     pub show_battery: Signal<CriticalSectionRawMutex, u8>,
     #[cfg(feature = "v4")]
     pub buzzer_sound_test: Signal<CriticalSectionRawMutex, ()>,
+    #[cfg(feature = "v4")]
+    pub buzzer_inspection_warning: Signal<CriticalSectionRawMutex, fkm_flow::InspectionWarning>,
 
     pub nvs: Nvs,
     pub aes: Mutex<NoopRawMutex, Aes<'static>>,
@@ -208,6 +177,8 @@ impl GlobalStateInner {
             show_battery: Signal::new(),
             #[cfg(feature = "v4")]
             buzzer_sound_test: Signal::new(),
+            #[cfg(feature = "v4")]
+            buzzer_inspection_warning: Signal::new(),
 
             nvs: nvs.clone(),
             aes: Mutex::new(Aes::new(aes)),
@@ -220,22 +191,13 @@ impl GlobalStateInner {
 
 #[derive(Debug, Clone)]
 pub struct SignaledGlobalStateInner {
-    pub scene: Scene,
+    /// Competition flow (scene, solve, penalty, cards), see `fkm_flow`
+    pub flow: Flow<PossibleGroup>,
     pub menu_scene: Option<MenuScene>,
-
-    pub inspection_start: Option<Instant>,
-    pub inspection_end: Option<Instant>,
-    pub solve_time: Option<u64>,
-    pub penalty: Option<i8>,
     pub session_id: Option<String>,
-    pub time_confirmed: bool,
-    pub solve_group: Option<PossibleGroup>,
 
     pub error_text: Option<String>,
 
-    pub possible_groups: Vec<PossibleGroup>,
-    pub group_selected_idx: usize,
-
     pub selected_config_menu: Option<usize>,
     pub error_log_entries: Vec<ErrorLogEntry>,
     pub selected_error_log_item: usize,
@@ -249,14 +211,10 @@ pub struct SignaledGlobalStateInner {
     pub sound_enabled: bool,
     pub device_added: Option<bool>,
     pub server_connected: Option<bool>,
+    pub server_rtt: Option<u64>,
     pub wifi_connected: Option<bool>,
     pub stackmat_connected: Option<bool>,
 
-    pub current_competitor: Option<u64>,
-    pub current_judge: Option<u64>,
-    pub competitor_display: Option<String>,
-
-    pub delegate_used: bool,
     pub delegate_hold: Option<u8>,
 
     #[cfg(feature = "v4")]
@@ -285,20 +243,11 @@ pub struct SavedGlobalState {
 impl SignaledGlobalStateInner {
     pub fn new() -> Self {
         Self {
-            scene: Scene::WifiConnect,
+            flow: Flow::new(Scene::WifiConnect),
             menu_scene: None,
-
-            inspection_start: None,
-            inspection_end: None,
-            solve_time: None,
-            penalty: None,
             session_id: None,
-            time_confirmed: false,
-            solve_group: None,
 
             error_text: None,
-            possible_groups: Vec::new(),
-            group_selected_idx: 0,
             selected_config_menu: None,
             error_log_entries: Vec::new(),
             selected_error_log_item: 0,
@@ -311,13 +260,10 @@ impl SignaledGlobalStateInner {
             sound_enabled: true,
             device_added: None,
             server_connected: None,
+            server_rtt: None,
             wifi_connected: None,
             stackmat_connected: None,
-            current_competitor: None,
-            current_judge: None,
-            competitor_display: None,
 
-            delegate_used: false,
             delegate_hold: None,
 
             #[cfg(feature = "v4")]
@@ -338,43 +284,42 @@ impl SignaledGlobalStateInner {
             return true;
         }
 
-        if self.scene.can_be_lcd_overwritten() {
+        if self.flow.scene.can_be_lcd_overwritten() {
             if self.server_connected == Some(false) {
                 return true;
             }
 
-            if self.stackmat_connected == Some(false) {
+            if self.stackmat_connected == Some(false) && !crate::settings::attendance_mode() {
                 return true;
             }
         }
 
-        if self.scene <= Scene::MdnsWait {
+        if self.flow.scene <= Scene::MdnsWait {
             return true;
         }
 
         false
     }
 
+    /// Server rtt shown in topbar (only when connection is slow)
+    #[cfg(feature = "v4")]
+    pub fn slow_server_rtt(&self) -> Option<u64> {
+        self.server_rtt.filter(|rtt| {
+            *rtt >= crate::consts::WS_RTT_SLOW_MS && self.server_connected == Some(true)
+        })
+    }
+
     pub async fn reset_solve_state(&mut self, save_nvs: Option<&Nvs>) {
+        self.flow.reset();
+        self.clear_solve_session(save_nvs).await;
+    }
+
+    /// Clears solve related state that isn't part of competition flow
+    async fn clear_solve_session(&mut self, save_nvs: Option<&Nvs>) {
         unsafe {
             GROUP_LIMIT = None;
         }
-        self.solve_time = None;
-        self.penalty = None;
-        self.inspection_start = None;
-        self.inspection_end = None;
-        self.current_competitor = None;
-        self.current_judge = None;
-        self.competitor_display = None;
         self.session_id = None;
-        self.time_confirmed = false;
-        self.scene = Scene::WaitingForCompetitor;
-        self.delegate_used = false;
-        self.inspection_start = None;
-        self.inspection_end = None;
-        self.solve_group = None;
-        self.possible_groups.clear();
-        self.group_selected_idx = 0;
 
         if let Some(nvs) = save_nvs {
             SavedGlobalState::clear_saved_global_state(nvs).await;
@@ -383,26 +328,66 @@ impl SignaledGlobalStateInner {
         crate::translations::restore_default_locale();
     }
 
+    /// Runs event through competition flow and executes its generic side effects.
+    /// Commands that depend on caller (solve submit, delegate call, timer) are left to it.
+    pub async fn handle_flow_event(
+        &mut self,
+        event: Event<PossibleGroup>,
+        nvs: &Nvs,
+    ) -> Transition<PossibleGroup> {
+        let transition = self
+            .flow
+            .handle(event, Instant::now().as_millis(), &flow_config());
+
+        for command in &transition.commands {
+            match command {
+                Command::SetGroupLimit(limit) => unsafe {
+                    GROUP_LIMIT = *limit;
+                },
+                Command::SaveState => {
+                    if self.session_id.is_none() {
+                        self.session_id = Some(uuid::Uuid::new_v4().to_string());
+                    }
+
+                    #[cfg(not(feature = "qa"))]
+                    if let Some(saved_state) = self.to_saved_global_state() {
+                        saved_state.to_nvs(nvs).await;
+                    }
+                }
+                Command::ResetSolve { clear_saved } => {
+                    self.clear_solve_session(clear_saved.then_some(nvs)).await;
+                }
+                Command::Notify(Notice::EmptyGroups) => {
+                    self.error_text = Some(get_translation(TranslationKey::EMPTY_GROUPS_ERROR));
+                }
+                Command::Notify(Notice::SolveGroupMissing) => {
+                    log::error!("Solve group is none! (How would that happen?)");
+                    static LOGGED: core::sync::atomic::AtomicBool =
+                        core::sync::atomic::AtomicBool::new(false);
+                    if !LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+                        crate::utils::error_log::add_error(
+                            crate::utils::error_log::codes::RFID_SOLVE_GROUP_MISSING,
+                        )
+                        .await;
+
+                        LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        transition
+    }
+
     #[allow(dead_code)]
     pub async fn hard_state_reset(&mut self) {
         unsafe {
             GROUP_LIMIT = None;
         }
-        self.scene = Scene::WaitingForCompetitor;
-        self.inspection_start = None;
-        self.inspection_end = None;
-        self.solve_time = None;
-        self.penalty = None;
+        self.flow.reset();
         self.session_id = None;
-        self.time_confirmed = false;
-        self.solve_group = None;
         self.error_text = None;
-        self.possible_groups.clear();
-        self.group_selected_idx = 0;
-        self.current_competitor = None;
-        self.current_judge = None;
-        self.competitor_display = None;
-        self.delegate_used = false;
         self.delegate_hold = None;
         self.custom_message = None;
     }
@@ -412,15 +397,18 @@ impl SignaledGlobalStateInner {
 
         Some(SavedGlobalState {
             session_id: self.session_id.clone()?,
-            current_competitor: self.current_competitor?,
-            penalty: self.penalty.unwrap_or(0),
-            solve_time: self.solve_time?,
-            inspection_time: self.inspection_end.map(|e| {
-                (e.saturating_duration_since(self.inspection_start.unwrap_or(Instant::now())))
-                    .as_millis()
+            current_competitor: self.flow.current_competitor?,
+            penalty: self.flow.penalty.unwrap_or(0),
+            solve_time: self.flow.solve_time?,
+            inspection_time: self.flow.inspection_end.map(|e| {
+                e.saturating_sub(
+                    self.flow
+                        .inspection_start
+                        .unwrap_or(Instant::now().as_millis()),
+                )
             }),
             solve_epoch: current_epoch(),
-            solve_group: self.solve_group.clone()?,
+            solve_group: self.flow.solve_group.clone()?,
         })
     }
 
@@ -428,46 +416,40 @@ impl SignaledGlobalStateInner {
         log::warn!("Parsed saved state: {saved:?}");
 
         self.session_id = Some(saved.session_id);
-        self.penalty = Some(saved.penalty);
-        self.solve_time = Some(saved.solve_time);
-        self.current_competitor = Some(saved.current_competitor);
-        self.solve_group = Some(saved.solve_group);
+        self.flow.penalty = Some(saved.penalty);
+        self.flow.solve_time = Some(saved.solve_time);
+        self.flow.current_competitor = Some(saved.current_competitor);
+        self.flow.solve_group = Some(saved.solve_group);
 
         if let Some(inspection_time) = saved.inspection_time {
-            let now = Instant::now();
-            self.inspection_end = now.checked_add(Duration::from_millis(inspection_time));
-            self.inspection_start = Some(now);
+            let now = Instant::now().as_millis();
+            self.flow.inspection_end = now.checked_add(inspection_time);
+            self.flow.inspection_start = Some(now);
         }
 
         if saved.solve_time > 0 {
-            self.scene = Scene::Finished;
-        }
-    }
-
-    pub fn use_inspection(&self) -> bool {
-        match self.solve_group.as_ref().map(|r| r.use_inspection) {
-            Some(true) | None => true,
-            Some(false) => false,
+            self.flow.scene = Scene::Finished;
         }
     }
 
     #[cfg(feature = "e2e")]
     pub fn snapshot_data(&self) -> crate::structs::SnapshotData {
-        let inspection_time = self
+        let flow = &self.flow;
+        let inspection_time = flow
             .inspection_end
-            .zip(self.inspection_start)
-            .map(|(end, start)| (end - start).as_millis());
+            .zip(flow.inspection_start)
+            .map(|(end, start)| end - start);
 
         crate::structs::SnapshotData {
-            scene: self.scene.to_index(),
+            scene: flow.scene.to_index(),
             inspection_time,
-            penalty: self.penalty,
-            solve_time: self.solve_time,
-            current_judge: self.current_judge,
-            current_competitor: self.current_competitor,
-            group_selected_idx: self.group_selected_idx,
-            time_confirmed: self.time_confirmed,
-            possible_groups: self.possible_groups.len(),
+            penalty: flow.penalty,
+            solve_time: flow.solve_time,
+            current_judge: flow.current_judge,
+            current_competitor: flow.current_competitor,
+            group_selected_idx: flow.group_selected_idx,
+            time_confirmed: flow.time_confirmed,
+            possible_groups: flow.possible_groups.len(),
         }
     }
 }
@@ -475,7 +457,7 @@ impl SignaledGlobalStateInner {
 #[cfg(not(feature = "e2e"))]
 impl SavedGlobalState {
     pub async fn from_nvs(nvs: &Nvs) -> Option<Self> {
-        while unsafe { EPOCH_BASE == 0 } {
+        while !crate::time_sync::is_synced() {
             Timer::after_millis(5).await;
         }
 
@@ -545,18 +527,10 @@ impl SavedGlobalState {
 
 impl PartialEq for SignaledGlobalStateInner {
     fn eq(&self, other: &Self) -> bool {
-        let result = self.scene == other.scene
+        let result = self.flow == other.flow
             && self.menu_scene == other.menu_scene
-            && self.inspection_start == other.inspection_start
-            && self.inspection_end == other.inspection_end
-            && self.solve_time == other.solve_time
-            && self.penalty == other.penalty
             && self.session_id == other.session_id
-            && self.time_confirmed == other.time_confirmed
-            && self.solve_group == other.solve_group
             && self.error_text == other.error_text
-            && self.possible_groups == other.possible_groups
-            && self.group_selected_idx == other.group_selected_idx
             && self.selected_config_menu == other.selected_config_menu
             && self.error_log_entries == other.error_log_entries
             && self.selected_error_log_item == other.selected_error_log_item
@@ -569,14 +543,14 @@ impl PartialEq for SignaledGlobalStateInner {
             && self.server_connected == other.server_connected
             && self.wifi_connected == other.wifi_connected
             && self.stackmat_connected == other.stackmat_connected
-            && self.current_competitor == other.current_competitor
-            && self.current_judge == other.current_judge
-            && self.competitor_display == other.competitor_display
-            && self.delegate_used == other.delegate_used
             && self.delegate_hold == other.delegate_hold
             // battery_status intentionally excluded (v4 hw)
             && self.custom_message == other.custom_message;
 
+        // server_rtt changes on every heartbeat, redraw only when it's shown
+        #[cfg(feature = "v4")]
+        let result = result && self.slow_server_rtt() == other.slow_server_rtt();
+
         #[cfg(feature = "bat_dev_lcd")]
         let result = result
             && self.current_bat_read == other.current_bat_read
@@ -2,24 +2,28 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+
+pub use fkm_common::packets::*;
 
 #[cfg(feature = "v3")]
-pub const CONFIG_MENU_ITEMS: [&str; 6] = [
+pub const CONFIG_MENU_ITEMS: [&str; 7] = [
     "Reset Settings",
     "BT Display",
     "Sign Cards",
     "Un-Sign Cards",
+    "Attendance Mode",
     "Error Log",
     "Exit",
 ];
 #[cfg(feature = "v4")]
-pub const CONFIG_MENU_ITEMS: [&str; 7] = [
+pub const CONFIG_MENU_ITEMS: [&str; 8] = [
     "Reset Settings",
     "BT Display",
     "Sign Cards",
     "Un-Sign Cards",
     "Buzzer Volume",
+    "Attendance Mode",
     "Error Log",
     "Exit",
 ];
@@ -34,6 +38,15 @@ pub struct BleDisplayDevice {
 pub struct ConnSettings {
     pub mdns: bool,
     pub ws_url: Option<String>,
+
+    /// Backup server urls (tried in order after `ws_url`)
+    pub ws_urls: Option<Vec<String>>,
+
+    /// SHA-256 fingerprint (hex) of server certificate or its public key (SPKI)
+    pub tls_pin: Option<String>,
+
+    /// Hex encoded device secret provisioned out of band (instead of in `Add` packet)
+    pub secret_key: Option<String>,
 }
 
 impl Default for ConnSettings {
@@ -41,171 +54,48 @@ impl Default for ConnSettings {
         Self {
             mdns: true,
             ws_url: None,
+            ws_urls: None,
+            tls_pin: None,
+            secret_key: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TimerPacket {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tag: Option<u64>,
-    pub data: TimerPacketInner,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(rename_all = "snake_case")]
-pub enum TimerPacketInner {
-    StartUpdate {
-        version: String,
-        build_time: u64, // NOT USED
-        size: u32,
-        crc: u32,
-        firmware: String,
-    },
-    Solve {
-        solve_time: u64,
-        penalty: i64,
-        competitor_id: u64,
-        judge_id: u64,
-        timestamp: u64,
-        session_id: String, // UUID
-        delegate: bool,
-        inspection_time: i64,
-        group_id: String,
-        sign_key: u32,
-    },
-    SolveConfirm(SolveConfirmPacket),
-    DelegateResponse(DelegateResponsePacket),
-    ApiError(ApiError),
-    CustomMessage {
-        line1: String,
-        line2: String,
-    },
-
-    CardInfoRequest {
-        card_id: u64,
-        is_competitor: bool,
-
-        #[serde(skip_serializing_if = "Option::is_none")]
-        attendance_device: Option<bool>,
-
-        sign_key: u32,
-    },
-    CardInfoResponse(CardInfoResponsePacket),
-    AttendanceMarked,
-    DeviceSettings {
-        added: bool,
-        locales: Vec<TranslationLocale>,
-        default_locale: String,
-        fkm_token: i32,
-        secure_rfid: bool,
-        auto_setup: bool,
-        sound_enabled: bool,
-    },
-    Battery {
-        level: Option<f64>,
-        voltage: Option<f64>,
-    },
-    Add {
-        firmware: String,
-        sign_key: u32,
-    },
-    EpochTime {
-        current_epoch: u64,
-    },
-    SetDeviceSettings {
-        volume: Option<u8>,
-    },
-    DumpCrashLog,
-
-    // packet for end to end testing
+/// Names of `TimerPacketInner` variants this firmware understands (sent in Hello packet)
+pub const SUPPORTED_PACKETS: &[&str] = &[
+    "start_update",
+    "ota_resume",
+    "solve",
+    "solve_confirm",
+    "delegate_response",
+    "api_error",
+    "custom_message",
+    "card_info_request",
+    "card_info_response",
+    "attendance_marked",
+    "device_settings",
+    "battery",
+    "add",
+    "epoch_time",
+    "time_sync_request",
+    "time_sync_response",
+    "set_device_settings",
+    "dump_crash_log",
+    "hello",
+    "hello_response",
+    "server_urls",
+    "status_request",
+    "set_settings",
+    "effective_settings",
+    "set_log_config",
+    "remote_command",
+    "remote_command_ack",
+    "status_response",
     #[cfg(feature = "e2e")]
-    TestPacket(TestPacketData),
-
+    "test_packet",
     #[cfg(feature = "e2e")]
-    TestAck(SnapshotData),
-}
-
-#[cfg(feature = "e2e")]
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", content = "data")]
-pub enum TestPacketData {
-    HardStateReset,
-    ResetState,
-    ScanCard(u64),
-    ButtonPress { pin: u8, press_time: u64 },
-    StackmatTime(u64),
-    StackmatReset,
-}
-
-#[cfg(feature = "e2e")]
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct SnapshotData {
-    pub scene: usize,
-    pub inspection_time: Option<u64>,
-    pub solve_time: Option<u64>,
-    pub penalty: Option<i8>,
-    pub time_confirmed: bool,
-    pub possible_groups: usize,
-    pub group_selected_idx: usize,
-    pub current_competitor: Option<u64>,
-    pub current_judge: Option<u64>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TranslationLocale {
-    pub locale: String,
-    pub translations: Vec<TranslationRecord>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TranslationRecord {
-    pub key: String,
-    pub translation: String,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct CardInfoResponsePacket {
-    pub card_id: u64,
-    pub display: String,
-    pub country_iso2: String,
-    pub can_compete: bool,
-    pub possible_groups: Vec<PossibleGroup>,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-#[serde(rename_all = "camelCase")]
-pub struct PossibleGroup {
-    pub group_id: String,
-    pub name: String,
-    pub secondary_text: Option<String>,
-    pub use_inspection: bool,
-    pub limit: Option<u64>,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct SolveConfirmPacket {
-    pub competitor_id: u64,
-    pub session_id: String,
-    pub message: String,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct DelegateResponsePacket {
-    pub should_scan_cards: bool,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub solve_time: Option<u64>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub penalty: Option<i64>,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct ApiError {
-    pub error: String,
-    pub should_reset_time: bool,
-}
+    "test_ack",
+];
 
 pub trait FromPacket: Sized {
     fn from_packet(packet: TimerPacket) -> Result<Self, ApiError>;
@@ -226,6 +116,31 @@ impl FromPacket for CardInfoResponsePacket {
     }
 }
 
+/// Response to attendance `CardInfoRequest` (server can reply with plain `AttendanceMarked`
+/// or with card info, then name of card owner is shown)
+#[derive(Clone, Debug)]
+pub struct AttendanceResponse {
+    pub display: Option<String>,
+}
+
+impl FromPacket for AttendanceResponse {
+    fn from_packet(packet: TimerPacket) -> Result<Self, ApiError> {
+        match packet.data {
+            TimerPacketInner::AttendanceMarked => Ok(AttendanceResponse { display: None }),
+            TimerPacketInner::CardInfoResponse(card_info_response_packet) => {
+                Ok(AttendanceResponse {
+                    display: Some(card_info_response_packet.display),
+                })
+            }
+            TimerPacketInner::ApiError(api_error) => Err(api_error),
+            _ => Err(ApiError {
+                error: "Wrong response type!".to_string(),
+                should_reset_time: false,
+            }),
+        }
+    }
+}
+
 impl FromPacket for SolveConfirmPacket {
     fn from_packet(packet: TimerPacket) -> Result<Self, ApiError> {
         match packet.data {
@@ -0,0 +1,133 @@
+use crate::{
+    consts::{
+        TIME_SLEW_RATE_DIV, TIME_STEP_THRESHOLD_MS, TIME_SYNC_INTERVAL_MS, TIME_SYNC_MAX_RTT_MS,
+    },
+    state::{GlobalState, sleep_state},
+    structs::{TimerPacket, TimerPacketInner},
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant, WithTimeout};
+
+/// Applied offset (epoch ms - uptime ms)
+static mut OFFSET_MS: i64 = 0;
+
+/// Offset estimated from last sync (applied offset is slewed towards it)
+static mut TARGET_OFFSET_MS: i64 = 0;
+
+/// Uptime (ms) of last slew step
+static mut LAST_SLEW_MS: u64 = 0;
+
+static mut SYNCED: bool = false;
+
+/// True after first millisecond sync (seconds from `EpochTime` are ignored then)
+static mut MS_SYNCED: bool = false;
+
+static SYNC_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Milliseconds since unix epoch (uptime if device wasn't synced yet)
+pub fn current_epoch_ms() -> u64 {
+    let now = Instant::now().as_millis();
+    (now as i64 + slew(now)) as u64
+}
+
+#[inline(always)]
+pub fn is_synced() -> bool {
+    unsafe { SYNCED }
+}
+
+/// Epoch (ms) at device boot, 0 if not synced
+pub fn boot_epoch_ms() -> u64 {
+    if !is_synced() {
+        return 0;
+    }
+
+    slew(Instant::now().as_millis()).max(0) as u64
+}
+
+/// Moves applied offset towards target by at most 1ms per `TIME_SLEW_RATE_DIV` ms
+/// of uptime, so time never jumps (or goes backwards) after resync
+fn slew(now: u64) -> i64 {
+    unsafe {
+        let steps = now.saturating_sub(LAST_SLEW_MS) / TIME_SLEW_RATE_DIV;
+        if steps == 0 {
+            return OFFSET_MS;
+        }
+
+        LAST_SLEW_MS += steps * TIME_SLEW_RATE_DIV;
+        let diff = TARGET_OFFSET_MS - OFFSET_MS;
+        OFFSET_MS += diff.clamp(-(steps as i64), steps as i64);
+        OFFSET_MS
+    }
+}
+
+fn set_offset(offset: i64, now: u64) {
+    let current = slew(now);
+    unsafe {
+        if !SYNCED || (offset - current).abs() > TIME_STEP_THRESHOLD_MS {
+            log::info!("[time] Step offset: {current} -> {offset}");
+            OFFSET_MS = offset;
+            LAST_SLEW_MS = now;
+        }
+
+        TARGET_OFFSET_MS = offset;
+        SYNCED = true;
+    }
+}
+
+/// Legacy (seconds precision) epoch pushed by server
+pub fn on_epoch_time(current_epoch: u64) {
+    if unsafe { MS_SYNCED } {
+        return;
+    }
+
+    let now = Instant::now().as_millis();
+    set_offset((current_epoch * 1000) as i64 - now as i64, now);
+}
+
+/// Response to `TimeSyncRequest`. Server time is assumed to be read in the middle of rtt.
+pub fn on_time_sync_response(uptime_ms: u64, epoch_ms: u64) {
+    let now = Instant::now().as_millis();
+    let Some(rtt) = now.checked_sub(uptime_ms) else {
+        return;
+    };
+
+    if rtt > TIME_SYNC_MAX_RTT_MS {
+        log::warn!("[time] Sync rtt too high: {rtt}ms");
+        return;
+    }
+
+    let offset = (epoch_ms + rtt / 2) as i64 - now as i64;
+    log::debug!("[time] Sync rtt: {rtt}ms offset: {offset}");
+    set_offset(offset, now);
+    unsafe { MS_SYNCED = true };
+}
+
+/// Sends sync request as soon as possible (called after ws connects)
+pub fn request_sync() {
+    SYNC_SIGNAL.signal(());
+}
+
+#[embassy_executor::task]
+pub async fn time_sync_task(global_state: GlobalState) {
+    loop {
+        _ = SYNC_SIGNAL
+            .wait()
+            .with_timeout(Duration::from_millis(TIME_SYNC_INTERVAL_MS))
+            .await;
+
+        if sleep_state()
+            || global_state.state.value().await.server_connected != Some(true)
+            || !crate::state::server_supports(crate::consts::PROTOCOL_TIME_SYNC)
+        {
+            continue;
+        }
+
+        crate::ws::send_packet(TimerPacket {
+            tag: None,
+            data: TimerPacketInner::TimeSyncRequest {
+                uptime_ms: Instant::now().as_millis(),
+            },
+        })
+        .await;
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{consts::NVS_ERROR_LOG, state::current_epoch};
+use crate::{consts::NVS_ERROR_LOG, time_sync::current_epoch_ms};
 use alloc::{
     format,
     string::{String, ToString},
@@ -58,6 +58,12 @@ pub mod codes {
     pub const OTA_MARK_VALID_FAILED: u8 = 41;
     pub const OTA_VERIFY_FAILED: u8 = 42;
     pub const WS_CONNECTION_LOST_DURING_OTA: u8 = 43;
+    pub const OTA_RESUME_TIMEOUT: u8 = 44;
+    pub const OTA_SIGNATURE_INVALID: u8 = 45;
+    pub const OTA_HEALTH_CHECK_FAILED: u8 = 46;
+    pub const OTA_DECOMPRESS_FAILED: u8 = 47;
+    pub const OTA_METADATA_INVALID: u8 = 48;
+    pub const OTA_DOWNGRADE_REJECTED: u8 = 49;
 
     // BLE (50-59)
     pub const BLE_INIT_FAILED: u8 = 50;
@@ -79,6 +85,9 @@ pub mod codes {
     pub const WS_PACKET_PARSE_FAILED: u8 = 64;
     pub const WS_PACKET_SERIALIZE_FAILED: u8 = 65;
     pub const WS_TAGGED_SUBSCRIBER_FAILED: u8 = 66;
+    pub const WS_TLS_PIN_MISMATCH: u8 = 67;
+    pub const WS_TLS_PIN_INVALID: u8 = 68;
+    pub const WS_HEARTBEAT_TIMEOUT: u8 = 69;
 
     // NVS persistence (70-79)
     pub const NVS_SAVED_STATE_WRITE_FAILED: u8 = 70;
@@ -87,6 +96,11 @@ pub mod codes {
     pub const NVS_BUZZER_VOLUME_WRITE_FAILED: u8 = 72;
     pub const ERROR_LOG_PARSE_FAILED: u8 = 73;
     pub const NVS_SAVED_STATE_DELETE_FAILED: u8 = 74;
+    pub const NVS_SOLVE_QUEUE_WRITE_FAILED: u8 = 75;
+    pub const SOLVE_QUEUE_FULL: u8 = 76;
+    pub const SOLVE_QUEUE_REJECTED: u8 = 77;
+    pub const NVS_SETTINGS_WRITE_FAILED: u8 = 78;
+    pub const SECRET_KEY_INVALID: u8 = 79;
 
     // Tasks / runtime (80-89)
     pub const TASK_SPAWN_FAILED: u8 = 80;
@@ -96,6 +110,9 @@ pub mod codes {
     #[cfg(feature = "release_build")]
     pub const DOUBLE_PANIC_RECOVERY: u8 = 90;
     pub const BACKTRACE_READ_FAILED: u8 = 91;
+
+    // Firmware / OTA, continued (100-109)
+    pub const OTA_FLASH_FAILED: u8 = 100;
 }
 
 pub async fn add_error(code: u8) {
@@ -103,8 +120,9 @@ pub async fn add_error(code: u8) {
         #[allow(static_mut_refs)]
         let error_log_buf = &mut (*ERROR_LOG_BUF.as_mut_ptr());
 
-        error_log_buf[OFFSET] = b'N';
-        error_log_buf[OFFSET + 1..OFFSET + 1 + 8].copy_from_slice(&current_epoch().to_be_bytes());
+        error_log_buf[OFFSET] = b'M';
+        error_log_buf[OFFSET + 1..OFFSET + 1 + 8]
+            .copy_from_slice(&current_epoch_ms().to_be_bytes());
         error_log_buf[OFFSET + 1 + 8] = code;
 
         OFFSET += 1 + 8 + 1;
@@ -138,11 +156,41 @@ pub async fn add_stacktrace(addrs: &[u32], version: &str, timestamp: u64) {
     }
 }
 
-pub fn dump_error_log() -> &'static [u8] {
-    #[allow(static_mut_refs)]
+pub async fn clear_error_log(nvs: &Nvs) {
     unsafe {
-        &(&*ERROR_LOG_BUF.as_ptr())[..OFFSET]
+        OFFSET = 0;
+        SAVE_READY = false;
+    }
+
+    _ = nvs.delete(NVS_ERROR_LOG).await;
+}
+
+/// Raw error log for `DumpCrashLog`. Servers older than `PROTOCOL_TIME_SYNC` don't know
+/// 'M' (ms timestamp) records, so these are sent to them as 'N' (secs) records.
+pub fn dump_error_log(ms_records: bool) -> Vec<u8> {
+    #[allow(static_mut_refs)]
+    let mut dump = unsafe { (&*ERROR_LOG_BUF.as_ptr())[..OFFSET].to_vec() };
+    if ms_records {
+        return dump;
+    }
+
+    let mut offset = 0;
+    while offset < dump.len() {
+        let Some(len) = entry_len(&dump, offset).filter(|len| offset + len <= dump.len()) else {
+            break;
+        };
+
+        if dump[offset] == b'M' {
+            let timestamp = offset + 1..offset + 1 + 8;
+            let ms = u64::from_be_bytes(dump[timestamp.clone()].try_into().unwrap_or_default());
+            dump[timestamp].copy_from_slice(&(ms / 1000).to_be_bytes());
+            dump[offset] = b'N';
+        }
+
+        offset += len;
     }
+
+    dump
 }
 
 pub async fn load_error_log(nvs: &Nvs) {
@@ -159,21 +207,11 @@ pub async fn load_error_log(nvs: &Nvs) {
 
     let mut offset = 0;
     while offset < loaded_len {
-        let log_type = error_log_buf[offset];
-        match log_type {
-            b'N' => {
-                // u64 + u8
-                offset += 1 + 8 + 1;
-            }
-            b'S' => {
-                // u64 + 16 * u8 + u8 (size) + size * u32
-                let size = error_log_buf[offset + 1 + 8 + 16];
-                offset += 1 + 8 + 16 + 1 + size as usize * 4;
-            }
-            _ => {
-                break;
-            }
-        }
+        let Some(len) = entry_len(&error_log_buf[..loaded_len], offset) else {
+            break;
+        };
+
+        offset += len;
     }
 
     unsafe {
@@ -181,6 +219,40 @@ pub async fn load_error_log(nvs: &Nvs) {
     }
 }
 
+/// Size of entry starting at `offset` (None if entry type is unknown)
+fn entry_len(buf: &[u8], offset: usize) -> Option<usize> {
+    match buf[offset] {
+        // u64 + u8
+        b'N' | b'M' => Some(1 + 8 + 1),
+        // u64 + 16 * u8 + u8 (size) + size * u32
+        b'S' => {
+            let size = *buf.get(offset + 1 + 8 + 16)?;
+            Some(1 + 8 + 16 + 1 + size as usize * 4)
+        }
+        _ => None,
+    }
+}
+
+/// Number of entries in error log (without parsing and logging them)
+pub fn error_log_count() -> usize {
+    #[allow(static_mut_refs)]
+    let error_log_buf = unsafe { &*ERROR_LOG_BUF.as_ptr() };
+    let max_offset = unsafe { OFFSET };
+
+    let mut count = 0;
+    let mut offset = 0;
+    while offset < max_offset {
+        let Some(len) = entry_len(&error_log_buf[..max_offset], offset) else {
+            break;
+        };
+
+        offset += len;
+        count += 1;
+    }
+
+    count
+}
+
 pub async fn save_error_log(nvs: &Nvs) {
     #[allow(static_mut_refs)]
     let error_log_buf = unsafe { &mut (*ERROR_LOG_BUF.as_mut_ptr()) };
@@ -206,12 +278,16 @@ pub fn parse_error_log_entries() -> Result<Vec<ErrorLogEntry>> {
     while offset < max_offset {
         let log_type = error_log_buf[offset];
         match log_type {
-            b'N' => {
-                // u64 + u8
+            b'N' | b'M' => {
+                // u64 (secs for 'N', ms for 'M') + u8
+                let mut timestamp =
+                    u64::from_be_bytes(error_log_buf[offset + 1..offset + 1 + 8].try_into()?);
+                if log_type == b'M' {
+                    timestamp /= 1000;
+                }
+
                 let entry = ErrorLogEntry::Code {
-                    timestamp: u64::from_be_bytes(
-                        error_log_buf[offset + 1..offset + 1 + 8].try_into()?,
-                    ),
+                    timestamp,
                     code: error_log_buf[offset + 1 + 8],
                 };
 
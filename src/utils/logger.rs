@@ -1,5 +1,38 @@
-use crate::state::{ota_state, sleep_state};
-use alloc::vec::Vec;
+use crate::{
+    consts::{LOG_TARGET_OVERRIDES_MAX, NVS_LOG_CONFIG, PROTOCOL_STRUCTURED_LOGS},
+    state::{ota_state, sleep_state},
+    structs::LogConfig,
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::str::FromStr;
+use esp_hal_wifimanager::Nvs;
+
+/// Log frame layout (all integers big endian):
+/// - header: `'L'`, version, flags (bit 0 = truncated), stackmat time (u64),
+///   boot epoch in ms (u64, 0 if unknown)
+/// - records: length (u16, rest of record), sequence (u32), uptime ms (u64),
+///   level (u8, 1 = error .. 5 = trace), target length (u8), target, message
+///
+/// Sequence is incremented for every emitted log line (also the ones that didn't fit
+/// into buffer), so gaps in sequence mean lost lines.
+///
+/// Servers older than `PROTOCOL_STRUCTURED_LOGS` get legacy frames instead:
+/// - header: `'L'`, truncated flag (`0`/`1`), stackmat time (u64)
+/// - records: length (u16), level letter + space (`"I "`), message
+pub const LOG_FRAME_VERSION: u8 = 2;
+const LOG_FRAME_HEADER_LEN: usize = 19;
+const LOG_LEGACY_FRAME_HEADER_LEN: usize = 10;
+/// Length (u16) + sequence (u32) + uptime (u64) + level (u8) + target length (u8)
+const LOG_RECORD_HEADER_LEN: usize = 16;
+/// Length (u16) + level letter + space
+const LOG_LEGACY_RECORD_HEADER_LEN: usize = 4;
+const LOG_FLAG_TRUNCATED: u8 = 0x01;
+
+/// Sequence number of next log record
+static mut LOG_SEQ: u32 = 0;
 
 #[unsafe(link_section = ".dram2_uninit")]
 static mut LOGS_BUF: core::mem::MaybeUninit<[u8; 8 * 1024]> = core::mem::MaybeUninit::uninit();
@@ -7,17 +40,32 @@ static mut LOGS_BUF: core::mem::MaybeUninit<[u8; 8 * 1024]> = core::mem::MaybeUn
 pub struct LogsBufWriter {
     buf: &'static mut [u8],
     pos: usize,
+
+    /// Layout of current frame (chosen when frame is started)
+    structured: bool,
 }
 
 #[allow(static_mut_refs)]
 pub static mut LOGS_WRITER: LogsBufWriter = LogsBufWriter {
     buf: unsafe { &mut *LOGS_BUF.as_mut_ptr() },
     pos: 0,
+    structured: false,
 };
 
 impl LogsBufWriter {
     pub fn get_vec(&mut self, current_time: Option<u64>) -> Vec<u8> {
-        if let Some(current_time) = current_time {
+        if self.pos == 0 {
+            return Vec::new();
+        }
+
+        if self.structured {
+            if let Some(current_time) = current_time {
+                self.buf[3..11].copy_from_slice(&current_time.to_be_bytes());
+            }
+
+            let boot_epoch_ms = crate::time_sync::boot_epoch_ms();
+            self.buf[11..19].copy_from_slice(&boot_epoch_ms.to_be_bytes());
+        } else if let Some(current_time) = current_time {
             self.buf[2..10].copy_from_slice(&current_time.to_be_bytes());
         }
 
@@ -28,7 +76,78 @@ impl LogsBufWriter {
     }
 
     fn mark_truncated(&mut self) {
-        self.buf[1] = 0x01;
+        if self.structured {
+            self.buf[2] |= LOG_FLAG_TRUNCATED;
+        } else {
+            self.buf[1] = 0x01;
+        }
+    }
+
+    fn write_record(&mut self, seq: u32, record: &log::Record) {
+        if self.pos == 0 {
+            self.structured = crate::state::server_supports(PROTOCOL_STRUCTURED_LOGS);
+            self.buf[0] = b'L';
+            if self.structured {
+                self.buf[1] = LOG_FRAME_VERSION;
+                self.buf[2] = 0x00;
+                self.pos = LOG_FRAME_HEADER_LEN;
+            } else {
+                self.buf[1] = 0x00;
+                self.pos = LOG_LEGACY_FRAME_HEADER_LEN;
+            }
+        }
+
+        if !self.structured {
+            self.write_legacy_record(record);
+            return;
+        }
+
+        let target = record.target().as_bytes();
+        let target = &target[..target.len().min(u8::MAX as usize)];
+        if self.pos + LOG_RECORD_HEADER_LEN + target.len() > self.buf.len() {
+            self.mark_truncated();
+            return;
+        }
+
+        let record_start = self.pos;
+        let uptime_ms = esp_hal::time::Instant::now()
+            .duration_since_epoch()
+            .as_millis();
+
+        self.pos += 2;
+        self.write_raw(&seq.to_be_bytes());
+        self.write_raw(&uptime_ms.to_be_bytes());
+        self.write_raw(&[record.level() as u8, target.len() as u8]);
+        self.write_raw(target);
+        _ = core::fmt::write(self, *record.args());
+
+        let record_len = self.pos - record_start - 2;
+        self.buf[record_start..record_start + 2]
+            .copy_from_slice(&(record_len as u16).to_be_bytes());
+    }
+
+    fn write_legacy_record(&mut self, record: &log::Record) {
+        if self.pos + LOG_LEGACY_RECORD_HEADER_LEN > self.buf.len() {
+            self.mark_truncated();
+            return;
+        }
+
+        let level = match record.level() {
+            log::Level::Error => "E ",
+            log::Level::Warn => "W ",
+            log::Level::Info => "I ",
+            log::Level::Debug => "D ",
+            log::Level::Trace => "T ",
+        };
+
+        let record_start = self.pos;
+        self.pos += 2;
+        self.write_raw(level.as_bytes());
+        _ = core::fmt::write(self, *record.args());
+
+        let record_len = self.pos - record_start - 2;
+        self.buf[record_start..record_start + 2]
+            .copy_from_slice(&(record_len as u16).to_be_bytes());
     }
 
     fn write_raw(&mut self, bytes: &[u8]) {
@@ -55,6 +174,15 @@ pub const FILTER_MAX: log::LevelFilter = log::LevelFilter::Info;
 #[cfg(not(feature = "release_build"))]
 pub const FILTER_MAX: log::LevelFilter = log::LevelFilter::Debug;
 
+/// Runtime global level (defaults to FILTER_MAX)
+static mut LEVEL: log::LevelFilter = FILTER_MAX;
+
+/// Per-target level overrides (target prefix, level)
+static mut TARGET_LEVELS: heapless::Vec<
+    (heapless::String<48>, log::LevelFilter),
+    LOG_TARGET_OVERRIDES_MAX,
+> = heapless::Vec::new();
+
 pub struct FkmLogger;
 
 impl FkmLogger {
@@ -66,15 +194,87 @@ impl FkmLogger {
     }
 }
 
-impl log::Log for FkmLogger {
-    fn enabled(&self, metadata: &log::Metadata) -> bool {
-        let level = metadata.level();
-        //let target = metadata.target();
+/// Applies global level and per-target overrides (longest matching target prefix wins)
+pub fn apply_log_config(config: &LogConfig) -> Result<(), String> {
+    let level = log::LevelFilter::from_str(&config.level)
+        .map_err(|_| alloc::format!("Invalid log level: {}", config.level))?;
+
+    let mut targets = heapless::Vec::new();
+    for target in &config.targets {
+        let target_level = log::LevelFilter::from_str(&target.level)
+            .map_err(|_| alloc::format!("Invalid log level: {}", target.level))?;
+        let target_name = heapless::String::from_str(&target.target)
+            .map_err(|_| alloc::format!("Log target too long: {}", target.target))?;
+
+        targets
+            .push((target_name, target_level))
+            .map_err(|_| "Too many log targets".to_string())?;
+    }
+
+    let max_level = targets
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(level, core::cmp::max);
+
+    unsafe {
+        LEVEL = level;
+        TARGET_LEVELS = targets;
+        log::set_max_level_racy(max_level);
+    }
+
+    Ok(())
+}
+
+pub async fn load_log_config(nvs: &Nvs) {
+    let Ok(buf) = nvs.get::<Vec<u8>>(NVS_LOG_CONFIG).await else {
+        return;
+    };
+
+    let res = serde_json::from_slice::<LogConfig>(&buf)
+        .map_err(|e| alloc::format!("{e:?}"))
+        .and_then(|config| apply_log_config(&config));
 
-        if level <= FILTER_MAX {
-            return true;
+    if let Err(e) = res {
+        log::error!("Log config load failed: {e}");
+        _ = nvs.delete(NVS_LOG_CONFIG).await;
+    }
+}
+
+pub async fn set_log_config(nvs: &Nvs, config: LogConfig, persist: bool) {
+    if let Err(e) = apply_log_config(&config) {
+        log::error!("{e}");
+        return;
+    }
+
+    log::info!("Log config set: {config:?}");
+    if persist {
+        _ = nvs.delete(NVS_LOG_CONFIG).await;
+        if let Ok(vec) = serde_json::to_vec(&config)
+            && let Err(e) = nvs.set(NVS_LOG_CONFIG, vec.as_slice()).await
+        {
+            log::error!("{e:?} Faile to write to nvs! (LOG_CONFIG {})", vec.len());
         }
-        false
+    }
+}
+
+fn target_level(target: &str) -> log::LevelFilter {
+    #[allow(static_mut_refs)]
+    let targets = unsafe { &TARGET_LEVELS };
+
+    targets
+        .iter()
+        .filter(|(prefix, _)| {
+            target.starts_with(prefix.as_str())
+                && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, level)| *level)
+        .unwrap_or(unsafe { LEVEL })
+}
+
+impl log::Log for FkmLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= target_level(metadata.target())
     }
 
     fn log(&self, record: &log::Record) {
@@ -101,36 +301,21 @@ impl log::Log for FkmLogger {
         esp_println::println!("{}{} - {}{}", color, record.level(), record.args(), reset);
 
         #[cfg(not(any(feature = "bat_dev_lcd", feature = "qa")))]
-        if !ota_state() && !sleep_state() {
-            let level: &str = match record.level() {
-                log::Level::Error => "E ",
-                log::Level::Warn => "W ",
-                log::Level::Info => "I ",
-                log::Level::Debug => "D ",
-                log::Level::Trace => "T ",
+        {
+            let seq = unsafe {
+                let seq = LOG_SEQ;
+                LOG_SEQ = LOG_SEQ.wrapping_add(1);
+                seq
             };
 
+            if ota_state() || sleep_state() {
+                return;
+            }
+
             unsafe {
                 #[allow(clippy::deref_addrof)]
                 let w = &mut *(&raw mut LOGS_WRITER);
-                if w.pos == 0 {
-                    w.buf[0] = b'L';
-                    w.buf[1] = 0x00;
-                    w.pos = 10;
-                }
-
-                if w.pos + 3 > w.buf.len() {
-                    w.mark_truncated();
-                    return;
-                }
-
-                let line_start = w.pos;
-                w.pos += 2;
-                w.write_raw(level.as_bytes());
-
-                _ = core::fmt::write(w, *record.args());
-                let line_len = w.pos - line_start - 2;
-                w.buf[line_start..line_start + 2].copy_from_slice(&(line_len as u16).to_be_bytes());
+                w.write_record(seq, record);
             }
         }
     }
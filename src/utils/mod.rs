@@ -7,7 +7,32 @@ pub mod error_log;
 pub mod logger;
 pub mod rolling_average;
 pub mod signaled_mutex;
+pub mod signing;
 pub mod stackmat;
+pub mod tls;
+
+/// Removes whole device configuration from NVS (caller should reset device afterwards)
+pub async fn factory_reset(nvs: &esp_hal_wifimanager::Nvs) {
+    use crate::consts::{
+        NVS_BONDING_KEY, NVS_ERROR_LOG, NVS_LOG_CONFIG, NVS_SAVED_STATE, NVS_SECRET_KEY,
+        NVS_SETTINGS, NVS_SIGN_KEY, NVS_SOLVE_QUEUE, NVS_TLS_PIN, NVS_WS_URLS,
+    };
+
+    _ = nvs.delete(esp_hal_wifimanager::WIFI_NVS_KEY).await;
+    _ = nvs.delete(NVS_SIGN_KEY).await;
+    _ = nvs.delete(NVS_SECRET_KEY).await;
+    _ = nvs.delete(NVS_BONDING_KEY).await;
+    _ = nvs.delete(NVS_ERROR_LOG).await;
+    _ = nvs.delete(NVS_TLS_PIN).await;
+    _ = nvs.delete(NVS_WS_URLS).await;
+    _ = nvs.delete(NVS_SETTINGS).await;
+    _ = nvs.delete(NVS_LOG_CONFIG).await;
+    _ = nvs.delete(NVS_SOLVE_QUEUE).await;
+    _ = nvs.delete(NVS_SAVED_STATE).await;
+
+    #[cfg(feature = "v4")]
+    _ = nvs.delete(crate::consts::NVS_BUZZER_VOLUME).await;
+}
 
 pub fn spawn_task<T>(
     spawner: &Spawner,
@@ -43,6 +68,17 @@ pub fn set_brownout_detection(state: bool) {
     }
 }
 
+/// Returns RSSI of currently connected AP (None if not connected)
+pub fn wifi_rssi() -> Option<i32> {
+    unsafe extern "C" {
+        fn esp_wifi_sta_get_rssi(rssi: *mut i32) -> i32;
+    }
+
+    let mut rssi = 0;
+    let res = unsafe { esp_wifi_sta_get_rssi(&mut rssi) };
+    (res == 0).then_some(rssi)
+}
+
 pub fn get_random_u64() -> u64 {
     let mut buf = [0; 8];
     _ = getrandom::getrandom(&mut buf);
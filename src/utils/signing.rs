@@ -0,0 +1,168 @@
+use crate::structs::TimerPacketInner;
+use alloc::{format, string::String, vec::Vec};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[inline(always)]
+pub fn secret_key() -> Option<[u8; 16]> {
+    unsafe { crate::state::SECRET_KEY }
+}
+
+/// Parses secret entered in setup panel (32 hex chars)
+pub fn parse_secret_key(secret: &str) -> Option<[u8; 16]> {
+    from_hex(secret.trim())?.try_into().ok()
+}
+
+pub fn generate_secret_key() -> [u8; 16] {
+    let mut secret = [0; 16];
+    _ = getrandom::getrandom(&mut secret);
+    secret
+}
+
+/// Verifies `randomhmac` upgrade header: HMAC-SHA256(secret, "trust|{random}|{fkm_token}")
+pub fn verify_trust_response(secret: &[u8; 16], random: u64, fkm_token: i32, hmac: &str) -> bool {
+    let Some(hmac) = from_hex(hmac) else {
+        return false;
+    };
+
+    let Ok(mut mac) = HmacSha256::new_from_slice(secret) else {
+        return false;
+    };
+
+    mac.update(format!("trust|{random}|{fkm_token}").as_bytes());
+    mac.verify_slice(&hmac).is_ok()
+}
+
+/// Verifies remote command: HMAC-SHA256(secret, "command|{efuse}|{challenge}|{command}|{nonce}").
+/// Devices without secret don't accept any commands.
+pub fn verify_command(command: &str, nonce: u64, hmac: &str) -> bool {
+    let Some(hmac) = from_hex(hmac) else {
+        return false;
+    };
+
+    let Some(secret) = secret_key() else {
+        return false;
+    };
+
+    let Ok(mut mac) = HmacSha256::new_from_slice(&secret) else {
+        return false;
+    };
+
+    mac.update(
+        format!(
+            "command|{}|{}|{command}|{nonce}",
+            crate::utils::get_efuse_u32(),
+            unsafe { crate::state::TRUST_CHALLENGE }
+        )
+        .as_bytes(),
+    );
+    mac.verify_slice(&hmac).is_ok()
+}
+
+/// Fills `hmac` field of signed packets (Solve, CardInfoRequest, Add) if device has secret key
+pub fn sign_packet(packet: &mut TimerPacketInner) {
+    let Some(secret) = secret_key() else {
+        return;
+    };
+
+    // hmac keyed with secret sent in the same packet proves nothing
+    if matches!(
+        packet,
+        TimerPacketInner::Add {
+            secret_key: Some(_),
+            ..
+        }
+    ) {
+        return;
+    }
+
+    let Some(canonical) = canonical_fields(packet) else {
+        return;
+    };
+
+    let Ok(mut mac) = HmacSha256::new_from_slice(&secret) else {
+        return;
+    };
+    mac.update(canonical.as_bytes());
+    let signature = to_hex(&mac.finalize().into_bytes());
+
+    match packet {
+        TimerPacketInner::Solve { hmac, .. }
+        | TimerPacketInner::CardInfoRequest { hmac, .. }
+        | TimerPacketInner::Add { hmac, .. } => *hmac = Some(signature),
+        _ => {}
+    }
+}
+
+/// Canonical representation of signed packet fields (joined with '|').
+/// CardInfoRequest is bound to current connection challenge (to prevent replays),
+/// Solve isn't (it can be replayed from solve queue and is deduped by session_id)
+fn canonical_fields(packet: &TimerPacketInner) -> Option<String> {
+    let canonical = match packet {
+        TimerPacketInner::Solve {
+            solve_time,
+            penalty,
+            competitor_id,
+            judge_id,
+            timestamp,
+            timestamp_ms,
+            session_id,
+            delegate,
+            inspection_time,
+            group_id,
+            ..
+        } => format!(
+            "solve|{}|{solve_time}|{penalty}|{competitor_id}|{judge_id}|{timestamp}|{timestamp_ms}|{session_id}|{delegate}|{inspection_time}|{group_id}",
+            crate::utils::get_efuse_u32()
+        ),
+        TimerPacketInner::CardInfoRequest {
+            card_id,
+            is_competitor,
+            attendance_device,
+            ..
+        } => format!(
+            "card_info|{}|{}|{card_id}|{is_competitor}|{}",
+            crate::utils::get_efuse_u32(),
+            unsafe { crate::state::TRUST_CHALLENGE },
+            attendance_device.unwrap_or(false)
+        ),
+        TimerPacketInner::Add {
+            firmware, sign_key, ..
+        } => format!(
+            "add|{}|{firmware}|{sign_key}",
+            crate::utils::get_efuse_u32()
+        ),
+        _ => return None,
+    };
+
+    Some(canonical)
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut out = String::with_capacity(data.len() * 2);
+    for b in data {
+        out.push(HEX[(b >> 4) as usize] as char);
+        out.push(HEX[(b & 0x0F) as usize] as char);
+    }
+
+    out
+}
+
+pub fn from_hex(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+
+    data.as_bytes()
+        .chunks(2)
+        .map(|chunk| {
+            let high = (chunk[0] as char).to_digit(16)?;
+            let low = (chunk[1] as char).to_digit(16)?;
+            Some(((high << 4) | low) as u8)
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+use alloc::{string::String, vec::Vec};
+use embedded_tls::{
+    CertificateEntryRef, CertificateRef, HandshakeVerifyRef, SignatureScheme, TlsCipherSuite,
+    TlsError, TlsVerifier,
+};
+use fkm_tls::{HandshakeScheme, PinError};
+
+pub use fkm_tls::parse_pin;
+
+/// Verifier that accepts server only if SHA-256 fingerprint of one of its certificates (or
+/// of its SubjectPublicKeyInfo) matches pinned one, see `fkm_tls::verify_pin`.
+/// Without pin it behaves like `NoVerify`.
+pub struct PinnedVerifier<CipherSuite: TlsCipherSuite> {
+    pin: Option<[u8; 32]>,
+    host: String,
+    spki: Option<Vec<u8>>,
+    transcript: Option<CipherSuite::Hash>,
+}
+
+impl<CipherSuite: TlsCipherSuite> PinnedVerifier<CipherSuite> {
+    pub fn new(pin: Option<[u8; 32]>, host: &str) -> Self {
+        Self {
+            pin,
+            host: String::from(host),
+            spki: None,
+            transcript: None,
+        }
+    }
+}
+
+impl<CipherSuite: TlsCipherSuite> TlsVerifier<CipherSuite> for PinnedVerifier<CipherSuite> {
+    fn set_hostname_verification(&mut self, _hostname: &str) -> Result<(), TlsError> {
+        Ok(())
+    }
+
+    fn verify_certificate(
+        &mut self,
+        transcript: &CipherSuite::Hash,
+        cert: CertificateRef,
+    ) -> Result<(), TlsError> {
+        let Some(pin) = self.pin else {
+            return Ok(());
+        };
+
+        let chain: Vec<&[u8]> = cert
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                CertificateEntryRef::X509(der) => Some(*der),
+                _ => None,
+            })
+            .collect();
+
+        if chain.len() != cert.entries.len() {
+            log::error!("[TLS] Server didn't send x509 certificate!");
+            return Err(TlsError::InvalidCertificate);
+        }
+
+        match fkm_tls::verify_pin(&chain, &pin, &self.host) {
+            Ok(spki) => {
+                self.spki = Some(spki.to_vec());
+                self.transcript = Some(transcript.clone());
+                Ok(())
+            }
+            Err(PinError::InvalidCertificate) => {
+                log::error!("[TLS] Cannot parse server certificate!");
+                Err(TlsError::InvalidCertificate)
+            }
+            Err(e) => {
+                log::error!("[TLS] Server certificate doesn't match pinned fingerprint! ({e:?})");
+                Err(TlsError::InvalidCertificate)
+            }
+        }
+    }
+
+    fn verify_signature(&mut self, verify: HandshakeVerifyRef) -> Result<(), TlsError> {
+        if self.pin.is_none() {
+            return Ok(());
+        }
+
+        // Pinned certificate is worthless if server doesn't prove that it owns its key
+        let (Some(spki), Some(transcript)) = (self.spki.take(), self.transcript.take()) else {
+            return Err(TlsError::InvalidSignature);
+        };
+
+        // every scheme offered by `TlsConfig` has to be handled here (or rejected)
+        let scheme = match verify.signature_scheme {
+            SignatureScheme::EcdsaSecp256r1Sha256 => HandshakeScheme::EcdsaSecp256r1Sha256,
+            SignatureScheme::EcdsaSecp384r1Sha384 => HandshakeScheme::EcdsaSecp384r1Sha384,
+            SignatureScheme::Ed25519 => HandshakeScheme::Ed25519,
+            SignatureScheme::RsaPssRsaeSha256 => HandshakeScheme::RsaPssRsaeSha256,
+            SignatureScheme::RsaPssRsaeSha384 => HandshakeScheme::RsaPssRsaeSha384,
+            SignatureScheme::RsaPssRsaeSha512 => HandshakeScheme::RsaPssRsaeSha512,
+            scheme => {
+                log::error!("[TLS] Unsupported signature scheme: {scheme:?}");
+                return Err(TlsError::InvalidSignatureScheme);
+            }
+        };
+
+        if !fkm_tls::verify_handshake(&spki, scheme, &transcript.finalize(), verify.signature) {
+            return Err(TlsError::InvalidSignature);
+        }
+
+        Ok(())
+    }
+}
@@ -9,3 +9,9 @@ pub const HW_VER: &str = "v0";
 
 #[cfg(not(feature = "gen_version"))]
 pub const FIRMWARE: &str = "FALLBACKF";
+
+#[cfg(not(feature = "gen_version"))]
+pub const BUILD_TIME: u64 = 0;
+
+#[cfg(not(feature = "gen_version"))]
+pub const OTA_PUBKEY: Option<[u8; 32]> = None;
@@ -1,7 +1,16 @@
 use crate::{
-    consts::WS_RETRY_MS,
-    state::{GlobalState, Scene, ota_state},
-    structs::{ApiError, FromPacket, TimerPacket, TimerPacketInner},
+    consts::{
+        PROTOCOL_OTA_RESUME, PROTOCOL_SETTINGS, PROTOCOL_TIME_SYNC, PROTOCOL_VERSION,
+        WS_HEARTBEAT_INTERVAL_MS, WS_HEARTBEAT_MAX_MISSED, WS_REQUEST_ATTEMPTS,
+        WS_REQUEST_MAX_TIMEOUT_MS, WS_REQUEST_TIMEOUT_MS, WS_RETRY_MS, WS_STABLE_CONNECTION_MS,
+    },
+    ota::{OtaUpdate, OtaUpdateError},
+    state::{GlobalState, Scene},
+    structs::{
+        ApiError, DELEGATE_TAG, FromPacket, HelloPacket, SUPPORTED_PACKETS, StatusResponsePacket,
+        TimerPacket, TimerPacketInner,
+    },
+    utils::tls::PinnedVerifier,
 };
 use alloc::{boxed::Box, rc::Rc, string::ToString, vec::Vec};
 use core::str::FromStr;
@@ -11,9 +20,7 @@ use embassy_sync::{
     signal::Signal,
 };
 use embassy_time::{Duration, Instant, Timer, WithTimeout};
-use embedded_tls::{Aes128GcmSha256, NoVerify, TlsConfig, TlsConnection, TlsContext};
-use esp_hal_ota::Ota;
-use esp_storage::FlashStorage;
+use embedded_tls::{Aes128GcmSha256, TlsConfig, TlsConnection, TlsContext, TlsError};
 use rand_core::OsRng;
 use ws_framer::{WsFrame, WsFrameOwned, WsRxFramer, WsTxFramer, WsUrl, WsUrlOwned};
 
@@ -31,6 +38,10 @@ static PACKET_SERIALIZE_LOGGED: core::sync::atomic::AtomicBool =
     core::sync::atomic::AtomicBool::new(false);
 static TAGGED_SUBSCRIBER_LOGGED: core::sync::atomic::AtomicBool =
     core::sync::atomic::AtomicBool::new(false);
+static TLS_PIN_MISMATCH_LOGGED: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
+static HEARTBEAT_TIMEOUT_LOGGED: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
 
 const WS_BUF_SIZE: usize = 8192;
 const TLS_BUF_SIZE: usize = 16640;
@@ -49,12 +60,15 @@ static mut TLS_TX_BUF: core::mem::MaybeUninit<[u8; TLS_BUF_SIZE]> =
 #[embassy_executor::task]
 pub async fn ws_task(
     stack: Stack<'static>,
-    ws_url: WsUrlOwned,
+    mut ws_url: WsUrlOwned,
+    mdns_first: bool,
     global_state: GlobalState,
     ws_sleep_sig: Rc<Signal<CriticalSectionRawMutex, bool>>,
     wifi_conn_sig: Rc<Signal<CriticalSectionRawMutex, bool>>,
 ) {
     log::debug!("ws_url: {ws_url:?}");
+    let mut rotation = crate::endpoints::EndpointRotation::new(mdns_first).await;
+    let mut ota = OtaUpdate::new();
 
     let mut rx_buf = [0; WS_BUF_SIZE];
     let mut tx_buf = [0; WS_BUF_SIZE];
@@ -79,6 +93,8 @@ pub async fn ws_task(
         unsafe { crate::state::SECURE_RFID = false };
         unsafe { crate::state::AUTO_SETUP = false };
         unsafe { crate::state::FKM_TOKEN = 0 };
+        unsafe { crate::state::SERVER_PROTOCOL_VERSION = 0 };
+        crate::remote_command::reset_nonce();
 
         let ws_fut = ws_loop(
             &global_state,
@@ -91,16 +107,21 @@ pub async fn ws_task(
             &mut *ssl_rx_buf,
             &mut *ssl_tx_buf,
             &wifi_conn_sig,
+            &mut ota,
         );
 
         let res = embassy_futures::select::select(ws_fut, ws_sleep_sig.wait()).await;
 
         match res {
-            embassy_futures::select::Either::First(res) => {
-                if let Err(e) = res {
+            embassy_futures::select::Either::First(res) => match res {
+                Ok(_) => rotation.on_success(),
+                Err(e) => {
                     log::error!("Ws_loop errored! {e:?}");
+                    if let Some(next_url) = rotation.on_failure(stack).await {
+                        ws_url = next_url;
+                    }
                 }
-            }
+            },
             embassy_futures::select::Either::Second(sleep) => {
                 if sleep {
                     loop {
@@ -113,10 +134,17 @@ pub async fn ws_task(
             }
         }
 
+        if ota.resume_expired() {
+            ota_resume_expired(&global_state).await;
+        }
+
         Timer::after_millis(500).await;
     }
 }
 
+/// Single connection attempt to given url. Returns Ok if connection was established
+/// (and later lost), Err if connection or upgrade failed or connection didn't last
+/// `WS_STABLE_CONNECTION_MS`.
 // TODO: maybe make less args?
 #[allow(clippy::too_many_arguments)]
 async fn ws_loop(
@@ -130,221 +158,408 @@ async fn ws_loop(
     ssl_rx_buf: &mut [u8],
     ssl_tx_buf: &mut [u8],
     wifi_conn_sig: &Rc<Signal<CriticalSectionRawMutex, bool>>,
+    ota: &mut OtaUpdate,
 ) -> Result<(), ()> {
-    loop {
-        {
-            global_state.state.lock().await.server_connected = Some(false);
-        }
-
-        let ip = if let Ok(addr) = embassy_net::Ipv4Address::from_str(ws_url.ip) {
-            addr
-        } else {
-            let dns_resolver = embassy_net::dns::DnsSocket::new(stack);
-            let res = dns_resolver
-                .query(ws_url.ip, embassy_net::dns::DnsQueryType::A)
-                .await;
-
-            let res = match res {
-                Ok(res) => res,
-                Err(e) => {
-                    log::error!("[WS]Dns resolver error: {e:?}");
-                    Timer::after_millis(1000).await;
-                    continue;
-                }
-            };
+    {
+        let mut state = global_state.state.lock().await;
+        state.server_connected = Some(false);
+        state.server_rtt = None;
+    }
 
-            let Some(IpAddress::Ipv4(addr)) = res.first() else {
-                log::error!("[WS]Dns resolver empty vec");
-                if !DNS_EMPTY_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
-                    crate::utils::error_log::add_error(
-                        crate::utils::error_log::codes::WS_DNS_RESOLVE_EMPTY,
-                    )
-                    .await;
+    let ip = if let Ok(addr) = embassy_net::Ipv4Address::from_str(ws_url.ip) {
+        addr
+    } else {
+        let dns_resolver = embassy_net::dns::DnsSocket::new(stack);
+        let res = dns_resolver
+            .query(ws_url.ip, embassy_net::dns::DnsQueryType::A)
+            .await;
 
-                    DNS_EMPTY_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
-                }
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                log::error!("[WS]Dns resolver error: {e:?}");
                 Timer::after_millis(1000).await;
-                continue;
-            };
-            *addr
+                return Err(());
+            }
         };
 
-        let mut socket = TcpSocket::new(stack, rx_buf, tx_buf);
-        socket.set_timeout(Some(embassy_time::Duration::from_secs(15)));
+        let Some(IpAddress::Ipv4(addr)) = res.first() else {
+            log::error!("[WS]Dns resolver empty vec");
+            if !DNS_EMPTY_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+                crate::utils::error_log::add_error(
+                    crate::utils::error_log::codes::WS_DNS_RESOLVE_EMPTY,
+                )
+                .await;
 
-        let remote_endpoint = (ip, ws_url.port);
-        let r = socket.connect(remote_endpoint).await;
-        if let Err(e) = r {
-            // but if wifi conneceted signal was sent remove wifi connection lost msg
-            if wifi_conn_sig.signaled() && wifi_conn_sig.wait().await {
-                global_state.state.lock().await.wifi_connected = Some(true);
+                DNS_EMPTY_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
             }
+            Timer::after_millis(1000).await;
+            return Err(());
+        };
+        *addr
+    };
 
-            log::error!("connect error: {e:?}");
-            Timer::after_millis(WS_RETRY_MS).await;
-            continue;
+    let mut socket = TcpSocket::new(stack, rx_buf, tx_buf);
+    socket.set_timeout(Some(embassy_time::Duration::from_secs(15)));
+
+    let remote_endpoint = (ip, ws_url.port);
+    let r = socket.connect(remote_endpoint).await;
+    if let Err(e) = r {
+        // but if wifi conneceted signal was sent remove wifi connection lost msg
+        if wifi_conn_sig.signaled() && wifi_conn_sig.wait().await {
+            global_state.state.lock().await.wifi_connected = Some(true);
         }
 
-        let mut socket = if ws_url.secure {
-            let mut tls = TlsConnection::new(socket, ssl_rx_buf, ssl_tx_buf);
+        log::error!("connect error: {e:?}");
+        Timer::after_millis(WS_RETRY_MS).await;
+        return Err(());
+    }
+
+    unsafe { crate::state::PINNED_CONNECTION = false };
+    let mut socket = if ws_url.secure {
+        let mut tls = TlsConnection::new(socket, ssl_rx_buf, ssl_tx_buf);
 
-            let config = TlsConfig::new().enable_rsa_signatures();
-            tls.open(TlsContext::new(
+        let tls_pin = unsafe { crate::state::TLS_PIN };
+        let config = TlsConfig::new().enable_rsa_signatures();
+        let res = tls
+            .open(TlsContext::new(
                 &config,
                 Provider {
                     rng: OsRng,
-                    verifier: NoVerify {},
+                    verifier: PinnedVerifier::new(tls_pin, ws_url.ip),
                 },
             ))
-            .await
-            .map_err(|e| {
-                log::error!("tls open error: {e:?}");
-            })?;
+            .await;
 
-            WsSocket::Tls(Box::new(tls))
-        } else {
-            WsSocket::Raw(socket)
-        };
+        if let Err(e) = res {
+            log::error!("tls open error: {e:?}");
+            if tls_pin.is_some()
+                && matches!(
+                    e,
+                    TlsError::InvalidCertificate
+                        | TlsError::InvalidSignature
+                        | TlsError::InvalidSignatureScheme
+                )
+                && !TLS_PIN_MISMATCH_LOGGED.load(core::sync::atomic::Ordering::Relaxed)
+            {
+                crate::utils::error_log::add_error(
+                    crate::utils::error_log::codes::WS_TLS_PIN_MISMATCH,
+                )
+                .await;
 
-        {
-            let mut state = global_state.state.lock().await;
-            state.server_connected = Some(true);
-            state.wifi_connected = Some(true);
-        }
-
-        log::info!("connected!");
-        let mut tx_framer = WsTxFramer::new(true, ws_tx_buf);
-        let mut rx_framer = WsRxFramer::new(ws_rx_buf);
-
-        let random = crate::utils::get_random_u64();
-        let path = alloc::format!(
-            "{}?id={}&ver={}&hw={}&firmware={}&random={}",
-            ws_url.path,
-            crate::utils::get_efuse_u32(),
-            crate::version::VERSION,
-            crate::version::HW_VER,
-            crate::version::FIRMWARE,
-            random
-        );
+                TLS_PIN_MISMATCH_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+            }
 
-        socket
-            .write_all(tx_framer.generate_http_upgrade(ws_url.host, &path, None))
-            .await
-            .map_err(|_| ())?;
+            return Err(());
+        }
 
-        let headers = loop {
-            let n = socket.read(rx_framer.mut_buf()).await.map_err(|_| ())?;
-            if n == 0 {
-                log::error!("error while reading http response");
-                if !HTTP_UPGRADE_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
-                    crate::utils::error_log::add_error(
-                        crate::utils::error_log::codes::WS_HTTP_UPGRADE_READ_FAILED,
-                    )
-                    .await;
+        unsafe { crate::state::PINNED_CONNECTION = tls_pin.is_some() };
+        WsSocket::Tls(Box::new(tls))
+    } else {
+        WsSocket::Raw(socket)
+    };
 
-                    HTTP_UPGRADE_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
-                }
-                return Err(());
-            }
+    {
+        let mut state = global_state.state.lock().await;
+        state.server_connected = Some(true);
+        state.wifi_connected = Some(true);
+    }
 
-            let res = rx_framer.process_http_response(n);
-            if let Some(resp) = res {
-                log::info!("http_resp_code: {}", resp.status_code);
-                break resp.headers;
-            }
-        };
+    log::info!("connected!");
+    let mut tx_framer = WsTxFramer::new(true, ws_tx_buf);
+    let mut rx_framer = WsRxFramer::new(ws_rx_buf);
 
-        if let Some(Ok(random_signed)) = headers
-            .iter()
-            .find(|h| h.name.to_lowercase() == "randomsigned")
-            .map(|h| h.value.parse::<u128>())
-        {
-            let mut key = [0; 16];
-            key[..4].copy_from_slice(&unsafe { crate::state::SIGN_KEY.to_be_bytes() });
+    let random = crate::utils::get_random_u64();
+    unsafe { crate::state::TRUST_CHALLENGE = random };
+    let auth = if crate::utils::signing::secret_key().is_some() {
+        "hmac"
+    } else {
+        "legacy"
+    };
 
-            let mut block = [0; 16];
-            block.copy_from_slice(&random_signed.to_be_bytes());
+    let path = alloc::format!(
+        "{}?id={}&ver={}&hw={}&firmware={}&random={}&auth={}",
+        ws_url.path,
+        crate::utils::get_efuse_u32(),
+        crate::version::VERSION,
+        crate::version::HW_VER,
+        crate::version::FIRMWARE,
+        random,
+        auth
+    );
+
+    socket
+        .write_all(tx_framer.generate_http_upgrade(ws_url.host, &path, None))
+        .await
+        .map_err(|_| ())?;
 
-            global_state
-                .aes
-                .lock()
-                .await
-                .decrypt(&mut block, esp_hal::aes::Key::Key128(key));
+    let headers = loop {
+        let n = socket.read(rx_framer.mut_buf()).await.map_err(|_| ())?;
+        if n == 0 {
+            log::error!("error while reading http response");
+            if !HTTP_UPGRADE_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+                crate::utils::error_log::add_error(
+                    crate::utils::error_log::codes::WS_HTTP_UPGRADE_READ_FAILED,
+                )
+                .await;
 
-            let recv_random = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
-            let fkm_token = i32::from_be_bytes(block[8..12].try_into().unwrap_or_default());
+                HTTP_UPGRADE_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+            }
+            return Err(());
+        }
 
-            log::debug!(
-                "[trust] random: {random}, recv_random: {recv_random} | fkm_token: {fkm_token}"
-            );
-            if random == recv_random {
-                unsafe { crate::state::TRUST_SERVER = true };
-                unsafe { crate::state::FKM_TOKEN = fkm_token };
-            } else {
-                #[cfg(not(feature = "e2e"))]
-                {
-                    global_state.state.lock().await.error_text =
-                        Some("Server Not Trusted!".to_string());
-                }
+        let res = rx_framer.process_http_response(n);
+        if let Some(resp) = res {
+            log::info!("http_resp_code: {}", resp.status_code);
+            if resp.status_code != 101 {
+                log::error!("Http upgrade rejected: {}", resp.status_code);
+                Timer::after_millis(WS_RETRY_MS).await;
+                return Err(());
             }
+
+            break resp.headers;
         }
+    };
+
+    let find_header = |name: &str| {
+        headers
+            .iter()
+            .find(|h| h.name.to_lowercase() == name)
+            .map(|h| h.value.to_string())
+    };
 
-        _ = FRAME_CHANNEL.try_send(WsFrameOwned::Ping(alloc::vec::Vec::new()));
+    let trust_response = if let Some(secret) = crate::utils::signing::secret_key() {
+        hmac_trust_response(
+            &secret,
+            random,
+            find_header("randomhmac"),
+            find_header("fkmtoken"),
+        )
+    } else {
+        #[cfg(feature = "legacy_trust")]
+        {
+            legacy_trust_response(global_state, random, find_header("randomsigned")).await
+        }
 
-        #[cfg(feature = "auto_add")]
+        #[cfg(not(feature = "legacy_trust"))]
         {
-            if !global_state
-                .state
-                .lock()
-                .await
-                .device_added
-                .unwrap_or(false)
+            log::error!("[trust] Device has no secret key! Re-add device.");
+            None
+        }
+    };
+
+    match trust_response {
+        Some((true, fkm_token)) => {
+            unsafe { crate::state::TRUST_SERVER = true };
+            unsafe { crate::state::FKM_TOKEN = fkm_token };
+            crate::solve_queue::trigger_replay();
+        }
+        Some((false, _)) => {
+            #[cfg(not(feature = "e2e"))]
             {
-                crate::ws::send_packet(crate::structs::TimerPacket {
-                    tag: None,
-                    data: crate::structs::TimerPacketInner::Add {
-                        firmware: alloc::string::ToString::to_string(crate::version::FIRMWARE),
-                        sign_key: unsafe { crate::state::SIGN_KEY },
-                    },
-                })
-                .await;
+                global_state.state.lock().await.error_text =
+                    Some("Server Not Trusted!".to_string());
             }
         }
+        None => {}
+    }
+
+    send_hello().await;
+    _ = FRAME_CHANNEL.try_send(WsFrameOwned::Ping(alloc::vec::Vec::new()));
 
-        loop {
-            let res = ws_rw(
-                &mut rx_framer,
-                &mut tx_framer,
-                global_state.clone(),
-                &mut socket,
-                wifi_conn_sig,
-            )
+    #[cfg(feature = "auto_add")]
+    {
+        if !global_state
+            .state
+            .lock()
+            .await
+            .device_added
+            .unwrap_or(false)
+        {
+            crate::ws::send_packet(crate::structs::TimerPacket {
+                tag: None,
+                data: crate::structs::TimerPacketInner::Add {
+                    firmware: alloc::string::ToString::to_string(crate::version::FIRMWARE),
+                    sign_key: unsafe { crate::state::SIGN_KEY },
+                    // secret is provisioned in setup panel, never sent on reconnect
+                    secret_key: None,
+                    hmac: None,
+                },
+            })
             .await;
+        }
+    }
 
-            if let Err(e) = res {
-                if ota_state() {
-                    log::error!("Connection lost during OTA update: {e:?}");
-                    crate::utils::error_log::add_error(
-                        crate::utils::error_log::codes::WS_CONNECTION_LOST_DURING_OTA,
-                    )
-                    .await;
-                    crate::utils::error_log::save_error_log(&global_state.nvs).await;
+    let connected_at = Instant::now();
+    loop {
+        let res = ws_rw(
+            &mut rx_framer,
+            &mut tx_framer,
+            global_state.clone(),
+            &mut socket,
+            wifi_conn_sig,
+            ota,
+        )
+        .await;
 
-                    global_state.state.lock().await.custom_message =
-                        Some(("Connection lost".to_string(), "during update".to_string()));
+        if let Err(e) = res {
+            if ota.in_progress() {
+                log::error!("Connection lost during OTA update: {e:?}");
+                crate::utils::error_log::add_error(
+                    crate::utils::error_log::codes::WS_CONNECTION_LOST_DURING_OTA,
+                )
+                .await;
 
-                    Timer::after_millis(5000).await;
-                    esp_hal::system::software_reset();
-                }
+                global_state.state.lock().await.custom_message =
+                    Some(("Connection lost".to_string(), "resuming update".to_string()));
+            }
 
-                log::error!("ws_rw_error: {e:?}");
-                Timer::after_millis(WS_RETRY_MS).await;
-                break;
+            log::error!("ws_rw_error: {e:?}");
+            Timer::after_millis(WS_RETRY_MS).await;
+
+            // server that drops connection right after upgrade is as broken as unreachable one
+            if connected_at.elapsed().as_millis() < WS_STABLE_CONNECTION_MS {
+                return Err(());
             }
+
+            return Ok(());
         }
     }
 }
 
+/// Announces protocol version and device capabilities (server replies with HelloResponse)
+async fn send_hello() {
+    let features = [
+        ("v3", cfg!(feature = "v3")),
+        ("v4", cfg!(feature = "v4")),
+        ("timer-func", cfg!(feature = "timer-func")),
+        ("sleep", cfg!(feature = "sleep")),
+        ("legacy_trust", cfg!(feature = "legacy_trust")),
+        ("e2e", cfg!(feature = "e2e")),
+        ("qa", cfg!(feature = "qa")),
+        ("signed_ota", crate::version::OTA_PUBKEY.is_some()),
+        ("ota_zlib", true),
+        ("attendance", true),
+    ];
+
+    let hello = HelloPacket {
+        protocol_version: PROTOCOL_VERSION,
+        features: features
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        packets: SUPPORTED_PACKETS.iter().map(|p| p.to_string()).collect(),
+        translations_count: crate::translations::TRANSLATIONS_COUNT,
+        ws_buf_size: WS_BUF_SIZE,
+        tls_buf_size: TLS_BUF_SIZE,
+    };
+
+    send_packet(TimerPacket {
+        tag: None,
+        data: TimerPacketInner::Hello(hello),
+    })
+    .await;
+}
+
+/// Sends packets newer than Hello, once server told which protocol version it speaks
+async fn after_hello(global_state: &GlobalState, ota: &OtaUpdate) {
+    if crate::state::server_supports(PROTOCOL_TIME_SYNC) {
+        crate::time_sync::request_sync();
+    }
+
+    if crate::state::server_supports(PROTOCOL_SETTINGS) {
+        send_packet(TimerPacket {
+            tag: None,
+            data: TimerPacketInner::EffectiveSettings(crate::settings::settings()),
+        })
+        .await;
+    }
+
+    if !crate::state::server_supports(PROTOCOL_OTA_RESUME) {
+        return;
+    }
+
+    if let Some(resume_packet) = ota.resume_packet() {
+        log::info!("Resuming OTA update: {resume_packet:?}");
+        global_state.state.lock().await.custom_message = None;
+        send_packet(TimerPacket {
+            tag: None,
+            data: resume_packet,
+        })
+        .await;
+    }
+}
+
+async fn device_status(global_state: &GlobalState) -> StatusResponsePacket {
+    let state = global_state.state.value().await;
+    let battery = unsafe { crate::state::BATTERY_STATUS };
+
+    StatusResponsePacket {
+        uptime_ms: Instant::now().as_millis(),
+        heap_used: esp_alloc::HEAP.used(),
+        heap_free: esp_alloc::HEAP.free(),
+        scene: alloc::format!("{:?}", state.flow.scene),
+        menu_scene: state.menu_scene.as_ref().map(|s| alloc::format!("{s:?}")),
+        battery_level: battery.map(|b| b.0),
+        battery_voltage_mv: battery.map(|b| b.1),
+        battery_current_ma: battery.and_then(|b| b.2),
+        stackmat_connected: state.stackmat_connected,
+        rfid_init: unsafe { crate::state::RFID_INIT },
+        wifi_rssi: crate::utils::wifi_rssi(),
+        ws_rtt_ms: state.server_rtt,
+        error_log_count: crate::utils::error_log::error_log_count(),
+        firmware: crate::version::FIRMWARE.to_string(),
+        hardware: crate::version::HW_VER.to_string(),
+        version: crate::version::VERSION.to_string(),
+    }
+}
+
+/// Returns (trusted, fkm_token) if server sent HMAC challenge response
+fn hmac_trust_response(
+    secret: &[u8; 16],
+    random: u64,
+    random_hmac: Option<alloc::string::String>,
+    fkm_token: Option<alloc::string::String>,
+) -> Option<(bool, i32)> {
+    let random_hmac = random_hmac?;
+    let fkm_token = fkm_token?.parse::<i32>().ok()?;
+
+    let trusted =
+        crate::utils::signing::verify_trust_response(secret, random, fkm_token, &random_hmac);
+    log::debug!("[trust] random: {random}, hmac valid: {trusted} | fkm_token: {fkm_token}");
+
+    Some((trusted, fkm_token))
+}
+
+/// Old trust scheme (32-bit sign key zero padded to AES-128 key)
+#[cfg(feature = "legacy_trust")]
+async fn legacy_trust_response(
+    global_state: &GlobalState,
+    random: u64,
+    random_signed: Option<alloc::string::String>,
+) -> Option<(bool, i32)> {
+    let random_signed = random_signed?.parse::<u128>().ok()?;
+
+    let mut key = [0; 16];
+    key[..4].copy_from_slice(&unsafe { crate::state::SIGN_KEY.to_be_bytes() });
+
+    let mut block = [0; 16];
+    block.copy_from_slice(&random_signed.to_be_bytes());
+
+    global_state
+        .aes
+        .lock()
+        .await
+        .decrypt(&mut block, esp_hal::aes::Key::Key128(key));
+
+    let recv_random = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
+    let fkm_token = i32::from_be_bytes(block[8..12].try_into().unwrap_or_default());
+
+    log::debug!("[trust] random: {random}, recv_random: {recv_random} | fkm_token: {fkm_token}");
+    Some((random == recv_random, fkm_token))
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 enum WsRwError {
@@ -353,36 +568,99 @@ enum WsRwError {
     SocketWriteError,
     SocketReadError,
     WifiDisconnected,
+    HeartbeatTimeout,
     Other,
 }
 
+/// Application level ping/pong tracking (detects half-open connections)
+struct Heartbeat {
+    next_ping: Instant,
+    pending: Option<(u64, Instant)>,
+    seq: u64,
+    missed: u8,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self {
+            next_ping: Instant::now() + Duration::from_millis(WS_HEARTBEAT_INTERVAL_MS),
+            pending: None,
+            seq: 0,
+            missed: 0,
+        }
+    }
+
+    /// Returns next ping payload or None if too many pongs were missed
+    fn tick(&mut self) -> Option<Vec<u8>> {
+        if self.pending.is_some() {
+            self.missed += 1;
+            log::warn!(
+                "[ws] Missed pong ({}/{WS_HEARTBEAT_MAX_MISSED})",
+                self.missed
+            );
+
+            if self.missed >= WS_HEARTBEAT_MAX_MISSED {
+                return None;
+            }
+        }
+
+        self.seq += 1;
+        self.pending = Some((self.seq, Instant::now()));
+        self.next_ping = Instant::now() + Duration::from_millis(WS_HEARTBEAT_INTERVAL_MS);
+
+        Some(self.seq.to_be_bytes().to_vec())
+    }
+
+    /// Returns round-trip time (ms) if pong answers last sent ping
+    fn pong(&mut self, payload: &[u8]) -> Option<u64> {
+        let (seq, sent_at) = self.pending?;
+        if payload != seq.to_be_bytes() {
+            return None;
+        }
+
+        self.pending = None;
+        self.missed = 0;
+        Some(sent_at.elapsed().as_millis())
+    }
+}
+
 async fn ws_rw(
     framer_rx: &mut WsRxFramer<'_>,
     framer_tx: &mut WsTxFramer<'_>,
     global_state: GlobalState,
     socket: &mut WsSocket<'_, '_>,
     wifi_conn_sig: &Rc<Signal<CriticalSectionRawMutex, bool>>,
+    ota: &mut OtaUpdate,
 ) -> Result<(), WsRwError> {
-    let mut ota = Ota::new(FlashStorage::new(unsafe {
-        esp_hal::peripherals::FLASH::steal()
-    }))
-    .map_err(WsRwError::OtaError)?;
     let tagged_publisher = TAGGED_RETURN
         .publisher()
         .map_err(|_| WsRwError::TaggedPublisherError)?;
     let recv = FRAME_CHANNEL.receiver();
+    let mut heartbeat = Heartbeat::new();
 
     loop {
+        // connected server may never continue interrupted update (heartbeat wakes this loop
+        // up, so deadline is checked at least every WS_HEARTBEAT_INTERVAL_MS)
+        if ota.resume_expired() {
+            ota_resume_expired(&global_state).await;
+        }
+
         let read_fut = socket.read(framer_rx.mut_buf());
         let write_fut = recv.receive();
+        let heartbeat_fut = Timer::at(heartbeat.next_ping);
 
-        let res = match embassy_futures::select::select3(read_fut, write_fut, wifi_conn_sig.wait())
-            .await
+        let res = match embassy_futures::select::select4(
+            read_fut,
+            write_fut,
+            wifi_conn_sig.wait(),
+            heartbeat_fut,
+        )
+        .await
         {
-            embassy_futures::select::Either3::First(read_res) => {
+            embassy_futures::select::Either4::First(read_res) => {
                 read_res.map_err(|_| WsRwError::SocketReadError)
             }
-            embassy_futures::select::Either3::Second(write_frame) => {
+            embassy_futures::select::Either4::Second(write_frame) => {
                 let mut offset = 0;
                 let frame_ref = write_frame.into_ref();
 
@@ -412,7 +690,7 @@ async fn ws_rw(
 
                 continue;
             }
-            embassy_futures::select::Either3::Third(state) => {
+            embassy_futures::select::Either4::Third(state) => {
                 if state {
                     // wifi connected signal
                     continue;
@@ -422,6 +700,24 @@ async fn ws_rw(
                 log::error!("Wifi disconnected, ws_rw stop.");
                 return Err(WsRwError::WifiDisconnected);
             }
+            embassy_futures::select::Either4::Fourth(_) => {
+                let Some(payload) = heartbeat.tick() else {
+                    log::error!("[ws] Heartbeat timeout, reconnecting.");
+                    if !HEARTBEAT_TIMEOUT_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
+                        crate::utils::error_log::add_error(
+                            crate::utils::error_log::codes::WS_HEARTBEAT_TIMEOUT,
+                        )
+                        .await;
+
+                        HEARTBEAT_TIMEOUT_LOGGED.store(true, core::sync::atomic::Ordering::Relaxed);
+                    }
+
+                    return Err(WsRwError::HeartbeatTimeout);
+                };
+
+                _ = FRAME_CHANNEL.try_send(WsFrameOwned::Ping(payload));
+                continue;
+            }
         };
 
         let n = match res {
@@ -483,37 +779,143 @@ async fn ws_rw(
                                 let mut state = global_state.state.lock().await;
                                 state.custom_message = Some((line1, line2));
                             }
-                            TimerPacketInner::EpochTime { current_epoch } => unsafe {
-                                crate::state::EPOCH_BASE = current_epoch - Instant::now().as_secs();
-                            },
-                            TimerPacketInner::DelegateResponse(_) => {
-                                tagged_publisher.publish((69420, timer_packet)).await;
+                            TimerPacketInner::EpochTime { current_epoch } => {
+                                crate::time_sync::on_epoch_time(current_epoch);
                             }
-                            TimerPacketInner::StartUpdate {
-                                version,
-                                build_time: _,
-                                size,
-                                crc,
-                                firmware,
+                            TimerPacketInner::TimeSyncResponse {
+                                uptime_ms,
+                                epoch_ms,
                             } => {
-                                if firmware != crate::version::FIRMWARE {
+                                crate::time_sync::on_time_sync_response(uptime_ms, epoch_ms);
+                            }
+                            TimerPacketInner::SetSettings { version, settings } => {
+                                if unsafe { !crate::state::TRUST_SERVER } {
+                                    log::warn!("[ws] Ignoring settings from untrusted server");
+
+                                    // server still learns which settings are in effect
+                                    send_packet(TimerPacket {
+                                        tag: timer_packet.tag,
+                                        data: TimerPacketInner::EffectiveSettings(
+                                            crate::settings::settings(),
+                                        ),
+                                    })
+                                    .await;
+                                    continue;
+                                }
+
+                                let effective = crate::settings::apply_settings(
+                                    &global_state.nvs,
+                                    version,
+                                    settings,
+                                )
+                                .await;
+                                global_state.state.signal();
+
+                                send_packet(TimerPacket {
+                                    tag: timer_packet.tag,
+                                    data: TimerPacketInner::EffectiveSettings(effective),
+                                })
+                                .await;
+                            }
+                            TimerPacketInner::SetLogConfig { config, persist } => {
+                                if unsafe { !crate::state::TRUST_SERVER } {
+                                    log::warn!("[ws] Ignoring log config from untrusted server");
                                     continue;
                                 }
 
+                                crate::utils::logger::set_log_config(
+                                    &global_state.nvs,
+                                    config,
+                                    persist,
+                                )
+                                .await;
+                            }
+                            TimerPacketInner::RemoteCommand {
+                                command,
+                                nonce,
+                                hmac,
+                            } => {
+                                crate::remote_command::handle_command(
+                                    &global_state,
+                                    timer_packet.tag,
+                                    command,
+                                    nonce,
+                                    &hmac,
+                                )
+                                .await;
+                            }
+                            TimerPacketInner::StatusRequest => {
+                                send_packet(TimerPacket {
+                                    tag: timer_packet.tag,
+                                    data: TimerPacketInner::StatusResponse(
+                                        device_status(&global_state).await,
+                                    ),
+                                })
+                                .await;
+                            }
+                            TimerPacketInner::ServerUrls { urls } => {
                                 #[cfg(not(feature = "e2e"))]
                                 if unsafe { !crate::state::TRUST_SERVER } {
+                                    log::warn!("[ws] Ignoring server urls from untrusted server");
                                     continue;
                                 }
 
-                                log::info!("Start update: {firmware}/{version}");
-                                log::info!("Begin update size: {size} crc: {crc}");
-                                ota.ota_begin(size, crc).map_err(WsRwError::OtaError)?;
+                                crate::endpoints::set_server_urls(&global_state.nvs, urls).await;
+                            }
+                            TimerPacketInner::HelloResponse { protocol_version } => {
+                                log::info!("[ws] Server protocol version: {protocol_version}");
                                 unsafe {
-                                    crate::state::OTA_STATE = true;
+                                    crate::state::SERVER_PROTOCOL_VERSION =
+                                        protocol_version.min(PROTOCOL_VERSION)
+                                };
+                                after_hello(&global_state, ota).await;
+                            }
+                            TimerPacketInner::SolveConfirm(confirm) => {
+                                crate::solve_queue::ack_solve(
+                                    &global_state.nvs,
+                                    &confirm.session_id,
+                                )
+                                .await;
+                            }
+                            TimerPacketInner::DelegateResponse(_) => {
+                                tagged_publisher.publish((DELEGATE_TAG, timer_packet)).await;
+                            }
+                            TimerPacketInner::StartUpdate(update) => {
+                                if update.firmware != crate::version::FIRMWARE {
+                                    continue;
+                                }
+
+                                #[cfg(not(feature = "e2e"))]
+                                if unsafe { !crate::state::TRUST_SERVER } {
+                                    continue;
+                                }
+
+                                log::info!("Start update: {}/{}", update.firmware, update.version);
+                                log::info!(
+                                    "Begin update size: {} crc: {} compression: {:?}",
+                                    update.size,
+                                    update.crc,
+                                    update.compression
+                                );
+
+                                match ota.begin(update) {
+                                    Ok(_) => {}
+                                    Err(OtaUpdateError::Ota(e)) => {
+                                        crate::utils::error_log::add_error(
+                                            crate::utils::error_log::codes::OTA_FLASH_FAILED,
+                                        )
+                                        .await;
+                                        return Err(WsRwError::OtaError(e));
+                                    }
+                                    Err(e) => {
+                                        log::error!("Update rejected: {e}");
+                                        crate::utils::error_log::add_error(e.error_code()).await;
+                                        continue;
+                                    }
                                 }
 
                                 let mut state = global_state.state.lock().await;
-                                state.scene = Scene::Update;
+                                state.flow.scene = Scene::Update;
                                 drop(state);
 
                                 clear_frame_channel();
@@ -560,7 +962,9 @@ async fn ws_rw(
                             TimerPacketInner::DumpCrashLog => {
                                 let mut tmp = Vec::new();
                                 tmp.push(b'C');
-                                tmp.extend_from_slice(crate::utils::error_log::dump_error_log());
+                                tmp.extend_from_slice(&crate::utils::error_log::dump_error_log(
+                                    crate::state::server_supports(PROTOCOL_TIME_SYNC),
+                                ));
 
                                 send_frame(ws_framer::WsFrameOwned::Binary(tmp)).await;
                             }
@@ -585,7 +989,7 @@ async fn ws_rw(
                     }
                 },
                 WsFrame::Binary(data) => {
-                    if !crate::state::ota_state() {
+                    if !ota.in_progress() {
                         continue;
                     }
 
@@ -594,10 +998,18 @@ async fn ws_rw(
                         continue;
                     }
 
-                    let res = ota.ota_write_chunk(data);
+                    let res = ota.write_chunk(data);
+                    if res == Err(OtaUpdateError::Decompress) {
+                        log::error!("OTA decompress failed!");
+                        ota_failed(&global_state, ota, OtaUpdateError::Decompress).await;
+                    }
+
                     if res == Ok(true) {
                         log::info!("OTA complete! Veryfying..");
-                        if ota.ota_flush(true, true).is_ok() {
+                        if let Err(e) = ota.verify_image() {
+                            log::error!("OTA image rejected: {e}");
+                            ota_failed(&global_state, ota, e).await;
+                        } else if ota.flush().is_ok() {
                             log::info!("OTA restart!");
                             esp_hal::system::software_reset();
                         } else {
@@ -609,8 +1021,7 @@ async fn ws_rw(
                         }
                     }
 
-                    let progress = (ota.get_ota_progress() * 100.0) as u8;
-                    global_state.update_progress.signal(progress);
+                    global_state.update_progress.signal(ota.progress());
 
                     FRAME_CHANNEL
                         .send(WsFrameOwned::Binary(alloc::vec::Vec::new()))
@@ -619,12 +1030,39 @@ async fn ws_rw(
                 WsFrame::Ping(_) => {
                     _ = FRAME_CHANNEL.try_send(WsFrameOwned::Pong(alloc::vec::Vec::new()));
                 }
+                WsFrame::Pong(payload) => {
+                    if let Some(rtt) = heartbeat.pong(payload) {
+                        log::debug!("[ws] RTT: {rtt}ms");
+                        global_state.state.lock().await.server_rtt = Some(rtt);
+                    }
+                }
                 _ => {}
             }
         }
     }
 }
 
+/// Aborts update, shows reason and restarts device
+/// Interrupted update wasn't continued in time, so partially written image is dropped
+async fn ota_resume_expired(global_state: &GlobalState) -> ! {
+    log::error!("OTA update wasn't resumed in time!");
+    crate::utils::error_log::add_error(crate::utils::error_log::codes::OTA_RESUME_TIMEOUT).await;
+    crate::utils::error_log::save_error_log(&global_state.nvs).await;
+    esp_hal::system::software_reset();
+}
+
+async fn ota_failed(global_state: &GlobalState, ota: &mut OtaUpdate, e: OtaUpdateError) {
+    ota.abort();
+    crate::utils::error_log::add_error(e.error_code()).await;
+    crate::utils::error_log::save_error_log(&global_state.nvs).await;
+
+    global_state.state.lock().await.custom_message =
+        Some(("Update rejected".to_string(), e.reason().to_string()));
+
+    Timer::after_millis(5000).await;
+    esp_hal::system::software_reset();
+}
+
 #[cfg(feature = "e2e")]
 async fn parse_test_packet(
     test_packet: crate::structs::TestPacketData,
@@ -685,7 +1123,9 @@ pub async fn send_test_ack(global_state: &GlobalState) {
     .await;
 }
 
-pub async fn send_packet(packet: TimerPacket) {
+pub async fn send_packet(mut packet: TimerPacket) {
+    crate::utils::signing::sign_packet(&mut packet.data);
+
     match serde_json::to_string(&packet) {
         Ok(string) => {
             FRAME_CHANNEL.send(WsFrameOwned::Text(string)).await;
@@ -713,7 +1153,64 @@ pub fn clear_frame_channel() {
     FRAME_CHANNEL.clear();
 }
 
-pub async fn send_request<T>(packet: TimerPacketInner) -> Result<T, ApiError>
+/// Retry policy of tagged requests. Every attempt resends packet with the same tag
+/// (so server can dedupe it) and waits for response twice as long as previous one.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u8,
+    pub timeout_ms: u64,
+    pub max_timeout_ms: u64,
+
+    /// After last attempt wait for response without timeout
+    pub wait_forever: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: WS_REQUEST_ATTEMPTS,
+            timeout_ms: WS_REQUEST_TIMEOUT_MS,
+            max_timeout_ms: WS_REQUEST_MAX_TIMEOUT_MS,
+            wait_forever: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delegate response is sent by server only after delegate resolves the case,
+    /// so call is sent once (resend would look like new delegate call)
+    pub fn delegate() -> Self {
+        Self {
+            attempts: 1,
+            wait_forever: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RequestError {
+    /// Packet couldn't be queued for sending (frame channel full)
+    NotSent,
+
+    /// Packet was sent, but server didn't respond after all attempts
+    Unacked,
+
+    /// Server responded with error (or unexpected packet)
+    Server(ApiError),
+}
+
+impl core::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RequestError::NotSent => write!(f, "Not connected!"),
+            RequestError::Unacked => write!(f, "Communication timeout!"),
+            RequestError::Server(e) => write!(f, "{}", e.error),
+        }
+    }
+}
+
+pub async fn send_request<T>(packet: TimerPacketInner) -> Result<T, RequestError>
 where
     T: FromPacket,
 {
@@ -721,14 +1218,14 @@ where
     _ = getrandom::getrandom(&mut tag_bytes);
     let tag = u64::from_be_bytes(tag_bytes);
 
-    send_tagged_request(tag, packet, true).await
+    send_tagged_request(tag, packet, RetryPolicy::default()).await
 }
 
 pub async fn send_tagged_request<T>(
     tag: u64,
     packet: TimerPacketInner,
-    timeout: bool,
-) -> Result<T, ApiError>
+    policy: RetryPolicy,
+) -> Result<T, RequestError>
 where
     T: FromPacket,
 {
@@ -737,38 +1234,65 @@ where
         data: packet,
     };
 
-    send_packet(packet)
-        .with_timeout(Duration::from_millis(5000))
-        .await
-        .map_err(|_| ApiError {
-            should_reset_time: false,
-            error: "Channel full".to_string(),
-        })?;
-
-    let packet = if timeout {
-        wait_for_tagged_response(tag)
-            .with_timeout(Duration::from_millis(5000))
-            .await
-            .map_err(|_| ApiError {
-                should_reset_time: false,
-                error: "Communication timeout!".to_string(),
-            })?
-    } else {
-        wait_for_tagged_response(tag).await
-    };
+    // subscribe before sending, so response to any attempt can't be missed
+    let mut subscriber = tagged_subscriber().await;
+    let mut timeout_ms = policy.timeout_ms;
+    let mut sent = false;
+
+    for attempt in 1..=policy.attempts.max(1) {
+        if attempt > 1 {
+            log::warn!("Request {tag} retry ({attempt}/{})", policy.attempts);
+        }
+
+        let send_res = send_packet(packet.clone())
+            .with_timeout(Duration::from_millis(timeout_ms))
+            .await;
+
+        match send_res {
+            Ok(()) => sent = true,
+            Err(_) => {
+                log::error!("Request {tag} not sent (channel full)");
+                timeout_ms = (timeout_ms * 2).min(policy.max_timeout_ms);
+                continue;
+            }
+        }
+
+        let last_attempt = attempt >= policy.attempts;
+        let resp = if last_attempt && policy.wait_forever {
+            Ok(wait_for_tagged_response(&mut subscriber, tag).await)
+        } else {
+            wait_for_tagged_response(&mut subscriber, tag)
+                .with_timeout(Duration::from_millis(timeout_ms))
+                .await
+        };
+
+        if let Ok(resp) = resp {
+            return FromPacket::from_packet(resp).map_err(RequestError::Server);
+        }
 
-    FromPacket::from_packet(packet)
+        timeout_ms = (timeout_ms * 2).min(policy.max_timeout_ms);
+    }
+
+    if sent {
+        Err(RequestError::Unacked)
+    } else {
+        Err(RequestError::NotSent)
+    }
 }
 
-async fn wait_for_tagged_response(tag: u64) -> TimerPacket {
+type TaggedSubscriber = embassy_sync::pubsub::Subscriber<
+    'static,
+    CriticalSectionRawMutex,
+    (u64, TimerPacket),
+    20,
+    20,
+    4,
+>;
+
+async fn tagged_subscriber() -> TaggedSubscriber {
     loop {
         match TAGGED_RETURN.subscriber() {
-            Ok(mut subscriber) => loop {
-                let (packet_tag, packet) = subscriber.next_message_pure().await;
-                if packet_tag == tag {
-                    return packet;
-                }
-            },
+            Ok(subscriber) => return subscriber,
             Err(_) => {
                 log::error!("failed to get TAGGED_RETURN subscriber! Retry!");
                 if !TAGGED_SUBSCRIBER_LOGGED.load(core::sync::atomic::Ordering::Relaxed) {
@@ -785,6 +1309,15 @@ async fn wait_for_tagged_response(tag: u64) -> TimerPacket {
     }
 }
 
+async fn wait_for_tagged_response(subscriber: &mut TaggedSubscriber, tag: u64) -> TimerPacket {
+    loop {
+        let (packet_tag, packet) = subscriber.next_message_pure().await;
+        if packet_tag == tag {
+            return packet;
+        }
+    }
+}
+
 enum WsSocket<'a, 'b> {
     Tls(Box<TlsConnection<'b, TcpSocket<'a>, Aes128GcmSha256>>),
     Raw(TcpSocket<'a>),
@@ -823,7 +1356,7 @@ impl WsSocket<'_, '_> {
 
 struct Provider {
     rng: OsRng,
-    verifier: NoVerify,
+    verifier: PinnedVerifier<Aes128GcmSha256>,
 }
 
 impl embedded_tls::CryptoProvider for Provider {
@@ -0,0 +1,54 @@
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+pub const EXPLICIT_VERSION: u8 = 0xA0;
+pub const EXPLICIT_EXTENSIONS: u8 = 0xA3;
+
+/// Reads single DER TLV with expected tag. Returns (whole tlv, value, rest)
+pub fn read(buf: &[u8], tag: u8) -> Option<(&[u8], &[u8], &[u8])> {
+    if *buf.first()? != tag {
+        return None;
+    }
+
+    let first_len = *buf.get(1)?;
+    let (len, header_len) = if first_len & 0x80 == 0 {
+        (first_len as usize, 2)
+    } else {
+        let len_bytes = (first_len & 0x7F) as usize;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+
+        let len = buf
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+
+        (len, 2 + len_bytes)
+    };
+
+    let end = header_len.checked_add(len)?;
+    if end > buf.len() {
+        return None;
+    }
+
+    Some((&buf[..end], &buf[header_len..end], &buf[end..]))
+}
+
+/// Reads TLV of any tag. Returns (tag, value, rest)
+pub fn read_any(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *buf.first()?;
+    let (_, value, rest) = read(buf, tag)?;
+    Some((tag, value, rest))
+}
+
+/// Value of BIT STRING without leading "unused bits" byte (only whole bytes are supported)
+pub fn bit_string(value: &[u8]) -> Option<&[u8]> {
+    match value.split_first()? {
+        (0, bits) => Some(bits),
+        _ => None,
+    }
+}
@@ -0,0 +1,136 @@
+//! Server certificate pinning of the timer `wss://` client. Pin is SHA-256 of one of the
+//! certificates sent by server (or of its SubjectPublicKeyInfo), so both leaf and CA
+//! (intermediate) pins work. Handshake signature is verified for every scheme the
+//! client offers, so pinned connection can't fall back to unverified one.
+
+#![no_std]
+
+extern crate alloc;
+
+mod der;
+mod x509;
+
+use sha2::{Digest, Sha256};
+use x509::{Algorithm, Certificate, Hash, PublicKey};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinError {
+    /// Certificate (or its public key) couldn't be parsed
+    InvalidCertificate,
+
+    /// No certificate in the chain matches the pin
+    Mismatch,
+
+    /// Certificates between leaf and pinned one aren't signed by each other
+    BrokenChain,
+
+    /// Pinned CA matched but leaf isn't issued for connected host
+    HostMismatch,
+}
+
+/// TLS 1.3 CertificateVerify schemes (RSASSA-PKCS1-v1_5 isn't allowed there)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandshakeScheme {
+    EcdsaSecp256r1Sha256,
+    EcdsaSecp384r1Sha384,
+    Ed25519,
+    RsaPssRsaeSha256,
+    RsaPssRsaeSha384,
+    RsaPssRsaeSha512,
+}
+
+/// Parses pin string (64 hex chars, optionally separated by ':')
+pub fn parse_pin(pin: &str) -> Option<[u8; 32]> {
+    let mut out = [0; 32];
+    let mut nibbles = pin
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8));
+
+    for byte in out.iter_mut() {
+        let high = nibbles.next()??;
+        let low = nibbles.next()??;
+        *byte = (high << 4) | low;
+    }
+
+    if nibbles.next().is_some() {
+        return None;
+    }
+
+    Some(out)
+}
+
+/// Checks certificate chain sent by server (leaf first) against pin and returns leaf
+/// SubjectPublicKeyInfo. When pin matches CA certificate, every certificate below it has
+/// to be signed by the next one and leaf has to be issued for `host`.
+pub fn verify_pin<'a>(
+    chain: &[&'a [u8]],
+    pin: &[u8; 32],
+    host: &str,
+) -> Result<&'a [u8], PinError> {
+    let certs = chain
+        .iter()
+        .map(|cert| Certificate::parse(cert))
+        .collect::<Option<alloc::vec::Vec<_>>>()
+        .ok_or(PinError::InvalidCertificate)?;
+
+    let leaf = certs.first().ok_or(PinError::InvalidCertificate)?;
+    let pinned = chain
+        .iter()
+        .zip(&certs)
+        .position(|(der, cert)| {
+            <[u8; 32]>::from(Sha256::digest(der)) == *pin
+                || <[u8; 32]>::from(Sha256::digest(cert.spki)) == *pin
+        })
+        .ok_or(PinError::Mismatch)?;
+
+    if pinned > 0 {
+        if !certs[..=pinned]
+            .windows(2)
+            .all(|c| c[0].is_signed_by(&c[1]))
+        {
+            return Err(PinError::BrokenChain);
+        }
+
+        if !leaf.matches_host(host) {
+            return Err(PinError::HostMismatch);
+        }
+    }
+
+    PublicKey::from_spki(leaf.spki).ok_or(PinError::InvalidCertificate)?;
+    Ok(leaf.spki)
+}
+
+/// Verifies server CertificateVerify signature of handshake transcript hash
+pub fn verify_handshake(
+    spki: &[u8],
+    scheme: HandshakeScheme,
+    transcript_hash: &[u8],
+    signature: &[u8],
+) -> bool {
+    let Some(key) = PublicKey::from_spki(spki) else {
+        return false;
+    };
+
+    // scheme fixes curve too (e.g. P-384 key can't be used with ecdsa_secp256r1_sha256)
+    let algorithm = match (scheme, &key) {
+        (HandshakeScheme::EcdsaSecp256r1Sha256, PublicKey::P256(_)) => {
+            Algorithm::Ecdsa(Hash::Sha256)
+        }
+        (HandshakeScheme::EcdsaSecp384r1Sha384, PublicKey::P384(_)) => {
+            Algorithm::Ecdsa(Hash::Sha384)
+        }
+        (HandshakeScheme::Ed25519, PublicKey::Ed25519(_)) => Algorithm::Ed25519,
+        (HandshakeScheme::RsaPssRsaeSha256, PublicKey::Rsa(_)) => Algorithm::RsaPss(Hash::Sha256),
+        (HandshakeScheme::RsaPssRsaeSha384, PublicKey::Rsa(_)) => Algorithm::RsaPss(Hash::Sha384),
+        (HandshakeScheme::RsaPssRsaeSha512, PublicKey::Rsa(_)) => Algorithm::RsaPss(Hash::Sha512),
+        _ => return false,
+    };
+
+    let mut msg = alloc::vec::Vec::with_capacity(98 + transcript_hash.len());
+    msg.resize(64, 0x20);
+    msg.extend_from_slice(b"TLS 1.3, server CertificateVerify\x00");
+    msg.extend_from_slice(transcript_hash);
+
+    key.verify(algorithm, &msg, signature)
+}
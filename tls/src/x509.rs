@@ -0,0 +1,262 @@
+use crate::der;
+use core::net::Ipv4Addr;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+const OID_CURVE_P256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+const OID_CURVE_P384: &[u8] = &[0x2B, 0x81, 0x04, 0x00, 0x22];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+const OID_ED25519: &[u8] = &[0x2B, 0x65, 0x70];
+
+const OID_ECDSA_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+const OID_ECDSA_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x03];
+const OID_ECDSA_SHA512: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x04];
+const OID_RSA_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B];
+const OID_RSA_SHA384: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0C];
+const OID_RSA_SHA512: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0D];
+
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1D, 0x11];
+const SAN_DNS_NAME: u8 = 0x82;
+const SAN_IP_ADDRESS: u8 = 0x87;
+
+/// Smaller RSA keys are rejected
+const RSA_MIN_BYTES: usize = 2048 / 8;
+
+/// Fields of x509 certificate needed for pinning and chain checks (borrowed from DER)
+pub struct Certificate<'a> {
+    /// Whole TBSCertificate (signed part)
+    pub tbs: &'a [u8],
+    pub issuer: &'a [u8],
+    pub subject: &'a [u8],
+
+    /// Whole SubjectPublicKeyInfo (tag + len + value)
+    pub spki: &'a [u8],
+    extensions: Option<&'a [u8]>,
+    signature_algorithm: &'a [u8],
+    signature: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    pub fn parse(cert: &'a [u8]) -> Option<Self> {
+        let (_, cert, _) = der::read(cert, der::SEQUENCE)?;
+        let (tbs, mut fields, rest) = der::read(cert, der::SEQUENCE)?;
+        let (_, algorithm, rest) = der::read(rest, der::SEQUENCE)?;
+        let (_, signature_algorithm, _) = der::read(algorithm, der::OID)?;
+        let (_, signature, _) = der::read(rest, der::BIT_STRING)?;
+
+        if fields.first() == Some(&der::EXPLICIT_VERSION) {
+            (_, _, fields) = der::read(fields, der::EXPLICIT_VERSION)?;
+        }
+
+        (_, _, fields) = der::read(fields, der::INTEGER)?; // serial
+        (_, _, fields) = der::read(fields, der::SEQUENCE)?; // signature
+        let (issuer, _, fields) = der::read(fields, der::SEQUENCE)?;
+        let (_, _, fields) = der::read(fields, der::SEQUENCE)?; // validity
+        let (subject, _, fields) = der::read(fields, der::SEQUENCE)?;
+        let (spki, _, mut fields) = der::read(fields, der::SEQUENCE)?;
+
+        // issuerUniqueID [1], subjectUniqueID [2], extensions [3]
+        let mut extensions = None;
+        while let Some((tag, value, rest)) = der::read_any(fields) {
+            if tag == der::EXPLICIT_EXTENSIONS {
+                extensions = Some(der::read(value, der::SEQUENCE)?.1);
+            }
+
+            fields = rest;
+        }
+
+        Some(Self {
+            tbs,
+            issuer,
+            subject,
+            spki,
+            extensions,
+            signature_algorithm,
+            signature: der::bit_string(signature)?,
+        })
+    }
+
+    /// Checks that certificate was signed by `issuer`
+    pub fn is_signed_by(&self, issuer: &Certificate) -> bool {
+        if self.issuer != issuer.subject {
+            return false;
+        }
+
+        let algorithm = match self.signature_algorithm {
+            OID_ECDSA_SHA256 => Algorithm::Ecdsa(Hash::Sha256),
+            OID_ECDSA_SHA384 => Algorithm::Ecdsa(Hash::Sha384),
+            OID_ECDSA_SHA512 => Algorithm::Ecdsa(Hash::Sha512),
+            OID_RSA_SHA256 => Algorithm::RsaPkcs1(Hash::Sha256),
+            OID_RSA_SHA384 => Algorithm::RsaPkcs1(Hash::Sha384),
+            OID_RSA_SHA512 => Algorithm::RsaPkcs1(Hash::Sha512),
+            OID_ED25519 => Algorithm::Ed25519,
+            _ => return false,
+        };
+
+        PublicKey::from_spki(issuer.spki)
+            .is_some_and(|key| key.verify(algorithm, self.tbs, self.signature))
+    }
+
+    /// Checks subjectAltName (dNSName with single label wildcard or IPv4 iPAddress)
+    pub fn matches_host(&self, host: &str) -> bool {
+        let Some(mut extensions) = self.extensions else {
+            return false;
+        };
+
+        while let Some((_, extension, rest)) = der::read(extensions, der::SEQUENCE) {
+            extensions = rest;
+
+            let Some((_, oid, mut extension)) = der::read(extension, der::OID) else {
+                return false;
+            };
+
+            if oid != OID_SUBJECT_ALT_NAME {
+                continue;
+            }
+
+            if extension.first() == Some(&der::BOOLEAN) {
+                let Some((_, _, rest)) = der::read(extension, der::BOOLEAN) else {
+                    return false;
+                };
+                extension = rest;
+            }
+
+            let Some((_, names, _)) = der::read(extension, der::OCTET_STRING)
+                .and_then(|(_, value, _)| der::read(value, der::SEQUENCE))
+            else {
+                return false;
+            };
+
+            let mut names = names;
+            while let Some((tag, name, rest)) = der::read_any(names) {
+                names = rest;
+
+                let matches = match tag {
+                    SAN_DNS_NAME => core::str::from_utf8(name)
+                        .is_ok_and(|pattern| dns_name_matches(pattern, host)),
+                    SAN_IP_ADDRESS => host.parse::<Ipv4Addr>().is_ok_and(|ip| ip.octets() == name),
+                    _ => false,
+                };
+
+                if matches {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+fn dns_name_matches(pattern: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    if let Some(suffix) = pattern.strip_prefix("*.") {
+        return host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(suffix));
+    }
+
+    pattern.eq_ignore_ascii_case(host)
+}
+
+#[derive(Clone, Copy)]
+pub enum Hash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+#[derive(Clone, Copy)]
+pub enum Algorithm {
+    Ecdsa(Hash),
+    RsaPkcs1(Hash),
+    RsaPss(Hash),
+    Ed25519,
+}
+
+pub enum PublicKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+    Rsa(rsa::RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+}
+
+impl PublicKey {
+    pub fn from_spki(spki: &[u8]) -> Option<Self> {
+        let (_, spki, _) = der::read(spki, der::SEQUENCE)?;
+        let (_, algorithm, rest) = der::read(spki, der::SEQUENCE)?;
+        let (_, key, _) = der::read(rest, der::BIT_STRING)?;
+        let key = der::bit_string(key)?;
+
+        let (_, oid, params) = der::read(algorithm, der::OID)?;
+        match oid {
+            OID_EC_PUBLIC_KEY => match der::read(params, der::OID)?.1 {
+                OID_CURVE_P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                    .ok()
+                    .map(Self::P256),
+                OID_CURVE_P384 => p384::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                    .ok()
+                    .map(Self::P384),
+                _ => None,
+            },
+            OID_RSA_ENCRYPTION => {
+                use rsa::{pkcs1::DecodeRsaPublicKey, traits::PublicKeyParts};
+
+                rsa::RsaPublicKey::from_pkcs1_der(key)
+                    .ok()
+                    .filter(|key| key.size() >= RSA_MIN_BYTES)
+                    .map(Self::Rsa)
+            }
+            OID_ED25519 => ed25519_dalek::VerifyingKey::from_bytes(key.try_into().ok()?)
+                .ok()
+                .map(Self::Ed25519),
+            _ => None,
+        }
+    }
+
+    pub fn verify(&self, algorithm: Algorithm, msg: &[u8], signature: &[u8]) -> bool {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+        match (self, algorithm) {
+            (Self::P256(key), Algorithm::Ecdsa(hash)) => {
+                p256::ecdsa::Signature::from_der(signature)
+                    .is_ok_and(|sig| key.verify_prehash(&digest(hash, msg), &sig).is_ok())
+            }
+            (Self::P384(key), Algorithm::Ecdsa(hash)) => {
+                p384::ecdsa::Signature::from_der(signature)
+                    .is_ok_and(|sig| key.verify_prehash(&digest(hash, msg), &sig).is_ok())
+            }
+            (Self::Rsa(key), Algorithm::RsaPkcs1(hash)) => {
+                let scheme = match hash {
+                    Hash::Sha256 => rsa::Pkcs1v15Sign::new::<Sha256>(),
+                    Hash::Sha384 => rsa::Pkcs1v15Sign::new::<Sha384>(),
+                    Hash::Sha512 => rsa::Pkcs1v15Sign::new::<Sha512>(),
+                };
+
+                key.verify(scheme, &digest(hash, msg), signature).is_ok()
+            }
+            (Self::Rsa(key), Algorithm::RsaPss(hash)) => {
+                let scheme = match hash {
+                    Hash::Sha256 => rsa::Pss::new::<Sha256>(),
+                    Hash::Sha384 => rsa::Pss::new::<Sha384>(),
+                    Hash::Sha512 => rsa::Pss::new::<Sha512>(),
+                };
+
+                key.verify(scheme, &digest(hash, msg), signature).is_ok()
+            }
+            (Self::Ed25519(key), Algorithm::Ed25519) => {
+                ed25519_dalek::Signature::from_slice(signature)
+                    .is_ok_and(|sig| key.verify_strict(msg, &sig).is_ok())
+            }
+            _ => false,
+        }
+    }
+}
+
+fn digest(hash: Hash, msg: &[u8]) -> alloc::vec::Vec<u8> {
+    match hash {
+        Hash::Sha256 => Sha256::digest(msg).to_vec(),
+        Hash::Sha384 => Sha384::digest(msg).to_vec(),
+        Hash::Sha512 => Sha512::digest(msg).to_vec(),
+    }
+}
@@ -0,0 +1,224 @@
+use fkm_tls::{HandshakeScheme, PinError, parse_pin, verify_handshake, verify_pin};
+use sha2::{Digest, Sha256};
+
+// P-384 root -> P-256 intermediate -> P-256 leaf (station.example.com, *.fkm.local, 192.168.1.10)
+const ROOT: &[u8] = include_bytes!("data/root.der");
+const INTER: &[u8] = include_bytes!("data/inter.der");
+const LEAF: &[u8] = include_bytes!("data/leaf.der");
+
+// same key and names as LEAF, signed by other CA with the same subject as INTER
+const LEAF_OTHER: &[u8] = include_bytes!("data/leaf_other.der");
+
+// RSA 2048 root -> RSA 2048 leaf (sha256WithRSAEncryption)
+const RSA_CA: &[u8] = include_bytes!("data/rsa_ca.der");
+const RSA_LEAF: &[u8] = include_bytes!("data/rsa_leaf.der");
+const ED25519: &[u8] = include_bytes!("data/ed.der");
+
+// CertificateVerify signatures of sha256("transcript") (sha384 for P-384)
+const SIG_P256: &[u8] = include_bytes!("data/sig_p256.bin");
+const SIG_P384: &[u8] = include_bytes!("data/sig_p384.bin");
+const SIG_RSA_PSS: &[u8] = include_bytes!("data/sig_rsa_pss256.bin");
+const SIG_RSA_PKCS1: &[u8] = include_bytes!("data/sig_rsa_pkcs1.bin");
+const SIG_ED25519: &[u8] = include_bytes!("data/sig_ed25519.bin");
+
+const HOST: &str = "station.example.com";
+
+fn cert_pin(cert: &[u8]) -> [u8; 32] {
+    Sha256::digest(cert).into()
+}
+
+/// Pin of public key (same as `tls_pin.sh` "Public key pin")
+fn spki_pin(cert: &[u8]) -> [u8; 32] {
+    let spki = verify_pin(&[cert], &cert_pin(cert), HOST).expect("valid cert");
+    Sha256::digest(spki).into()
+}
+
+fn transcript_hash() -> Vec<u8> {
+    Sha256::digest(b"transcript").to_vec()
+}
+
+#[test]
+fn pin_parsing() {
+    let hex = "00112233445566778899aabbccddeeff00112233445566778899AABBCCDDEEFF";
+    let pin = parse_pin(hex).expect("valid pin");
+    assert_eq!(pin[..4], [0x00, 0x11, 0x22, 0x33]);
+    assert_eq!(pin[31], 0xFF);
+
+    let colons = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|c| std::str::from_utf8(c).unwrap())
+        .collect::<Vec<_>>()
+        .join(":");
+    assert_eq!(parse_pin(&colons), Some(pin));
+
+    assert_eq!(parse_pin(&hex[..62]), None);
+    assert_eq!(parse_pin(&format!("{hex}00")), None);
+    assert_eq!(parse_pin(&hex.replace('a', "g")), None);
+}
+
+#[test]
+fn leaf_pin_match() {
+    let chain = [LEAF, INTER];
+    let spki = verify_pin(&chain, &cert_pin(LEAF), HOST).expect("cert pin");
+    assert_eq!(verify_pin(&chain, &spki_pin(LEAF), HOST), Ok(spki));
+
+    // exact leaf is pinned, host isn't checked then
+    assert!(verify_pin(&[LEAF], &cert_pin(LEAF), "other.example.com").is_ok());
+}
+
+#[test]
+fn pin_mismatch() {
+    assert_eq!(
+        verify_pin(&[LEAF, INTER], &cert_pin(LEAF_OTHER), HOST),
+        Err(PinError::Mismatch)
+    );
+    assert_eq!(
+        verify_pin(&[LEAF, INTER], &spki_pin(RSA_LEAF), HOST),
+        Err(PinError::Mismatch)
+    );
+
+    // pinned CA that server didn't send can't be checked
+    assert_eq!(
+        verify_pin(&[LEAF, INTER], &spki_pin(ROOT), HOST),
+        Err(PinError::Mismatch)
+    );
+
+    assert_eq!(
+        verify_pin(&[&LEAF[..100]], &cert_pin(LEAF), HOST),
+        Err(PinError::InvalidCertificate)
+    );
+}
+
+#[test]
+fn ca_pin_match() {
+    let leaf_spki = verify_pin(&[LEAF], &cert_pin(LEAF), HOST).unwrap();
+    let chain = [LEAF, INTER, ROOT];
+
+    assert_eq!(verify_pin(&chain, &spki_pin(INTER), HOST), Ok(leaf_spki));
+    assert_eq!(verify_pin(&chain, &cert_pin(ROOT), HOST), Ok(leaf_spki));
+    assert!(verify_pin(&chain, &spki_pin(ROOT), "timer.fkm.local").is_ok());
+    assert!(verify_pin(&chain, &spki_pin(ROOT), "192.168.1.10").is_ok());
+
+    assert!(verify_pin(&[RSA_LEAF, RSA_CA], &spki_pin(RSA_CA), HOST).is_ok());
+}
+
+#[test]
+fn ca_pin_host_mismatch() {
+    let chain = [LEAF, INTER];
+    for host in [
+        "example.com",
+        "evil.example.com",
+        "fkm.local",
+        "a.b.fkm.local",
+        "192.168.1.11",
+    ] {
+        assert_eq!(
+            verify_pin(&chain, &spki_pin(INTER), host),
+            Err(PinError::HostMismatch),
+            "{host}"
+        );
+    }
+}
+
+#[test]
+fn ca_pin_broken_chain() {
+    // leaf issued by other key with the same issuer name
+    assert_eq!(
+        verify_pin(&[LEAF_OTHER, INTER], &spki_pin(INTER), HOST),
+        Err(PinError::BrokenChain)
+    );
+
+    // certificate in between isn't signed by pinned one
+    assert_eq!(
+        verify_pin(&[LEAF, INTER, RSA_CA], &spki_pin(RSA_CA), HOST),
+        Err(PinError::BrokenChain)
+    );
+}
+
+#[test]
+fn handshake_signatures() {
+    let spki = |cert| verify_pin(&[cert], &cert_pin(cert), HOST).unwrap();
+    let hash = transcript_hash();
+    let hash384 = sha2::Sha384::digest(b"transcript").to_vec();
+
+    assert!(verify_handshake(
+        spki(LEAF),
+        HandshakeScheme::EcdsaSecp256r1Sha256,
+        &hash,
+        SIG_P256
+    ));
+    assert!(verify_handshake(
+        spki(ROOT),
+        HandshakeScheme::EcdsaSecp384r1Sha384,
+        &hash384,
+        SIG_P384
+    ));
+    assert!(verify_handshake(
+        spki(RSA_LEAF),
+        HandshakeScheme::RsaPssRsaeSha256,
+        &hash,
+        SIG_RSA_PSS
+    ));
+    assert!(verify_handshake(
+        spki(ED25519),
+        HandshakeScheme::Ed25519,
+        &hash,
+        SIG_ED25519
+    ));
+}
+
+#[test]
+fn handshake_signature_mismatch() {
+    let spki = |cert| verify_pin(&[cert], &cert_pin(cert), HOST).unwrap();
+    let hash = transcript_hash();
+    let mut other_hash = hash.clone();
+    other_hash[0] ^= 1;
+
+    assert!(!verify_handshake(
+        spki(LEAF),
+        HandshakeScheme::EcdsaSecp256r1Sha256,
+        &other_hash,
+        SIG_P256
+    ));
+    assert!(!verify_handshake(
+        spki(RSA_LEAF),
+        HandshakeScheme::RsaPssRsaeSha256,
+        &other_hash,
+        SIG_RSA_PSS
+    ));
+    assert!(!verify_handshake(
+        spki(ED25519),
+        HandshakeScheme::Ed25519,
+        &other_hash,
+        SIG_ED25519
+    ));
+
+    // signed by other key
+    assert!(!verify_handshake(
+        spki(INTER),
+        HandshakeScheme::EcdsaSecp256r1Sha256,
+        &hash,
+        SIG_P256
+    ));
+
+    // scheme has to match key type (and curve), pkcs1 padding isn't accepted
+    assert!(!verify_handshake(
+        spki(ROOT),
+        HandshakeScheme::EcdsaSecp256r1Sha256,
+        &hash,
+        SIG_P256
+    ));
+    assert!(!verify_handshake(
+        spki(RSA_LEAF),
+        HandshakeScheme::RsaPssRsaeSha256,
+        &hash,
+        SIG_RSA_PKCS1
+    ));
+    assert!(!verify_handshake(
+        spki(LEAF),
+        HandshakeScheme::Ed25519,
+        &hash,
+        SIG_ED25519
+    ));
+}
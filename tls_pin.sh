@@ -0,0 +1,31 @@
+#!/bin/bash
+# Prints SHA-256 pins (certificate and public key) accepted by station "TLS pin" setting.
+# CA (intermediate) certificate sent by server can be pinned too, leaf has to be issued
+# for the ws host then.
+set -e
+
+usage() {
+    echo "Usage: $0 <cert.pem | host:port>"
+    echo "  cert.pem:  Path to server certificate (PEM)"
+    echo "  host:port: Running wss:// server (e.g. local TLS WebSocket stand-in)"
+    exit 1
+}
+
+if [ $# -ne 1 ]; then
+    usage
+fi
+
+temp_file=$(mktemp)
+trap 'rm -f "$temp_file"' EXIT
+
+if [ -f "$1" ]; then
+    cp "$1" "$temp_file"
+else
+    openssl s_client -connect "$1" -tls1_3 </dev/null 2>/dev/null | openssl x509 > "$temp_file"
+fi
+
+cert_pin=$(openssl x509 -in "$temp_file" -outform der | openssl dgst -sha256 -hex | cut -d' ' -f 2)
+spki_pin=$(openssl x509 -in "$temp_file" -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256 -hex | cut -d' ' -f 2)
+
+echo "Certificate pin: $cert_pin"
+echo "Public key pin:  $spki_pin"
@@ -0,0 +1,366 @@
+//! Decoder for binary log frames sent by the timer over websocket.
+//!
+//! Frame layout (all integers big endian, see `src/utils/logger.rs`):
+//! - header: `'L'`, version, flags (bit 0 = truncated), stackmat time (u64),
+//!   boot epoch in ms (u64, 0 if unknown)
+//! - records: length (u16, rest of record), sequence (u32), uptime ms (u64),
+//!   level (u8, 1 = error .. 5 = trace), target length (u8), target, message
+//!
+//! Legacy frames (byte 1 is truncated flag `0`/`1`, single stackmat time header,
+//! records without sequence/timestamp/target) are decoded too.
+
+use std::fmt;
+
+pub const LOG_FRAME_VERSION: u8 = 2;
+const FLAG_TRUNCATED: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            1 => Self::Error,
+            2 => Self::Warn,
+            3 => Self::Info,
+            4 => Self::Debug,
+            5 => Self::Trace,
+            _ => return None,
+        })
+    }
+
+    fn from_letter(letter: u8) -> Option<Self> {
+        Some(match letter {
+            b'E' => Self::Error,
+            b'W' => Self::Warn,
+            b'I' => Self::Info,
+            b'D' => Self::Debug,
+            b'T' => Self::Trace,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// None for legacy frames
+    pub seq: Option<u32>,
+    /// Device uptime in ms (None for legacy frames)
+    pub uptime_ms: Option<u64>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// 1 for legacy frames
+    pub version: u8,
+    pub truncated: bool,
+    pub stackmat_time: u64,
+    /// Epoch (ms) at device boot, None if device didn't receive time from server yet
+    pub boot_epoch_ms: Option<u64>,
+    pub records: Vec<Record>,
+}
+
+impl Frame {
+    /// Wall clock time of record in ms since unix epoch
+    pub fn record_epoch_ms(&self, record: &Record) -> Option<u64> {
+        Some(self.boot_epoch_ms? + record.uptime_ms?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Empty,
+    BadMagic(u8),
+    UnsupportedVersion(u8),
+    ShortHeader,
+    ShortRecord { offset: usize },
+    BadLevel { offset: usize, level: u8 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty frame"),
+            Self::BadMagic(magic) => write!(f, "bad frame magic: {magic:#04x}"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported frame version: {version}"),
+            Self::ShortHeader => write!(f, "frame header too short"),
+            Self::ShortRecord { offset } => write!(f, "record at offset {offset} too short"),
+            Self::BadLevel { offset, level } => {
+                write!(f, "record at offset {offset} has invalid level: {level}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub fn decode_frame(buf: &[u8]) -> Result<Frame, DecodeError> {
+    let magic = *buf.first().ok_or(DecodeError::Empty)?;
+    if magic != b'L' {
+        return Err(DecodeError::BadMagic(magic));
+    }
+
+    match buf.get(1) {
+        Some(0 | 1) => decode_legacy(buf),
+        Some(&LOG_FRAME_VERSION) => decode_v2(buf),
+        Some(version) => Err(DecodeError::UnsupportedVersion(*version)),
+        None => Err(DecodeError::ShortHeader),
+    }
+}
+
+fn decode_v2(buf: &[u8]) -> Result<Frame, DecodeError> {
+    let mut reader = Reader::new(buf);
+    reader.skip(2).ok_or(DecodeError::ShortHeader)?;
+    let flags = reader.u8().ok_or(DecodeError::ShortHeader)?;
+    let stackmat_time = reader.u64().ok_or(DecodeError::ShortHeader)?;
+    let boot_epoch_ms = reader.u64().ok_or(DecodeError::ShortHeader)?;
+
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        let offset = reader.pos;
+        let short = DecodeError::ShortRecord { offset };
+
+        let len = reader.u16().ok_or(short.clone())? as usize;
+        let mut record = Reader::new(reader.bytes(len).ok_or(short.clone())?);
+        let seq = record.u32().ok_or(short.clone())?;
+        let uptime_ms = record.u64().ok_or(short.clone())?;
+        let level = record.u8().ok_or(short.clone())?;
+        let level = Level::from_byte(level).ok_or(DecodeError::BadLevel { offset, level })?;
+        let target_len = record.u8().ok_or(short.clone())? as usize;
+        let target = record.bytes(target_len).ok_or(short)?;
+        let message = record.rest();
+
+        records.push(Record {
+            seq: Some(seq),
+            uptime_ms: Some(uptime_ms),
+            level,
+            target: String::from_utf8_lossy(target).into_owned(),
+            message: String::from_utf8_lossy(message).into_owned(),
+        });
+    }
+
+    Ok(Frame {
+        version: LOG_FRAME_VERSION,
+        truncated: flags & FLAG_TRUNCATED != 0,
+        stackmat_time,
+        boot_epoch_ms: (boot_epoch_ms != 0).then_some(boot_epoch_ms),
+        records,
+    })
+}
+
+fn decode_legacy(buf: &[u8]) -> Result<Frame, DecodeError> {
+    let mut reader = Reader::new(buf);
+    reader.skip(1).ok_or(DecodeError::ShortHeader)?;
+    let truncated = reader.u8().ok_or(DecodeError::ShortHeader)? != 0;
+    let stackmat_time = reader.u64().ok_or(DecodeError::ShortHeader)?;
+
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        let offset = reader.pos;
+        let short = DecodeError::ShortRecord { offset };
+
+        let len = reader.u16().ok_or(short.clone())? as usize;
+        let line = reader.bytes(len).ok_or(short.clone())?;
+        if line.len() < 2 {
+            return Err(short);
+        }
+
+        let level = Level::from_letter(line[0]).ok_or(DecodeError::BadLevel {
+            offset,
+            level: line[0],
+        })?;
+
+        records.push(Record {
+            seq: None,
+            uptime_ms: None,
+            level,
+            target: String::new(),
+            message: String::from_utf8_lossy(&line[2..]).into_owned(),
+        });
+    }
+
+    Ok(Frame {
+        version: 1,
+        truncated,
+        stackmat_time,
+        boot_epoch_ms: None,
+        records,
+    })
+}
+
+/// Tracks record sequence numbers across frames to report lost lines
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    next: Option<u32>,
+}
+
+/// Lost lines between two received records (`from..to`, `to` exclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl Gap {
+    pub fn lost(&self) -> u32 {
+        self.to.wrapping_sub(self.from)
+    }
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns gap before this record (if any). Sequence going backwards means device
+    /// rebooted, then tracking starts over.
+    pub fn track(&mut self, record: &Record) -> Option<Gap> {
+        let seq = record.seq?;
+        let expected = self.next.replace(seq.wrapping_add(1))?;
+        if seq <= expected {
+            return None;
+        }
+
+        Some(Gap {
+            from: expected,
+            to: seq,
+        })
+    }
+}
+
+/// Formats ms since unix epoch as `YYYY-MM-DDTHH:MM:SS.mmmZ`
+pub fn format_epoch_ms(epoch_ms: u64) -> String {
+    let secs = epoch_ms / 1000;
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        epoch_ms % 1000
+    )
+}
+
+/// Formats single record as readable log line
+pub fn format_record(frame: &Frame, record: &Record) -> String {
+    let mut line = String::new();
+    if let Some(epoch_ms) = frame.record_epoch_ms(record) {
+        line += &format!("{} ", format_epoch_ms(epoch_ms));
+    }
+
+    if let Some(uptime_ms) = record.uptime_ms {
+        line += &format!("[{:>6}.{:03}] ", uptime_ms / 1000, uptime_ms % 1000);
+    }
+
+    if let Some(seq) = record.seq {
+        line += &format!("#{seq:<6} ");
+    }
+
+    line += &format!("{:<5} ", record.level);
+    if !record.target.is_empty() {
+        line += &format!("{}: ", record.target);
+    }
+
+    line += &record.message;
+    line
+}
+
+/// Parses hex string (whitespace ignored)
+pub fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .map(|b| (b as char).to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+
+    Some(digits.chunks(2).map(|d| (d[0] << 4) | d[1]).collect())
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.buf[self.pos.min(self.buf.len())..];
+        self.pos = self.buf.len();
+        rest
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_be_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+}
@@ -0,0 +1,112 @@
+use log_decoder::{SequenceTracker, decode_frame, format_record, parse_hex};
+use std::io::{BufRead, Read};
+
+const USAGE: &str = "Usage: log-decoder [--raw] [FILE...]
+
+Decodes timer log frames into readable log lines.
+By default input is hex encoded, one frame per line (stdin if no FILE given).
+With --raw every FILE is a single binary frame.";
+
+fn main() {
+    let mut raw = false;
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--raw" => raw = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let frames = match read_frames(raw, &files) {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut tracker = SequenceTracker::new();
+    let mut failed = false;
+    for (name, buf) in frames {
+        let frame = match decode_frame(&buf) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+
+        for record in &frame.records {
+            if let Some(gap) = tracker.track(record) {
+                println!(
+                    "-- {} line(s) lost (#{}..#{})",
+                    gap.lost(),
+                    gap.from,
+                    gap.to.wrapping_sub(1)
+                );
+            }
+
+            println!("{}", format_record(&frame, record));
+        }
+
+        if frame.truncated {
+            println!("-- frame truncated (device log buffer was full)");
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn read_frames(raw: bool, files: &[String]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    if raw {
+        if files.is_empty() {
+            let mut buf = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut buf)
+                .map_err(|e| format!("stdin: {e}"))?;
+
+            return Ok(vec![("stdin".to_string(), buf)]);
+        }
+
+        return files
+            .iter()
+            .map(|file| {
+                std::fs::read(file)
+                    .map(|buf| (file.clone(), buf))
+                    .map_err(|e| format!("{file}: {e}"))
+            })
+            .collect();
+    }
+
+    let mut inputs: Vec<(String, Box<dyn BufRead>)> = Vec::new();
+    if files.is_empty() {
+        inputs.push(("stdin".to_string(), Box::new(std::io::stdin().lock())));
+    }
+
+    for file in files {
+        let reader = std::fs::File::open(file).map_err(|e| format!("{file}: {e}"))?;
+        inputs.push((file.clone(), Box::new(std::io::BufReader::new(reader))));
+    }
+
+    let mut frames = Vec::new();
+    for (name, input) in inputs {
+        for (i, line) in input.lines().enumerate() {
+            let line = line.map_err(|e| format!("{name}: {e}"))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let buf = parse_hex(&line).ok_or(format!("{name}:{}: invalid hex", i + 1))?;
+            frames.push((format!("{name}:{}", i + 1), buf));
+        }
+    }
+
+    Ok(frames)
+}
@@ -0,0 +1,263 @@
+use log_decoder::{
+    DecodeError, Gap, LOG_FRAME_VERSION, Level, SequenceTracker, decode_frame, format_epoch_ms,
+    format_record, parse_hex,
+};
+
+const FRAME_HEADER_LEN: usize = 19;
+/// Including record length
+const RECORD_HEADER_LEN: usize = 16;
+
+struct TestRecord<'a> {
+    seq: u32,
+    uptime_ms: u64,
+    level: u8,
+    target: &'a str,
+    message: &'a str,
+}
+
+fn record<'a>(
+    seq: u32,
+    uptime_ms: u64,
+    level: u8,
+    target: &'a str,
+    message: &'a str,
+) -> TestRecord<'a> {
+    TestRecord {
+        seq,
+        uptime_ms,
+        level,
+        target,
+        message,
+    }
+}
+
+/// Builds frame the same way as firmware `LogsBufWriter` does
+fn encode_frame(
+    truncated: bool,
+    stackmat_time: u64,
+    boot_epoch_ms: u64,
+    records: &[TestRecord],
+) -> Vec<u8> {
+    let mut buf = vec![b'L', LOG_FRAME_VERSION, u8::from(truncated)];
+    buf.extend_from_slice(&stackmat_time.to_be_bytes());
+    buf.extend_from_slice(&boot_epoch_ms.to_be_bytes());
+    assert_eq!(buf.len(), FRAME_HEADER_LEN);
+
+    for record in records {
+        let record_start = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&record.seq.to_be_bytes());
+        buf.extend_from_slice(&record.uptime_ms.to_be_bytes());
+        buf.extend_from_slice(&[record.level, record.target.len() as u8]);
+        assert_eq!(buf.len() - record_start, RECORD_HEADER_LEN);
+
+        buf.extend_from_slice(record.target.as_bytes());
+        buf.extend_from_slice(record.message.as_bytes());
+
+        let record_len = (buf.len() - record_start - 2) as u16;
+        buf[record_start..record_start + 2].copy_from_slice(&record_len.to_be_bytes());
+    }
+
+    buf
+}
+
+#[test]
+fn round_trip() {
+    let buf = encode_frame(
+        false,
+        12345,
+        1_700_000_000_000,
+        &[
+            record(0, 1500, 3, "fkm_firmware::ws", "connected!"),
+            record(1, 1502, 1, "fkm_firmware::ota", "OTA failed: 3"),
+            record(2, 61_000, 5, "", ""),
+        ],
+    );
+
+    let frame = decode_frame(&buf).expect("valid frame");
+    assert_eq!(frame.version, LOG_FRAME_VERSION);
+    assert!(!frame.truncated);
+    assert_eq!(frame.stackmat_time, 12345);
+    assert_eq!(frame.boot_epoch_ms, Some(1_700_000_000_000));
+    assert_eq!(frame.records.len(), 3);
+
+    let first = &frame.records[0];
+    assert_eq!(first.seq, Some(0));
+    assert_eq!(first.uptime_ms, Some(1500));
+    assert_eq!(first.level, Level::Info);
+    assert_eq!(first.target, "fkm_firmware::ws");
+    assert_eq!(first.message, "connected!");
+    assert_eq!(frame.record_epoch_ms(first), Some(1_700_000_001_500));
+
+    assert_eq!(frame.records[1].level, Level::Error);
+    assert_eq!(frame.records[1].message, "OTA failed: 3");
+
+    let last = &frame.records[2];
+    assert_eq!(last.level, Level::Trace);
+    assert_eq!(last.target, "");
+    assert_eq!(last.message, "");
+}
+
+#[test]
+fn unknown_boot_epoch_and_truncated_flag() {
+    let buf = encode_frame(true, 0, 0, &[record(7, 10, 2, "t", "m")]);
+    let frame = decode_frame(&buf).expect("valid frame");
+
+    assert!(frame.truncated);
+    assert_eq!(frame.boot_epoch_ms, None);
+    assert_eq!(frame.record_epoch_ms(&frame.records[0]), None);
+}
+
+#[test]
+fn empty_frame_has_only_header() {
+    let buf = encode_frame(false, 1, 2, &[]);
+    let frame = decode_frame(&buf).expect("valid frame");
+    assert!(frame.records.is_empty());
+
+    assert_eq!(
+        decode_frame(&buf[..FRAME_HEADER_LEN - 1]),
+        Err(DecodeError::ShortHeader)
+    );
+}
+
+#[test]
+fn invalid_frames() {
+    assert_eq!(decode_frame(&[]), Err(DecodeError::Empty));
+    assert_eq!(decode_frame(b"X"), Err(DecodeError::BadMagic(b'X')));
+    assert_eq!(decode_frame(b"L"), Err(DecodeError::ShortHeader));
+    assert_eq!(
+        decode_frame(&[b'L', 9]),
+        Err(DecodeError::UnsupportedVersion(9))
+    );
+
+    let buf = encode_frame(false, 0, 0, &[record(0, 0, 3, "ws", "hello")]);
+
+    // record cut in the middle of its header and of its message
+    for len in [FRAME_HEADER_LEN + 1, FRAME_HEADER_LEN + 10, buf.len() - 1] {
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Err(DecodeError::ShortRecord {
+                offset: FRAME_HEADER_LEN
+            }),
+            "{len}"
+        );
+    }
+
+    let mut bad_level = buf.clone();
+    bad_level[FRAME_HEADER_LEN + RECORD_HEADER_LEN - 2] = 6;
+    assert_eq!(
+        decode_frame(&bad_level),
+        Err(DecodeError::BadLevel {
+            offset: FRAME_HEADER_LEN,
+            level: 6
+        })
+    );
+
+    // target length pointing past the record
+    let mut bad_target = buf;
+    bad_target[FRAME_HEADER_LEN + RECORD_HEADER_LEN - 1] = 100;
+    assert_eq!(
+        decode_frame(&bad_target),
+        Err(DecodeError::ShortRecord {
+            offset: FRAME_HEADER_LEN
+        })
+    );
+}
+
+#[test]
+fn legacy_frame() {
+    let mut buf = vec![b'L', 1];
+    buf.extend_from_slice(&500u64.to_be_bytes());
+    for line in ["I connected!", "E ws error"] {
+        buf.extend_from_slice(&(line.len() as u16).to_be_bytes());
+        buf.extend_from_slice(line.as_bytes());
+    }
+
+    let frame = decode_frame(&buf).expect("valid legacy frame");
+    assert_eq!(frame.version, 1);
+    assert!(frame.truncated);
+    assert_eq!(frame.stackmat_time, 500);
+    assert_eq!(frame.records.len(), 2);
+    assert_eq!(frame.records[0].seq, None);
+    assert_eq!(frame.records[0].level, Level::Info);
+    assert_eq!(frame.records[1].level, Level::Error);
+    assert_eq!(frame.records[1].message, "ws error");
+}
+
+#[test]
+fn sequence_gaps_across_frames() {
+    let frames = [
+        encode_frame(
+            false,
+            0,
+            0,
+            &[record(10, 0, 3, "", "a"), record(11, 0, 3, "", "b")],
+        ),
+        // 12..=14 didn't fit into buffer
+        encode_frame(true, 0, 0, &[record(15, 0, 3, "", "c")]),
+        encode_frame(false, 0, 0, &[record(16, 0, 3, "", "d")]),
+        // device rebooted
+        encode_frame(
+            false,
+            0,
+            0,
+            &[record(0, 0, 3, "", "e"), record(2, 0, 3, "", "f")],
+        ),
+    ];
+
+    let mut tracker = SequenceTracker::new();
+    let gaps: Vec<_> = frames
+        .iter()
+        .flat_map(|buf| decode_frame(buf).expect("valid frame").records)
+        .filter_map(|record| tracker.track(&record))
+        .collect();
+
+    assert_eq!(gaps, [Gap { from: 12, to: 15 }, Gap { from: 1, to: 2 }]);
+    assert_eq!(gaps[0].lost(), 3);
+}
+
+#[test]
+fn sequence_wraps_around() {
+    let buf = encode_frame(
+        false,
+        0,
+        0,
+        &[record(u32::MAX, 0, 3, "", "a"), record(0, 0, 3, "", "b")],
+    );
+
+    let mut tracker = SequenceTracker::new();
+    for record in decode_frame(&buf).expect("valid frame").records {
+        assert_eq!(tracker.track(&record), None);
+    }
+}
+
+#[test]
+fn formatting() {
+    assert_eq!(format_epoch_ms(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        format_epoch_ms(1_709_210_096_789),
+        "2024-02-29T12:34:56.789Z"
+    );
+
+    let buf = encode_frame(
+        false,
+        0,
+        1_709_210_000_000,
+        &[record(42, 96_789, 2, "ws", "slow")],
+    );
+    let frame = decode_frame(&buf).expect("valid frame");
+    assert_eq!(
+        format_record(&frame, &frame.records[0]),
+        "2024-02-29T12:34:56.789Z [    96.789] #42     WARN  ws: slow"
+    );
+}
+
+#[test]
+fn hex_input() {
+    let buf = encode_frame(false, 1, 2, &[record(0, 0, 3, "", "x")]);
+    let hex: String = buf.iter().map(|b| format!("{b:02x} ")).collect();
+
+    assert_eq!(parse_hex(&hex), Some(buf));
+    assert_eq!(parse_hex("4c0"), None);
+    assert_eq!(parse_hex("zz"), None);
+}